@@ -26,16 +26,11 @@ impl ExtractState<CounterDriver> for CounterState {
     }
 }
 
+#[derive(Default)]
 struct CounterDriver {
     value: i64,
 }
 
-impl Default for CounterDriver {
-    fn default() -> Self {
-        Self { value: 0 }
-    }
-}
-
 impl Driver for CounterDriver {
     type State = CounterState;
 
@@ -27,16 +27,11 @@ impl ExtractState<CounterDriver> for CounterState {
 }
 
 /// The Rust implementation under test.
+#[derive(Default)]
 struct CounterDriver {
     value: i64,
 }
 
-impl Default for CounterDriver {
-    fn default() -> Self {
-        Self { value: 0 }
-    }
-}
-
 impl Driver for CounterDriver {
     type State = CounterState;
 
@@ -36,7 +36,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         TraceResult::Valid => {
             println!("✓ Trace is valid! Implementation matches spec.");
         }
-        TraceResult::Invalid { reason } => {
+        TraceResult::Invalid { reason, .. } => {
             println!("✗ Trace is invalid: {reason}");
             std::process::exit(1);
         }
@@ -0,0 +1,501 @@
+//! Structured interpretation of Apalache CLI results.
+//!
+//! Apalache reports its verdict through the exit code and free-form
+//! stdout/stderr. This module turns both into an [`ApalacheOutcome`], with
+//! [`Diagnostic`]s pointing at spec locations for parse and type errors.
+//! [`check_spec`] runs only Apalache's parser and type checker, so spec
+//! mistakes surface before a long model checking run.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use tla_connect::{check_spec, SpecCheckConfig};
+//!
+//! let config = SpecCheckConfig::builder().spec("specs/Counter.tla").build()?;
+//! if let Err(e) = check_spec(&config) {
+//!     for diagnostic in e.diagnostics() {
+//!         eprintln!("{diagnostic}");
+//!     }
+//! }
+//! ```
+
+use crate::error::{ApalacheError, Error};
+use crate::runner::ApalacheRunner;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+/// Apalache exit code for a found counterexample (invariant violation or deadlock).
+const EXIT_COUNTEREXAMPLE: i32 = 12;
+
+/// Apalache exit code for a spec that failed to parse.
+const EXIT_PARSE_ERROR: i32 = 75;
+
+/// Apalache exit code for a spec that failed to type-check.
+const EXIT_TYPECHECK_ERROR: i32 = 120;
+
+/// The outcome of an Apalache run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ApalacheOutcome {
+    /// Checking completed without finding a violation.
+    NoError,
+
+    /// An invariant was violated; counterexamples were written.
+    InvariantViolated,
+
+    /// A deadlock was found.
+    Deadlock,
+
+    /// The spec could not be parsed.
+    ParseError { diagnostics: Vec<Diagnostic> },
+
+    /// The spec failed type checking.
+    TypeCheckError { diagnostics: Vec<Diagnostic> },
+
+    /// The SMT solver timed out.
+    SmtTimeout,
+
+    /// The JVM ran out of memory.
+    OutOfMemory,
+
+    /// The spec uses a construct Apalache does not support.
+    UnsupportedFeature { message: String },
+
+    /// An outcome not recognized from the exit code and output.
+    Unknown { exit_code: Option<i32> },
+}
+
+impl ApalacheOutcome {
+    /// Diagnostics attached to parse and type errors (empty otherwise).
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        match self {
+            ApalacheOutcome::ParseError { diagnostics } | ApalacheOutcome::TypeCheckError { diagnostics } => {
+                diagnostics
+            }
+            _ => &[],
+        }
+    }
+
+    /// Resolve diagnostic file names against `spec_dir`.
+    ///
+    /// Apalache runs on a staged copy of the spec directory; this points the
+    /// diagnostics back at the user's own files.
+    pub fn with_spec_dir(mut self, spec_dir: &Path) -> Self {
+        if let ApalacheOutcome::ParseError { ref mut diagnostics }
+        | ApalacheOutcome::TypeCheckError { ref mut diagnostics } = self
+        {
+            for diagnostic in diagnostics {
+                if let Some(ref file) = diagnostic.file {
+                    if let Some(name) = file.file_name() {
+                        diagnostic.file = Some(spec_dir.join(name));
+                    }
+                }
+            }
+        }
+        self
+    }
+}
+
+impl std::fmt::Display for ApalacheOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApalacheOutcome::NoError => write!(f, "no error found"),
+            ApalacheOutcome::InvariantViolated => write!(f, "invariant violated"),
+            ApalacheOutcome::Deadlock => write!(f, "deadlock"),
+            ApalacheOutcome::ParseError { diagnostics } => {
+                write!(f, "parse error")?;
+                diagnostics.iter().try_for_each(|d| write!(f, "\n  {d}"))
+            }
+            ApalacheOutcome::TypeCheckError { diagnostics } => {
+                write!(f, "type checking error")?;
+                diagnostics.iter().try_for_each(|d| write!(f, "\n  {d}"))
+            }
+            ApalacheOutcome::SmtTimeout => write!(f, "SMT solver timeout"),
+            ApalacheOutcome::OutOfMemory => write!(f, "out of memory"),
+            ApalacheOutcome::UnsupportedFeature { message } => write!(f, "unsupported feature: {message}"),
+            ApalacheOutcome::Unknown { exit_code } => write!(f, "unknown outcome (exit code: {exit_code:?})"),
+        }
+    }
+}
+
+/// A message from Apalache tied to a spec location, where known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    /// The `.tla` file the message refers to.
+    pub file: Option<PathBuf>,
+
+    /// 1-based line number.
+    pub line: Option<usize>,
+
+    /// 1-based column number.
+    pub column: Option<usize>,
+
+    /// The message text.
+    pub message: String,
+
+    /// For Snowcat type errors, the expected type (e.g. `(Int) => Bool`).
+    pub expected_type: Option<String>,
+
+    /// For Snowcat type errors, the type actually found.
+    pub found_type: Option<String>,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.file, self.line, self.column) {
+            (Some(file), Some(line), Some(column)) => write!(f, "{}:{line}:{column}: ", file.display())?,
+            (Some(file), Some(line), None) => write!(f, "{}:{line}: ", file.display())?,
+            (Some(file), None, _) => write!(f, "{}: ", file.display())?,
+            (None, Some(line), Some(column)) => write!(f, "line {line}, column {column}: ")?,
+            _ => {}
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Configuration for [`check_spec`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SpecCheckConfig {
+    /// Path to the TLA+ spec file.
+    pub spec: PathBuf,
+
+    /// Path to the Apalache binary (default: "apalache-mc").
+    /// Ignored when `runner` is set.
+    pub apalache_bin: String,
+
+    /// How to launch Apalache (optional). If None, `apalache_bin` is run
+    /// directly.
+    pub runner: Option<ApalacheRunner>,
+
+    /// Run the type checker after parsing (default: true). If false, only
+    /// `apalache-mc parse` runs.
+    pub typecheck: bool,
+
+    /// Timeout for the Apalache subprocess. If None, no timeout is applied.
+    pub timeout: Option<std::time::Duration>,
+}
+
+impl Default for SpecCheckConfig {
+    fn default() -> Self {
+        Self {
+            spec: PathBuf::new(),
+            apalache_bin: "apalache-mc".into(),
+            runner: None,
+            typecheck: true,
+            timeout: None,
+        }
+    }
+}
+
+crate::builder::impl_builder!(SpecCheckConfig, SpecCheckConfigBuilder {
+    required { spec: PathBuf }
+    optional { apalache_bin: String, typecheck: bool }
+    optional_or { timeout: std::time::Duration, runner: ApalacheRunner }
+});
+
+/// Parse and type-check a spec without model checking it.
+///
+/// Fails with [`ApalacheError::Failed`] carrying an
+/// [`ApalacheOutcome::ParseError`] or [`ApalacheOutcome::TypeCheckError`]
+/// whose diagnostics point at the spec's files; see [`Error::diagnostics`].
+#[must_use = "returns a Result that should be checked for spec errors"]
+pub fn check_spec(config: &SpecCheckConfig) -> Result<(), Error> {
+    let spec = config
+        .spec
+        .canonicalize()
+        .map_err(|_| ApalacheError::SpecNotFound(config.spec.clone()))?;
+    let spec_dir = spec.parent().unwrap_or(Path::new("."));
+    let runner = crate::runner::resolve(&config.runner, &config.apalache_bin);
+    run_spec_check(&runner, &spec, spec_dir, config.typecheck, config.timeout)?;
+    Ok(())
+}
+
+/// Run `apalache-mc typecheck` (or `parse`) on `spec`, resolving
+/// diagnostics against `spec_dir`.
+///
+/// `spec` may be a staged copy; `spec_dir` is where the user's files live.
+pub(crate) fn run_spec_check(
+    runner: &ApalacheRunner,
+    spec: &Path,
+    spec_dir: &Path,
+    typecheck: bool,
+    timeout: Option<std::time::Duration>,
+) -> Result<(), ApalacheError> {
+    // Apalache writes run logs into `--out-dir`, which would otherwise
+    // default to `_apalache-out` in the working directory.
+    let out_dir = tempfile::Builder::new()
+        .prefix("tla_check_")
+        .tempdir()
+        .map_err(|e| ApalacheError::Execution {
+            exit_code: None,
+            message: format!("failed to create output directory: {e}"),
+        })?;
+
+    let command = if typecheck { "typecheck" } else { "parse" };
+    let mut cmd = runner.checked_command()?;
+    cmd.arg(command)
+        .arg(format!("--out-dir={}", out_dir.path().display()))
+        .arg(spec);
+
+    info!(spec = %spec.display(), command, "Checking spec with Apalache");
+    debug!("Command: {:?}", cmd);
+
+    let output = crate::util::run_with_timeout(&mut cmd, timeout)?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    match parse_outcome(&stdout, &stderr, output.status.code()) {
+        ApalacheOutcome::NoError => Ok(()),
+        ApalacheOutcome::Unknown { exit_code } => Err(ApalacheError::Execution {
+            exit_code,
+            message: format!("stdout: {stdout}\nstderr: {stderr}"),
+        }),
+        outcome => Err(ApalacheError::Failed {
+            outcome: outcome.with_spec_dir(spec_dir),
+        }),
+    }
+}
+
+/// Classify an Apalache run from its exit code and output.
+pub fn parse_outcome(stdout: &str, stderr: &str, exit_code: Option<i32>) -> ApalacheOutcome {
+    let combined = format!("{stdout}\n{stderr}");
+    let lower = combined.to_lowercase();
+
+    if lower.contains("outofmemoryerror") || lower.contains("gc overhead limit exceeded") {
+        return ApalacheOutcome::OutOfMemory;
+    }
+
+    if let Some(outcome) = combined
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("The outcome is:"))
+        .map(str::trim)
+    {
+        match outcome {
+            "NoError" => return ApalacheOutcome::NoError,
+            "Deadlock" => return ApalacheOutcome::Deadlock,
+            "Error" => return ApalacheOutcome::InvariantViolated,
+            _ => {}
+        }
+    }
+
+    if lower.contains("smt timeout") || lower.contains("smt solver timed out") || lower.contains("solver timeout") {
+        return ApalacheOutcome::SmtTimeout;
+    }
+
+    let typecheck_diagnostics = parse_snowcat_diagnostics(&combined);
+    if exit_code == Some(EXIT_TYPECHECK_ERROR)
+        || lower.contains("typechecker detected")
+        || lower.contains("type checking error")
+        || lower.contains("snowcat asks you to fix the types")
+    {
+        return ApalacheOutcome::TypeCheckError {
+            diagnostics: typecheck_diagnostics,
+        };
+    }
+
+    if exit_code == Some(EXIT_PARSE_ERROR)
+        || lower.contains("parsing error")
+        || lower.contains("***parse error***")
+        || lower.contains("error by tla+ parser")
+    {
+        return ApalacheOutcome::ParseError {
+            diagnostics: parse_sany_diagnostics(&combined),
+        };
+    }
+
+    // Warnings mentioning unsupported features are common in successful
+    // runs, so only a failed run is attributed to them.
+    let succeeded = matches!(exit_code, Some(0 | EXIT_COUNTEREXAMPLE));
+    if let Some(line) = combined
+        .lines()
+        .find(|l| l.to_lowercase().contains("not supported") || l.to_lowercase().contains("unsupported"))
+        .filter(|_| !succeeded)
+    {
+        return ApalacheOutcome::UnsupportedFeature {
+            message: line.trim().to_string(),
+        };
+    }
+
+    if lower.contains("deadlock") && exit_code == Some(EXIT_COUNTEREXAMPLE) {
+        return ApalacheOutcome::Deadlock;
+    }
+
+    match exit_code {
+        Some(0) => ApalacheOutcome::NoError,
+        Some(EXIT_COUNTEREXAMPLE) => ApalacheOutcome::InvariantViolated,
+        _ => ApalacheOutcome::Unknown { exit_code },
+    }
+}
+
+/// Parse Snowcat-style locations: `[File.tla:12:5-12:19]: message`.
+fn parse_snowcat_diagnostics(output: &str) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let rest = &line[line.find('[')? + 1..];
+            let (location, message) = rest.split_once("]:")?;
+            let (file, position) = location.split_once(".tla:")?;
+            let start = position.split('-').next()?;
+            let (line_no, column) = start.split_once(':')?;
+            let message = message.trim();
+            Some(Diagnostic {
+                file: Some(PathBuf::from(format!("{}.tla", file.trim()))),
+                line: line_no.parse().ok(),
+                column: column.parse().ok(),
+                message: message.to_string(),
+                expected_type: type_after(message, "Expected"),
+                found_type: type_after(message, "Found").or_else(|| type_after(message, "found")),
+            })
+        })
+        .collect()
+}
+
+/// Extract the type following `label:` in a Snowcat message, up to the
+/// next sentence or clause.
+fn type_after(message: &str, label: &str) -> Option<String> {
+    let rest = &message[message.find(&format!("{label}:"))? + label.len() + 1..];
+    let end = [". ", ", found", ". Found"]
+        .iter()
+        .filter_map(|sep| rest.find(sep))
+        .min()
+        .unwrap_or(rest.len());
+    let ty = rest[..end].trim().trim_end_matches('.');
+    (!ty.is_empty()).then(|| ty.to_string())
+}
+
+/// Parse SANY-style locations: `line 5, col 3 to line 5, col 9 of module Foo`
+/// and `Encountered "X" at line 5, column 3`.
+fn parse_sany_diagnostics(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let lines: Vec<&str> = output.lines().collect();
+
+    for (idx, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        let (line_no, column, module) = if let Some(pos) = trimmed.find("at line ") {
+            let rest = &trimmed[pos + "at line ".len()..];
+            let (line_no, rest) = rest.split_once(", column ").unwrap_or((rest, ""));
+            let column: String = rest.chars().take_while(char::is_ascii_digit).collect();
+            (line_no.trim().parse().ok(), column.parse().ok(), None)
+        } else if let Some(rest) = trimmed.strip_prefix("line ") {
+            let Some((line_no, rest)) = rest.split_once(", col ") else {
+                continue;
+            };
+            let column: String = rest.chars().take_while(char::is_ascii_digit).collect();
+            let module = rest
+                .split(" of module ")
+                .nth(1)
+                .map(|m| m.trim().trim_end_matches(['.', ':']).to_string());
+            (line_no.trim().parse().ok(), column.parse().ok(), module)
+        } else {
+            continue;
+        };
+
+        if line_no.is_none() {
+            continue;
+        }
+
+        // SANY prints the message on the line before a bare location.
+        let message = if trimmed.starts_with("line ") && idx > 0 {
+            lines[idx - 1].trim().to_string()
+        } else {
+            trimmed.to_string()
+        };
+
+        diagnostics.push(Diagnostic {
+            file: module.map(|m| PathBuf::from(format!("{m}.tla"))),
+            line: line_no,
+            column,
+            message,
+            expected_type: None,
+            found_type: None,
+        });
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outcome_from_summary_line() {
+        assert_eq!(parse_outcome("The outcome is: NoError", "", Some(0)), ApalacheOutcome::NoError);
+        assert_eq!(
+            parse_outcome("The outcome is: Error\nEXITCODE: ERROR (12)", "", Some(12)),
+            ApalacheOutcome::InvariantViolated
+        );
+        assert_eq!(parse_outcome("The outcome is: Deadlock", "", Some(12)), ApalacheOutcome::Deadlock);
+    }
+
+    #[test]
+    fn outcome_out_of_memory() {
+        let outcome = parse_outcome("", "Exception in thread \"main\" java.lang.OutOfMemoryError: Java heap space", Some(1));
+        assert_eq!(outcome, ApalacheOutcome::OutOfMemory);
+    }
+
+    #[test]
+    fn outcome_typecheck_with_locations() {
+        let stdout = "Typing input error\n\
+                      [Counter.tla:12:5-12:19]: Mismatch in argument types. Expected: (Int) => Bool\n\
+                      Typechecker detected type errors";
+        let outcome = parse_outcome(stdout, "", Some(120));
+        let ApalacheOutcome::TypeCheckError { ref diagnostics } = outcome else {
+            panic!("expected type check error, got {outcome:?}");
+        };
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].file, Some(PathBuf::from("Counter.tla")));
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (Some(12), Some(5)));
+        assert!(diagnostics[0].message.starts_with("Mismatch in argument types"));
+
+        let resolved = outcome.with_spec_dir(Path::new("/specs"));
+        assert_eq!(resolved.diagnostics()[0].file, Some(PathBuf::from("/specs/Counter.tla")));
+        assert_eq!(
+            resolved.diagnostics()[0].to_string(),
+            "/specs/Counter.tla:12:5: Mismatch in argument types. Expected: (Int) => Bool"
+        );
+    }
+
+    #[test]
+    fn snowcat_expected_and_found_types() {
+        let stdout = "[Counter.tla:3:1-3:9]: Mismatch in argument types. Expected: (Int) => Bool. Found: (Str) => Bool";
+        let diagnostics = parse_snowcat_diagnostics(stdout);
+        assert_eq!(diagnostics[0].expected_type.as_deref(), Some("(Int) => Bool"));
+        assert_eq!(diagnostics[0].found_type.as_deref(), Some("(Str) => Bool"));
+
+        let diagnostics = parse_snowcat_diagnostics("[A.tla:1:1-1:2]: Expected: Set(Int), found: Seq(Int)");
+        assert_eq!(diagnostics[0].expected_type.as_deref(), Some("Set(Int)"));
+        assert_eq!(diagnostics[0].found_type.as_deref(), Some("Seq(Int)"));
+    }
+
+    #[test]
+    fn outcome_parse_error_with_sany_locations() {
+        let stdout = "***Parse Error***\n\
+                      Encountered \"==\" at line 7, column 12 and token \"Init\"\n\
+                      Unknown operator: `Foo'.\n\
+                      line 9, col 5 to line 9, col 7 of module Counter\n";
+        let outcome = parse_outcome(stdout, "", Some(255));
+        let diagnostics = outcome.diagnostics();
+        assert!(matches!(outcome, ApalacheOutcome::ParseError { .. }));
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (Some(7), Some(12)));
+        assert_eq!(diagnostics[1].file, Some(PathBuf::from("Counter.tla")));
+        assert_eq!(diagnostics[1].message, "Unknown operator: `Foo'.");
+    }
+
+    #[test]
+    fn outcome_unsupported_and_unknown() {
+        let outcome = parse_outcome("Operator RECURSIVE is not supported", "", Some(255));
+        assert!(matches!(outcome, ApalacheOutcome::UnsupportedFeature { .. }));
+        assert_eq!(parse_outcome("", "", Some(99)), ApalacheOutcome::Unknown { exit_code: Some(99) });
+    }
+
+    #[test]
+    fn outcome_ignores_unsupported_warnings_on_success() {
+        let warning = "WARN: Operator Print is not supported, ignoring it\nFound 1 error(s)";
+        assert_eq!(parse_outcome(warning, "", Some(12)), ApalacheOutcome::InvariantViolated);
+        assert_eq!(parse_outcome("WARN: unsupported option", "", Some(0)), ApalacheOutcome::NoError);
+    }
+}
@@ -41,7 +41,9 @@
 use crate::error::DriverError;
 use serde::de::DeserializeOwned;
 use similar::{ChangeTag, TextDiff};
+use std::collections::BTreeMap;
 use std::fmt::Debug;
+use std::sync::Arc;
 
 /// A single step from an Apalache-generated ITF trace.
 ///
@@ -60,6 +62,80 @@ pub struct Step {
     /// Full TLA+ state after this step – an `itf::Value::Record` containing
     /// all state variables. Used for state comparison via `State::from_spec`.
     pub state: itf::Value,
+
+    /// Metadata of the trace this step belongs to (None outside replay).
+    pub(crate) trace_meta: Option<Arc<TraceMeta>>,
+}
+
+impl Step {
+    /// Metadata of the ITF trace being replayed, e.g. its source or lasso
+    /// loop index. None for steps not replayed from an ITF trace.
+    pub fn trace_meta(&self) -> Option<&TraceMeta> {
+        self.trace_meta.as_deref()
+    }
+
+    /// Look up a nondeterministic pick by name.
+    ///
+    /// Reads the `name` field of `nondet_picks`. Option-style variants, as
+    /// recorded by MBT instrumentation and Quint, are unwrapped: a
+    /// `Some`-tagged value yields its payload and a `None`-tagged value
+    /// yields `None`.
+    pub fn nondet(&self, name: &str) -> Option<&itf::Value> {
+        let itf::Value::Record(ref picks) = self.nondet_picks else {
+            return None;
+        };
+        let value = picks.get(name)?;
+
+        if let itf::Value::Record(ref variant) = value {
+            if variant.len() == 2 {
+                match variant.get("tag") {
+                    Some(itf::Value::String(tag)) if tag == "Some" => return variant.get("value"),
+                    Some(itf::Value::String(tag)) if tag == "None" => return None,
+                    _ => {}
+                }
+            }
+        }
+
+        Some(value)
+    }
+}
+
+/// Trace-level metadata of an ITF trace (its `#meta`, `params` and `loop`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TraceMeta {
+    /// Tool or file the trace came from.
+    pub source: Option<String>,
+
+    /// Free-form description of the trace.
+    pub description: Option<String>,
+
+    /// Variable types, keyed by variable name (`varTypes`).
+    pub var_types: BTreeMap<String, String>,
+
+    /// Names of the trace's parameters (CONSTANTS).
+    pub params: Vec<String>,
+
+    /// For lasso-shaped traces, such as liveness counterexamples, the index
+    /// of the state the last state loops back to.
+    pub loop_index: Option<usize>,
+
+    /// Any other `#meta` fields.
+    pub other: BTreeMap<String, String>,
+}
+
+impl TraceMeta {
+    /// Metadata of `trace`.
+    pub fn from_trace<S>(trace: &itf::Trace<S>) -> Self {
+        Self {
+            source: trace.meta.source.clone(),
+            description: trace.meta.description.clone(),
+            var_types: trace.meta.var_types.clone(),
+            params: trace.params.clone(),
+            loop_index: trace.loop_index.and_then(|i| usize::try_from(i).ok()),
+            other: trace.meta.other.clone(),
+        }
+    }
 }
 
 /// Core trait for connecting Rust implementations to TLA+ specs.
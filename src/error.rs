@@ -10,7 +10,7 @@ use thiserror::Error;
 /// Shared error for Apalache CLI execution failures.
 ///
 /// Used by both `TraceGenError` and `ValidationError` to avoid duplication.
-#[cfg(any(feature = "trace-gen", feature = "trace-validation"))]
+#[cfg(any(feature = "trace-gen", feature = "trace-validation", feature = "rpc"))]
 #[derive(Debug, Error)]
 #[non_exhaustive]
 pub enum ApalacheError {
@@ -18,6 +18,11 @@ pub enum ApalacheError {
     #[error("Apalache failed (exit code: {exit_code:?}): {message}")]
     Execution { exit_code: Option<i32>, message: String },
 
+    /// Apalache ran but reported a failure: a parse or type error, an SMT
+    /// timeout, running out of memory or an unsupported construct.
+    #[error("Apalache failed: {outcome}")]
+    Failed { outcome: crate::apalache::ApalacheOutcome },
+
     /// Apalache binary not found or not executable.
     #[error("Failed to execute Apalache. Is it installed and on PATH? {0}")]
     NotFound(String),
@@ -25,6 +30,32 @@ pub enum ApalacheError {
     /// Apalache timed out after the specified duration.
     #[error("Apalache timed out after {duration:?}")]
     Timeout { duration: std::time::Duration },
+
+    /// The spec passed to Apalache does not exist.
+    #[error("Spec not found: {0}")]
+    SpecNotFound(PathBuf),
+
+    /// The installed Apalache is older than the required version.
+    #[error("Apalache {found} is not supported: version {required} or newer is required")]
+    UnsupportedVersion {
+        found: crate::runner::ApalacheVersion,
+        required: crate::runner::ApalacheVersion,
+    },
+
+    /// The output of `apalache version` could not be parsed.
+    #[error("Could not determine Apalache version from: {0:?}")]
+    UnknownVersion(String),
+}
+
+#[cfg(any(feature = "trace-gen", feature = "trace-validation", feature = "rpc"))]
+impl ApalacheError {
+    /// Parse or type error diagnostics, if Apalache rejected the spec.
+    pub fn diagnostics(&self) -> &[crate::apalache::Diagnostic] {
+        match self {
+            ApalacheError::Failed { outcome } => outcome.diagnostics(),
+            _ => &[],
+        }
+    }
 }
 
 /// Shared error for directory read failures.
@@ -98,6 +129,12 @@ pub enum Error {
     #[error("Trace validation error: {0}")]
     Validation(#[from] ValidationError),
 
+    /// Apalache failed outside trace generation or validation, e.g. in
+    /// [`check_spec`](crate::check_spec).
+    #[cfg(any(feature = "trace-gen", feature = "trace-validation", feature = "rpc"))]
+    #[error("Apalache error: {0}")]
+    Apalache(#[from] ApalacheError),
+
     /// Error during RPC communication with Apalache server.
     #[cfg(feature = "rpc")]
     #[error("RPC error: {0}")]
@@ -123,6 +160,28 @@ pub enum Error {
     /// JSON serialization/deserialization error.
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
+
+    /// Error encoding a value as ITF.
+    #[error("ITF encoding error: {0}")]
+    Itf(#[from] ItfError),
+}
+
+#[cfg(any(feature = "trace-gen", feature = "trace-validation", feature = "rpc"))]
+impl Error {
+    /// Parse or type error diagnostics from Apalache, wherever the
+    /// [`ApalacheError`] is nested (empty for other errors).
+    pub fn diagnostics(&self) -> &[crate::apalache::Diagnostic] {
+        match self {
+            Error::Apalache(e) => e.diagnostics(),
+            #[cfg(feature = "trace-gen")]
+            Error::TraceGen(TraceGenError::Apalache(e)) => e.diagnostics(),
+            #[cfg(feature = "trace-validation")]
+            Error::Validation(ValidationError::Apalache(e)) => e.diagnostics(),
+            #[cfg(feature = "rpc")]
+            Error::Rpc(RpcError::Server(e)) => e.diagnostics(),
+            _ => &[],
+        }
+    }
 }
 
 /// Error during ITF trace replay.
@@ -146,6 +205,22 @@ pub enum ReplayError {
     #[error("Failed to parse ITF trace: {0}")]
     Parse(String),
 
+    /// Replay failed for traces generated under a constant assignment.
+    #[error("With constants {{{constants}}}: {source}")]
+    WithConstants {
+        constants: String,
+        #[source]
+        source: Box<Error>,
+    },
+
+    /// Replay failed for traces generated by a seeded `simulate` run.
+    #[error("{source}\n(traces generated with seed {seed}; set ApalacheConfig::seed to reproduce)")]
+    WithSeed {
+        seed: u64,
+        #[source]
+        source: Box<Error>,
+    },
+
     /// Directory read error.
     #[error(transparent)]
     DirectoryRead(#[from] DirectoryReadError),
@@ -168,6 +243,31 @@ pub enum TraceGenError {
     #[error(transparent)]
     Apalache(#[from] ApalacheError),
 
+    /// The model checker found no violation of the invariant, so no traces exist.
+    #[error("No violation of invariant '{inv}' was found, so no traces were generated")]
+    NoViolation { inv: String },
+
+    /// TLC failed to start, timed out or did not produce a usable trace.
+    #[error("TLC failed (exit code {exit_code:?}): {message}")]
+    Tlc { exit_code: Option<i32>, message: String },
+
+    /// Quint failed to start, timed out or wrote no traces.
+    #[error("Quint failed (exit code {exit_code:?}): {message}")]
+    Quint { exit_code: Option<i32>, message: String },
+
+    /// A worker of parallel trace generation failed.
+    #[error("Worker {worker} (seed {seed:?}) failed: {source}")]
+    Worker {
+        worker: usize,
+        seed: Option<u64>,
+        #[source]
+        source: Box<Error>,
+    },
+
+    /// Coverage-guided generation found no trace for these actions.
+    #[error("Actions unreachable within the configured trace length: {}", .0.join(", "))]
+    UnreachableActions(Vec<String>),
+
     /// No ITF traces found in output.
     #[error("No ITF traces found in Apalache output directory: {0}")]
     NoTracesFound(PathBuf),
@@ -176,6 +276,18 @@ pub enum TraceGenError {
     #[error("Failed to parse ITF trace {path}: {reason}")]
     TraceParse { path: PathBuf, reason: String },
 
+    /// Invalid MBT instrumentation settings.
+    #[error("Invalid MBT instrumentation: {0}")]
+    Instrumentation(String),
+
+    /// Failed to read or write the trace cache.
+    #[error("Trace cache error at {path}: {reason}")]
+    Cache { path: PathBuf, reason: String },
+
+    /// Failed to stage the spec and its generated wrapper module.
+    #[error("Failed to stage spec file {path}: {reason}")]
+    SpecStaging { path: PathBuf, reason: String },
+
     /// Directory read error.
     #[error(transparent)]
     DirectoryRead(#[from] DirectoryReadError),
@@ -207,7 +319,10 @@ pub enum ValidationError {
     NonObjectState { found: String },
 
     /// Inconsistent record schema across trace lines.
-    #[error("Inconsistent record schema: line {line} has keys {found:?}, expected {expected:?}")]
+    #[error(
+        "Inconsistent record schema: line {line} has keys {found:?}, expected {expected:?}; \
+         set `TraceValidatorConfig::partial_observations` to allow missing fields"
+    )]
     InconsistentSchema {
         line: usize,
         expected: Vec<String>,
@@ -250,10 +365,30 @@ pub enum ValidationError {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
+    /// The TraceSpec could not be generated.
+    #[error("Cannot generate TraceSpec: {0}")]
+    TraceSpecGen(String),
+
+    /// The trace is compressed in a format whose feature is not enabled.
+    #[error("Trace is {0}-compressed; enable the `{0}` feature to read it")]
+    CompressedTrace(&'static str),
+
+    /// Per-node traces could not be merged.
+    #[error("Cannot merge traces: {0}")]
+    TraceMerge(String),
+
     /// Cannot emit after StateEmitter has been finished.
     #[error("Cannot emit after StateEmitter has been finished")]
     EmitterFinished,
 
+    /// A state recorded by `StateRecorder` has a field the recorder adds.
+    #[error("State field '{0}' is reserved by the StateRecorder; rename it or the recorder's field")]
+    ReservedField(String),
+
+    /// A `tracing` state event has neither an action field nor a `name:`.
+    #[error("State event at {location} has no action: set its `{action_field}` field or `name:`")]
+    MissingAction { location: String, action_field: String },
+
     /// Inconsistent array element types.
     #[error("Inconsistent array element types at field '{field}': expected {expected}, got {found}")]
     InconsistentArrayType {
@@ -261,6 +396,27 @@ pub enum ValidationError {
         expected: String,
         found: String,
     },
+
+    /// A top-level field is `null` in some rows but holds non-string values
+    /// in others, and partial observations are not enabled.
+    #[error(
+        "Field '{field}' is null in some rows and not in others (line {line}); \
+         set `TraceValidatorConfig::partial_observations` to encode it as Some/None"
+    )]
+    PartialObservation { line: usize, field: String },
+
+    /// A trace row's value does not match the type of other rows or of the schema.
+    #[error("Type conflict at line {line}, field '{field}': expected {expected}, found {found}")]
+    TypeConflict {
+        line: usize,
+        field: String,
+        expected: String,
+        found: String,
+    },
+
+    /// The trace schema is not a valid Snowcat record type.
+    #[error("Invalid trace schema: {0}")]
+    InvalidSchema(String),
 }
 
 /// Error during RPC communication with Apalache server.
@@ -276,6 +432,10 @@ pub enum RpcError {
     #[error("Failed to send request to {url}. Is the Apalache server running? {reason}")]
     RequestFailed { url: String, reason: String },
 
+    /// Failed to start or stop a managed Apalache server.
+    #[error("Apalache server error: {0}")]
+    Server(#[from] ApalacheError),
+
     /// Failed to parse JSON-RPC response.
     #[error("Failed to parse JSON-RPC response: {0}")]
     ResponseParse(String),
@@ -330,6 +490,29 @@ pub enum BuilderError {
     MissingRequiredField { builder: &'static str, field: &'static str },
 }
 
+/// Error encoding a Rust value as ITF.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ItfError {
+    /// The value has no ITF representation, e.g. a float.
+    #[error("A {0} cannot be represented in ITF")]
+    Unsupported(&'static str),
+
+    /// A recorded state did not serialize to a record.
+    #[error("ITF states must be records, got: {0}")]
+    NonRecordState(String),
+
+    /// Error reported by a `Serialize` implementation.
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl serde::ser::Error for ItfError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        ItfError::Custom(msg.to_string())
+    }
+}
+
 /// Error during driver step execution.
 #[derive(Debug, Error)]
 #[non_exhaustive]
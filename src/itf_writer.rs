@@ -0,0 +1,759 @@
+//! ITF output: serialize Rust states and traces to ITF.
+//!
+//! [`to_itf_value`] converts any `Serialize` value into an [`itf::Value`]
+//! using the ITF encodings, and [`ItfTraceWriter`] records a sequence of
+//! such states with their action labels as an ITF trace. The result can be
+//! opened in ITF viewers or diffed against traces generated from the spec.
+//!
+//! | Rust (serde)                  | ITF                                   |
+//! |-------------------------------|---------------------------------------|
+//! | `bool`                        | boolean                               |
+//! | integers                      | `{"#bigint": "..."}`                  |
+//! | `String`, `char`              | string                                |
+//! | `Vec`, slices                 | list                                  |
+//! | tuples, tuple structs         | `{"#tup": [...]}`                     |
+//! | sets, with [`as_set`]         | `{"#set": [...]}`                     |
+//! | maps                          | `{"#map": [[k, v], ...]}`             |
+//! | structs                       | record                                |
+//! | `Option`, enums               | `{"tag": "...", "value": ...}` variant|
+//!
+//! Floats have no TLA+ counterpart and are rejected. `itf::Value` itself
+//! serializes to its JSON form, so it should not be nested in recorded
+//! states.
+//!
+//! The `serialize_with` helpers [`as_set`], [`as_map`], [`as_tuple`] and
+//! [`as_bigint`] produce the same encodings under `serde_json`, so
+//! [`StateEmitter`](crate::StateEmitter) records sets, functions, tuples
+//! and big integers that trace validation converts to their TLA+ forms.
+//! For enums, `#[serde(tag = "tag", content = "value")]` gives the variant
+//! encoding under both.
+//!
+//! # Example
+//!
+//! ```
+//! use serde::Serialize;
+//! use std::collections::BTreeSet;
+//! use tla_connect::ItfTraceWriter;
+//!
+//! #[derive(Serialize)]
+//! struct Bank {
+//!     balance: u64,
+//!     #[serde(serialize_with = "tla_connect::itf_writer::as_set")]
+//!     accounts: BTreeSet<String>,
+//! }
+//!
+//! let mut writer = ItfTraceWriter::new().source("bank_impl");
+//! writer.record("init", &Bank { balance: 0, accounts: BTreeSet::new() })?;
+//! writer.record("deposit", &Bank { balance: 5, accounts: ["alice".into()].into() })?;
+//!
+//! let trace = writer.trace();
+//! assert_eq!(trace.vars, ["accounts", "balance"]);
+//! assert_eq!(trace.states.len(), 2);
+//! # Ok::<(), tla_connect::Error>(())
+//! ```
+
+use crate::driver::{Driver, ExtractState};
+use crate::error::{Error, ItfError};
+use serde::ser::{self, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Newtype name marking a value in its ITF JSON encoding, a single-entry
+/// map such as `{"#set": [...]}`, which [`ItfSerializer`] decodes.
+const ENCODED_TOKEN: &str = "$tla_connect::itf_writer::Encoded";
+
+/// Serialize a collection as an ITF set (`{"#set": [...]}`) rather than a
+/// list.
+///
+/// Use with `#[serde(serialize_with = "tla_connect::itf_writer::as_set")]`
+/// on `HashSet`/`BTreeSet` fields. The encoding is the same for
+/// [`to_itf_value`] and for `serde_json`, so
+/// [`StateEmitter`](crate::StateEmitter) records TLA+ sets.
+pub fn as_set<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize + ?Sized,
+    S: ser::Serializer,
+{
+    serializer.serialize_newtype_struct(ENCODED_TOKEN, &Encoded("#set", value))
+}
+
+/// Serialize a map as an ITF map (`{"#map": [[k, v], ...]}`), a TLA+
+/// function.
+///
+/// [`to_itf_value`] already encodes maps this way; with `serde_json`, this
+/// keeps non-string keys intact instead of turning the map into a record.
+pub fn as_map<'a, M, K, V, S>(value: &'a M, serializer: S) -> Result<S::Ok, S::Error>
+where
+    &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+    K: Serialize + 'a,
+    V: Serialize + 'a,
+    S: ser::Serializer,
+{
+    serializer.serialize_newtype_struct(ENCODED_TOKEN, &Encoded("#map", &Pairs(value)))
+}
+
+/// Serialize a tuple as an ITF tuple (`{"#tup": [...]}`) rather than a list.
+pub fn as_tuple<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize + ?Sized,
+    S: ser::Serializer,
+{
+    serializer.serialize_newtype_struct(ENCODED_TOKEN, &Encoded("#tup", value))
+}
+
+/// Serialize an integer as an ITF big integer (`{"#bigint": "..."}`), for
+/// values outside the range JSON numbers represent exactly.
+pub fn as_bigint<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: std::fmt::Display + ?Sized,
+    S: ser::Serializer,
+{
+    serializer.serialize_newtype_struct(ENCODED_TOKEN, &Encoded("#bigint", &value.to_string()))
+}
+
+/// A value in ITF JSON form: `{key: value}`.
+struct Encoded<'a, T: ?Sized>(&'static str, &'a T);
+
+impl<T: Serialize + ?Sized> Serialize for Encoded<'_, T> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(self.0, self.1)?;
+        map.end()
+    }
+}
+
+/// A map's entries as a sequence of `[key, value]` pairs.
+struct Pairs<'a, M>(&'a M);
+
+impl<'a, M, K, V> Serialize for Pairs<'a, M>
+where
+    &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+    K: Serialize + 'a,
+    V: Serialize + 'a,
+{
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0)
+    }
+}
+
+/// Decode an [`Encoded`] value that [`ItfSerializer`] serialized as a
+/// single-entry map.
+fn decode(value: itf::Value) -> Result<itf::Value, ItfError> {
+    let itf::Value::Map(map) = value else {
+        return Ok(value);
+    };
+    let mut entries = map.into_iter();
+    let (Some((itf::Value::String(key), value)), None) = (entries.next(), entries.next()) else {
+        return Err(ItfError::Custom("malformed ITF encoding".to_string()));
+    };
+    let elems = |value: itf::Value| match value {
+        itf::Value::List(elems) => Ok(elems),
+        itf::Value::Tuple(elems) => Ok(elems.into_iter().collect()),
+        other => Err(ItfError::Custom(format!("{key} must hold a sequence, got {other:?}"))),
+    };
+    match key.as_str() {
+        "#set" => Ok(itf::Value::Set(elems(value)?.into_iter().collect())),
+        "#tup" => Ok(itf::Value::Tuple(elems(value)?.into())),
+        "#map" => {
+            let mut map = itf::value::Map::new();
+            for pair in elems(value)? {
+                let mut pair = elems(pair)?.into_iter();
+                let (Some(k), Some(v), None) = (pair.next(), pair.next(), pair.next()) else {
+                    return Err(ItfError::Custom("#map entries must be [key, value] pairs".to_string()));
+                };
+                map.insert(k, v);
+            }
+            Ok(itf::Value::Map(map))
+        }
+        "#bigint" => match value {
+            itf::Value::String(ref n) => n
+                .parse::<i128>()
+                .map(int)
+                .or_else(|_| n.parse::<u128>().map(|n| itf::Value::BigInt(itf::value::BigInt::new(n))))
+                .map_err(|_| ItfError::Custom(format!("#bigint must hold an integer, got {n:?}"))),
+            other => Err(ItfError::Custom(format!("#bigint must hold a string, got {other:?}"))),
+        },
+        _ => Err(ItfError::Custom(format!("unknown ITF encoding {key}"))),
+    }
+}
+
+/// Convert a serializable value into an ITF value.
+pub fn to_itf_value<T: Serialize + ?Sized>(value: &T) -> Result<itf::Value, ItfError> {
+    value.serialize(ItfSerializer)
+}
+
+/// Records states and action labels as an ITF trace.
+///
+/// Each state must serialize to a record; its fields become the trace's
+/// variables. The action label is stored in the state's `#meta` as
+/// `action`, which replay reads back as the step's action.
+#[derive(Debug, Clone)]
+pub struct ItfTraceWriter {
+    meta: itf::trace::Meta,
+    vars: Vec<String>,
+    states: Vec<itf::state::State<itf::Value>>,
+}
+
+impl Default for ItfTraceWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ItfTraceWriter {
+    /// Create an empty trace.
+    pub fn new() -> Self {
+        Self {
+            meta: itf::trace::Meta {
+                format: Some("ITF".to_string()),
+                format_description: Some("https://apalache-mc.org/docs/adr/015adr-trace.html".to_string()),
+                timestamp: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .ok()
+                    .map(|d| d.as_secs()),
+                ..Default::default()
+            },
+            vars: Vec::new(),
+            states: Vec::new(),
+        }
+    }
+
+    /// Set the trace's `source` metadata, e.g. the implementation's name.
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.meta.source = Some(source.into());
+        self
+    }
+
+    /// Set the trace's `description` metadata.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.meta.description = Some(description.into());
+        self
+    }
+
+    /// Record a state reached by `action`.
+    pub fn record<S: Serialize + ?Sized>(&mut self, action: &str, state: &S) -> Result<(), Error> {
+        self.record_value(action, to_itf_value(state)?)
+    }
+
+    /// Record the state of `driver`, as extracted by its `State` type.
+    pub fn record_driver<D>(&mut self, action: &str, driver: &D) -> Result<(), Error>
+    where
+        D: Driver,
+        D::State: Serialize,
+    {
+        let state = <D::State as ExtractState<D>>::from_driver(driver)?;
+        self.record(action, &state)
+    }
+
+    /// Record a state already encoded as an ITF record.
+    pub fn record_value(&mut self, action: &str, state: itf::Value) -> Result<(), Error> {
+        let itf::Value::Record(ref fields) = state else {
+            return Err(ItfError::NonRecordState(format!("{state:?}")).into());
+        };
+        for (name, _) in fields.iter() {
+            if !self.vars.contains(name) {
+                self.vars.push(name.clone());
+            }
+        }
+
+        let mut other = BTreeMap::new();
+        other.insert("action".to_string(), action.to_string());
+        self.states.push(itf::state::State {
+            meta: itf::state::Meta {
+                index: Some(self.states.len() as u64),
+                other,
+            },
+            value: state,
+        });
+        Ok(())
+    }
+
+    /// Number of recorded states.
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Whether no state has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// The recorded trace.
+    pub fn trace(&self) -> itf::Trace<itf::Value> {
+        itf::Trace {
+            meta: self.meta.clone(),
+            params: Vec::new(),
+            vars: self.vars.clone(),
+            loop_index: None,
+            states: self.states.clone(),
+        }
+    }
+
+    /// The recorded trace as ITF JSON.
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(&self.trace())?)
+    }
+
+    /// Write the recorded trace to `path`, conventionally `*.itf.json`.
+    pub fn write_to(&self, path: &Path) -> Result<(), Error> {
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+}
+
+fn variant(tag: &str, value: itf::Value) -> itf::Value {
+    itf::Value::Record(
+        [
+            ("tag".to_string(), itf::Value::String(tag.to_string())),
+            ("value".to_string(), value),
+        ]
+        .into_iter()
+        .collect(),
+    )
+}
+
+fn unit() -> itf::Value {
+    itf::Value::Tuple(Vec::new().into())
+}
+
+fn int(n: impl Into<i128>) -> itf::Value {
+    itf::Value::BigInt(itf::value::BigInt::new(n.into()))
+}
+
+/// A serde `Serializer` producing [`itf::Value`]s.
+struct ItfSerializer;
+
+impl ser::Serializer for ItfSerializer {
+    type Ok = itf::Value;
+    type Error = ItfError;
+    type SerializeSeq = SeqBuilder;
+    type SerializeTuple = SeqBuilder;
+    type SerializeTupleStruct = SeqBuilder;
+    type SerializeTupleVariant = SeqBuilder;
+    type SerializeMap = MapBuilder;
+    type SerializeStruct = RecordBuilder;
+    type SerializeStructVariant = RecordBuilder;
+
+    fn serialize_bool(self, v: bool) -> Result<itf::Value, ItfError> {
+        Ok(itf::Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<itf::Value, ItfError> {
+        Ok(int(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<itf::Value, ItfError> {
+        Ok(int(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<itf::Value, ItfError> {
+        Ok(int(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<itf::Value, ItfError> {
+        Ok(int(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<itf::Value, ItfError> {
+        Ok(int(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<itf::Value, ItfError> {
+        Ok(int(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<itf::Value, ItfError> {
+        Ok(int(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<itf::Value, ItfError> {
+        Ok(int(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<itf::Value, ItfError> {
+        Ok(int(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<itf::Value, ItfError> {
+        Ok(itf::Value::BigInt(itf::value::BigInt::new(v)))
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<itf::Value, ItfError> {
+        Err(ItfError::Unsupported("floating point number"))
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<itf::Value, ItfError> {
+        Err(ItfError::Unsupported("floating point number"))
+    }
+
+    fn serialize_char(self, v: char) -> Result<itf::Value, ItfError> {
+        Ok(itf::Value::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<itf::Value, ItfError> {
+        Ok(itf::Value::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<itf::Value, ItfError> {
+        Ok(itf::Value::List(v.iter().map(|&b| int(b)).collect()))
+    }
+
+    fn serialize_none(self) -> Result<itf::Value, ItfError> {
+        Ok(variant("None", unit()))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<itf::Value, ItfError> {
+        Ok(variant("Some", value.serialize(self)?))
+    }
+
+    fn serialize_unit(self) -> Result<itf::Value, ItfError> {
+        Ok(unit())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<itf::Value, ItfError> {
+        Ok(unit())
+    }
+
+    fn serialize_unit_variant(self, _: &'static str, _: u32, variant_name: &'static str) -> Result<itf::Value, ItfError> {
+        Ok(variant(variant_name, unit()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, name: &'static str, value: &T) -> Result<itf::Value, ItfError> {
+        let value = value.serialize(self)?;
+        if name == ENCODED_TOKEN {
+            decode(value)
+        } else {
+            Ok(value)
+        }
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant_name: &'static str,
+        value: &T,
+    ) -> Result<itf::Value, ItfError> {
+        Ok(variant(variant_name, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqBuilder, ItfError> {
+        Ok(SeqBuilder::new(len, false, None))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqBuilder, ItfError> {
+        Ok(SeqBuilder::new(Some(len), true, None))
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, len: usize) -> Result<SeqBuilder, ItfError> {
+        Ok(SeqBuilder::new(Some(len), true, None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant_name: &'static str,
+        len: usize,
+    ) -> Result<SeqBuilder, ItfError> {
+        Ok(SeqBuilder::new(Some(len), true, Some(variant_name)))
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<MapBuilder, ItfError> {
+        Ok(MapBuilder {
+            map: itf::value::Map::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<RecordBuilder, ItfError> {
+        Ok(RecordBuilder {
+            fields: BTreeMap::new(),
+            variant: None,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant_name: &'static str,
+        _: usize,
+    ) -> Result<RecordBuilder, ItfError> {
+        Ok(RecordBuilder {
+            fields: BTreeMap::new(),
+            variant: Some(variant_name),
+        })
+    }
+}
+
+/// Builds lists, tuples and tuple variants.
+struct SeqBuilder {
+    items: Vec<itf::Value>,
+    tuple: bool,
+    variant: Option<&'static str>,
+}
+
+impl SeqBuilder {
+    fn new(len: Option<usize>, tuple: bool, variant: Option<&'static str>) -> Self {
+        Self {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            tuple,
+            variant,
+        }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ItfError> {
+        self.items.push(to_itf_value(value)?);
+        Ok(())
+    }
+
+    fn finish(self) -> itf::Value {
+        let value = if self.tuple {
+            itf::Value::Tuple(self.items.into())
+        } else {
+            itf::Value::List(self.items)
+        };
+        match self.variant {
+            Some(tag) => variant(tag, value),
+            None => value,
+        }
+    }
+}
+
+impl ser::SerializeSeq for SeqBuilder {
+    type Ok = itf::Value;
+    type Error = ItfError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ItfError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<itf::Value, ItfError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for SeqBuilder {
+    type Ok = itf::Value;
+    type Error = ItfError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ItfError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<itf::Value, ItfError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqBuilder {
+    type Ok = itf::Value;
+    type Error = ItfError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ItfError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<itf::Value, ItfError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqBuilder {
+    type Ok = itf::Value;
+    type Error = ItfError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ItfError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<itf::Value, ItfError> {
+        Ok(self.finish())
+    }
+}
+
+/// Builds ITF maps (`#map`) from serde maps.
+struct MapBuilder {
+    map: itf::value::Map<itf::Value, itf::Value>,
+    key: Option<itf::Value>,
+}
+
+impl ser::SerializeMap for MapBuilder {
+    type Ok = itf::Value;
+    type Error = ItfError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), ItfError> {
+        self.key = Some(to_itf_value(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ItfError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ItfError::Custom("map value serialized before its key".to_string()))?;
+        self.map.insert(key, to_itf_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<itf::Value, ItfError> {
+        Ok(itf::Value::Map(self.map))
+    }
+}
+
+/// Builds records from structs and struct variants.
+struct RecordBuilder {
+    fields: BTreeMap<String, itf::Value>,
+    variant: Option<&'static str>,
+}
+
+impl RecordBuilder {
+    fn finish(self) -> itf::Value {
+        let record = itf::Value::Record(self.fields.into());
+        match self.variant {
+            Some(tag) => variant(tag, record),
+            None => record,
+        }
+    }
+}
+
+impl ser::SerializeStruct for RecordBuilder {
+    type Ok = itf::Value;
+    type Error = ItfError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), ItfError> {
+        self.fields.insert(key.to_string(), to_itf_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<itf::Value, ItfError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStructVariant for RecordBuilder {
+    type Ok = itf::Value;
+    type Error = ItfError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), ItfError> {
+        self.fields.insert(key.to_string(), to_itf_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<itf::Value, ItfError> {
+        Ok(self.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+    use std::collections::{BTreeSet, HashMap};
+
+    #[derive(Serialize)]
+    enum Mode {
+        Idle,
+        Busy { job: u32 },
+    }
+
+    #[derive(Serialize)]
+    struct Sample {
+        count: i64,
+        name: String,
+        pair: (u8, bool),
+        log: Vec<u8>,
+        #[serde(serialize_with = "as_set")]
+        ids: BTreeSet<u32>,
+        owners: HashMap<u32, String>,
+        mode: Mode,
+        idle: Mode,
+        last: Option<u32>,
+    }
+
+    #[test]
+    fn encodes_itf_forms() {
+        let sample = Sample {
+            count: -3,
+            name: "a".into(),
+            pair: (1, true),
+            log: vec![7],
+            ids: [2].into(),
+            owners: [(1, "x".to_string())].into(),
+            mode: Mode::Busy { job: 4 },
+            idle: Mode::Idle,
+            last: None,
+        };
+
+        let json = serde_json::to_value(to_itf_value(&sample).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "count": {"#bigint": "-3"},
+                "name": "a",
+                "pair": {"#tup": [{"#bigint": "1"}, true]},
+                "log": [{"#bigint": "7"}],
+                "ids": {"#set": [{"#bigint": "2"}]},
+                "owners": {"#map": [[{"#bigint": "1"}, "x"]]},
+                "mode": {"tag": "Busy", "value": {"job": {"#bigint": "4"}}},
+                "idle": {"tag": "Idle", "value": {"#tup": []}},
+                "last": {"tag": "None", "value": {"#tup": []}},
+            })
+        );
+    }
+
+    #[derive(Serialize)]
+    struct Helpers {
+        #[serde(serialize_with = "as_set")]
+        ids: BTreeSet<u32>,
+        #[serde(serialize_with = "as_map")]
+        owners: BTreeMap<u32, String>,
+        #[serde(serialize_with = "as_tuple")]
+        pair: (u8, bool),
+        #[serde(serialize_with = "as_bigint")]
+        big: u128,
+    }
+
+    #[test]
+    fn helpers_encode_the_same_under_serde_json() {
+        let helpers = Helpers {
+            ids: [2].into(),
+            owners: [(1, "x".to_string())].into(),
+            pair: (1, true),
+            big: u128::MAX,
+        };
+
+        let json = serde_json::to_value(&helpers).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "ids": {"#set": [2]},
+                "owners": {"#map": [[1, "x"]]},
+                "pair": {"#tup": [1, true]},
+                "big": {"#bigint": u128::MAX.to_string()},
+            })
+        );
+
+        let itf = serde_json::to_value(to_itf_value(&helpers).unwrap()).unwrap();
+        assert_eq!(
+            itf,
+            serde_json::json!({
+                "ids": {"#set": [{"#bigint": "2"}]},
+                "owners": {"#map": [[{"#bigint": "1"}, "x"]]},
+                "pair": {"#tup": [{"#bigint": "1"}, true]},
+                "big": {"#bigint": u128::MAX.to_string()},
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_floats_and_non_record_states() {
+        assert!(matches!(to_itf_value(&1.5f64), Err(ItfError::Unsupported(_))));
+
+        let mut writer = ItfTraceWriter::new();
+        assert!(writer.record("init", &5u32).is_err());
+        assert!(writer.is_empty());
+    }
+}
@@ -26,6 +26,9 @@
 //! - `trace-validation` (default): Post-hoc NDJSON trace validation
 //! - `rpc`: Interactive symbolic testing via Apalache JSON-RPC
 //! - `parallel`: Parallel trace replay using rayon
+//! - `async`: Async trace generation and validation via `tokio::process`
+//! - `tracing-layer`: `tracing_subscriber` layer recording NDJSON states
+//! - `gzip`, `zstd`: Compressed NDJSON trace sinks and inputs
 //! - `full`: Enable all features
 //!
 //! # Quick Start (Approach 1)
@@ -61,9 +64,13 @@
 //! replay_traces(|| MyDriver::default(), &traces.traces)?;
 //! ```
 
+#[cfg(any(feature = "trace-gen", feature = "trace-validation", feature = "rpc"))]
+pub mod apalache;
+
 mod builder;
 pub mod driver;
 pub mod error;
+pub mod itf_writer;
 
 #[cfg(feature = "replay")]
 pub mod replay;
@@ -71,20 +78,31 @@ pub mod replay;
 #[cfg(feature = "rpc")]
 pub mod rpc;
 
+#[cfg(any(feature = "trace-gen", feature = "trace-validation", feature = "rpc"))]
+pub mod runner;
+
 #[cfg(feature = "trace-gen")]
 pub mod trace_gen;
 
 #[cfg(feature = "trace-validation")]
 pub mod trace_validation;
 
+#[cfg(any(feature = "trace-gen", feature = "trace-validation"))]
+mod tla;
+
 mod util;
 
 // Re-export core types (always available)
-pub use driver::{debug_diff, Driver, ExtractState, State, Step};
-pub use error::{BuilderError, DriverError, Error, TlaResult};
+pub use driver::{debug_diff, Driver, ExtractState, State, Step, TraceMeta};
+pub use error::{BuilderError, DriverError, Error, ItfError, TlaResult};
+pub use itf_writer::{to_itf_value, ItfTraceWriter};
 
-#[cfg(any(feature = "trace-gen", feature = "trace-validation"))]
+#[cfg(any(feature = "trace-gen", feature = "trace-validation", feature = "rpc"))]
+pub use apalache::{check_spec, ApalacheOutcome, Diagnostic, SpecCheckConfig, SpecCheckConfigBuilder};
+#[cfg(any(feature = "trace-gen", feature = "trace-validation", feature = "rpc"))]
 pub use error::ApalacheError;
+#[cfg(any(feature = "trace-gen", feature = "trace-validation", feature = "rpc"))]
+pub use runner::{ApalacheRunner, ApalacheVersion, Launcher};
 #[cfg(any(feature = "replay", feature = "trace-gen"))]
 pub use error::DirectoryReadError;
 #[cfg(feature = "replay")]
@@ -99,30 +117,53 @@ pub use error::{StepContext, StepError};
 // Re-export replay types
 #[cfg(feature = "replay")]
 pub use replay::{
-    replay_trace_str, replay_traces, replay_traces_with_progress, ReplayProgress, ReplayProgressFn,
-    ReplayStats,
+    replay_trace_str, replay_trace_stream, replay_traces, replay_traces_with_options, replay_traces_with_progress,
+    ReplayOptions, ReplayProgress, ReplayProgressFn, ReplayStats,
 };
 
 #[cfg(feature = "parallel")]
 pub use replay::replay_traces_parallel;
 
+#[cfg(all(feature = "replay", feature = "trace-gen"))]
+pub use replay::{replay_generated, replay_traces_matrix};
+
 // Re-export RPC types
 #[cfg(feature = "rpc")]
 pub use error::RpcError;
 #[cfg(feature = "rpc")]
 pub use rpc::{
-    interactive_test, interactive_test_with_progress, ApalacheRpcClient, InteractiveConfig,
+    interactive_test, interactive_test_with_progress, ApalacheRpcClient, ApalacheServer, InteractiveConfig,
     InteractiveConfigBuilder, InteractiveProgress, InteractiveProgressFn, InteractiveStats,
     RetryConfig,
 };
 
 // Re-export trace generation types
 #[cfg(feature = "trace-gen")]
-pub use trace_gen::{generate_traces, ApalacheConfig, ApalacheConfigBuilder, ApalacheMode, GeneratedTraces};
+pub use trace_gen::{
+    generate_coverage_traces, generate_quint_traces, generate_tlc_traces, generate_traces, generate_traces_matrix,
+    generate_traces_parallel, stream_traces, ApalacheConfig, ApalacheConfigBuilder, ApalacheMode, ConfiguredTraces,
+    ConstantAssignment, CoverageTraces, GeneratedTraces, MbtAction, MbtInstrumentation, MbtParam, ParallelOptions,
+    QuintConfig, QuintConfigBuilder, QuintMode, TlcConfig, TlcConfigBuilder, TlcMode, TraceCache, TraceGenerator,
+    TraceStream,
+};
+#[cfg(all(feature = "trace-gen", feature = "async"))]
+pub use trace_gen::generate_traces_async;
 
 // Re-export trace validation types
 #[cfg(feature = "trace-validation")]
-pub use trace_validation::{validate_trace, StateEmitter, TraceResult, TraceValidatorConfig, TraceValidatorConfigBuilder};
+pub use trace_validation::{
+    merge_traces, validate_trace, InvalidStep, LogicalClock, MemorySink, MergedTraces, RotatingSink, StateEmitter,
+    StateLock, StateRecorder, TlaType, TraceInput, TraceMergeConfig, TraceMergeConfigBuilder, TraceResult, TraceSink, TraceSpecGenerator,
+    TraceSpecGeneratorBuilder, TraceValidatorConfig, TraceValidatorConfigBuilder, WriterSink,
+};
+#[cfg(feature = "gzip")]
+pub use trace_validation::GzipSink;
+#[cfg(feature = "zstd")]
+pub use trace_validation::ZstdSink;
+#[cfg(all(feature = "trace-validation", feature = "async"))]
+pub use trace_validation::validate_trace_async;
+#[cfg(feature = "tracing-layer")]
+pub use trace_validation::{StateLayer, StateLayerConfig, StateLayerConfigBuilder, StateLayerHandle};
 #[cfg(feature = "trace-validation")]
 #[doc(hidden)]
 pub use trace_validation::ndjson_to_tla_module;
@@ -28,10 +28,11 @@
 //! replay_trace_str(|| D { v: 0 }, trace).unwrap();
 //! ```
 
-use crate::driver::{Driver, ExtractState, State, Step};
+use crate::driver::{Driver, ExtractState, State, Step, TraceMeta};
 use crate::error::{Error, ReplayError, StepContext, StepError};
 use serde::Deserialize;
 use std::borrow::Borrow;
+use std::sync::Arc;
 use std::time::Instant;
 use tracing::{debug, info};
 
@@ -57,6 +58,35 @@ pub struct ReplayProgress {
     pub state_index: usize,
     pub total_states: usize,
     pub action: String,
+    /// Metadata of the trace being replayed.
+    pub trace_meta: Arc<TraceMeta>,
+}
+
+/// Options for [`replay_traces_with_options`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ReplayOptions {
+    /// How many extra times to replay the loop of lasso-shaped traces
+    /// (default: 0). Traces without a loop are unaffected. See
+    /// [`unroll_loops`](Self::unroll_loops) for how a loop is replayed.
+    pub unroll_loops: usize,
+}
+
+impl ReplayOptions {
+    /// Replay the loop of lasso-shaped traces `times` extra times.
+    ///
+    /// An ITF `loop` index `l` means the last state is followed by state
+    /// `l` again. Apalache's liveness counterexamples end in a copy of
+    /// state `l`, so if the last state equals state `l`, each unrolling
+    /// replays the states after `l` with their recorded actions. Otherwise
+    /// each unrolling first replays the transition from the last state back
+    /// to state `l`, which the trace records no action for: its step has
+    /// action `"unknown"` and no nondet picks. State indices in progress
+    /// reports and errors count the unrolled states.
+    pub fn unroll_loops(mut self, times: usize) -> Self {
+        self.unroll_loops = times;
+        self
+    }
 }
 
 /// Replay multiple ITF traces against a Driver.
@@ -80,6 +110,16 @@ pub fn replay_traces_with_progress<D: Driver>(
     driver_factory: impl Fn() -> D,
     traces: impl IntoIterator<Item = impl Borrow<itf::Trace<itf::Value>>>,
     progress: Option<ReplayProgressFn>,
+) -> Result<ReplayStats, Error> {
+    replay_traces_with_options(driver_factory, traces, ReplayOptions::default(), progress)
+}
+
+/// Replay with [`ReplayOptions`] and an optional progress callback.
+pub fn replay_traces_with_options<D: Driver>(
+    driver_factory: impl Fn() -> D,
+    traces: impl IntoIterator<Item = impl Borrow<itf::Trace<itf::Value>>>,
+    options: ReplayOptions,
+    progress: Option<ReplayProgressFn>,
 ) -> Result<ReplayStats, Error> {
     let start = Instant::now();
     let traces: Vec<_> = traces.into_iter().collect();
@@ -104,6 +144,7 @@ pub fn replay_traces_with_progress<D: Driver>(
             trace,
             trace_idx,
             total_traces,
+            options,
             &progress,
         )?;
 
@@ -120,6 +161,84 @@ pub fn replay_traces_with_progress<D: Driver>(
     Ok(stats)
 }
 
+/// Replay traces as they arrive from a fallible source.
+///
+/// Intended for [`stream_traces`](crate::stream_traces): each trace is
+/// replayed as soon as it is yielded, and the source is dropped on the
+/// first error, whether from the source itself or from replay. Dropping a
+/// trace stream stops Apalache.
+#[must_use = "returns a Result that should be checked for replay failures"]
+pub fn replay_trace_stream<D: Driver>(
+    driver_factory: impl Fn() -> D,
+    traces: impl IntoIterator<Item = Result<itf::Trace<itf::Value>, Error>>,
+) -> Result<ReplayStats, Error> {
+    let start = Instant::now();
+    let mut stats = ReplayStats::default();
+
+    for (trace_idx, trace) in traces.into_iter().enumerate() {
+        let trace = trace?;
+        debug!(trace = trace_idx, states = trace.states.len(), "Replaying streamed trace");
+
+        let mut driver = driver_factory();
+        stats.total_states += replay_single_trace(&mut driver, &trace, trace_idx, 0, ReplayOptions::default(), &None)?;
+        stats.traces_replayed += 1;
+    }
+
+    stats.duration = start.elapsed();
+    info!(trace_count = stats.traces_replayed, "All streamed traces replayed successfully");
+    Ok(stats)
+}
+
+/// Replay traces from [`generate_traces`](crate::generate_traces).
+///
+/// Like [`replay_traces`], but a failure reports the seed the traces were
+/// generated with, so the exact trace set can be regenerated.
+#[cfg(feature = "trace-gen")]
+#[must_use = "returns a Result that should be checked for replay failures"]
+pub fn replay_generated<D: Driver>(
+    driver_factory: impl Fn() -> D,
+    generated: &crate::trace_gen::GeneratedTraces,
+) -> Result<ReplayStats, Error> {
+    replay_traces(driver_factory, &generated.traces).map_err(|e| match generated.seed {
+        Some(seed) => ReplayError::WithSeed {
+            seed,
+            source: Box::new(e),
+        }
+        .into(),
+        None => e,
+    })
+}
+
+/// Replay traces generated over a constant matrix.
+///
+/// Each entry's traces are replayed against drivers built by
+/// `driver_factory` for that entry's constants, so the driver matches the
+/// configuration the traces were generated under. Failures report the
+/// constant assignment alongside the step error.
+#[cfg(feature = "trace-gen")]
+#[must_use = "returns a Result that should be checked for replay failures"]
+pub fn replay_traces_matrix<D: Driver>(
+    driver_factory: impl Fn(&crate::trace_gen::ConstantAssignment) -> D,
+    matrix: &[crate::trace_gen::ConfiguredTraces],
+) -> Result<ReplayStats, Error> {
+    let start = Instant::now();
+    let mut stats = ReplayStats::default();
+
+    for entry in matrix {
+        debug!(constants = %entry.constants, "Replaying traces for configuration");
+        let run = replay_generated(|| driver_factory(&entry.constants), &entry.generated)
+            .map_err(|e| ReplayError::WithConstants {
+                constants: entry.constants.to_string(),
+                source: Box::new(e),
+            })?;
+        stats.traces_replayed += run.traces_replayed;
+        stats.total_states += run.total_states;
+    }
+
+    stats.duration = start.elapsed();
+    Ok(stats)
+}
+
 /// Replay a single ITF trace against a Driver.
 ///
 /// Internal helper used by both sequential and parallel replay.
@@ -128,19 +247,37 @@ fn replay_single_trace<D: Driver>(
     trace: &itf::Trace<itf::Value>,
     trace_idx: usize,
     total_traces: usize,
+    options: ReplayOptions,
     progress: &Option<ReplayProgressFn>,
 ) -> Result<usize, Error> {
-    let total_states = trace.states.len();
+    let trace_meta = Arc::new(TraceMeta::from_trace(trace));
+    let len = trace.states.len();
+    // Indices of the states to replay, with whether each is reached by the
+    // transition closing the lasso rather than by its recorded action.
+    let mut order: Vec<(usize, bool)> = (0..len).map(|i| (i, false)).collect();
+    if let Some(start) = trace_meta.loop_index.filter(|&start| start < len) {
+        let closed = trace.states[len - 1].value == trace.states[start].value;
+        let closing = (!closed).then_some((start, true));
+        let unrolled = closing.into_iter().chain((start + 1..len).map(|i| (i, false)));
+        for _ in 0..options.unroll_loops {
+            order.extend(unrolled.clone());
+        }
+    }
+    let total_states = order.len();
 
-    for (state_idx, itf_state) in trace.states.iter().enumerate() {
+    for (state_idx, &(i, closing)) in order.iter().enumerate() {
+        let itf_state = &trace.states[i];
         let state_value = &itf_state.value;
 
-        let (action_taken, nondet_picks) =
+        let (action_taken, nondet_picks) = if closing {
+            ("unknown".to_string(), itf::Value::Tuple(vec![].into()))
+        } else {
             extract_mbt_vars(state_value, &itf_state.meta).map_err(|reason| ReplayError::MbtVarExtraction {
                 trace: trace_idx,
                 state: state_idx,
                 reason,
-            })?;
+            })?
+        };
 
         if let Some(ref cb) = progress {
             cb(ReplayProgress {
@@ -149,6 +286,7 @@ fn replay_single_trace<D: Driver>(
                 state_index: state_idx,
                 total_states,
                 action: action_taken.clone(),
+                trace_meta: Arc::clone(&trace_meta),
             });
         }
 
@@ -156,6 +294,7 @@ fn replay_single_trace<D: Driver>(
             action_taken: action_taken.clone(),
             nondet_picks,
             state: state_value.clone(),
+            trace_meta: Some(Arc::clone(&trace_meta)),
         };
 
         let ctx = StepContext::Replay { trace: trace_idx, state: state_idx };
@@ -192,14 +331,15 @@ fn replay_single_trace<D: Driver>(
         }
     }
 
-    Ok(trace.states.len())
+    Ok(total_states)
 }
 
 /// Extract `action_taken` and `nondet_picks` from an ITF state.
 ///
 /// Action resolution priority:
 /// 1. ITF state metadata (`#meta`) fields: `"action"`, `"label"`, or `"transition"`
-/// 2. Explicit `action_taken` field in the state record
+/// 2. Explicit `action_taken` field in the state record, or Quint's
+///    `mbt::actionTaken` (written by `quint run --mbt`)
 /// 3. Default: `"init"` for state index 0, `"unknown"` otherwise
 fn extract_mbt_vars(
     state: &itf::Value,
@@ -216,18 +356,19 @@ fn extract_mbt_vars(
 
     let action_taken = if let Some(action) = action_from_meta {
         action
-    } else if let Some(action) = rec.get("action_taken") {
+    } else if let Some(action) = rec.get("action_taken").or_else(|| rec.get("mbt::actionTaken")) {
         // Priority 2: explicit action_taken field in state
         String::deserialize(action.clone())
             .map_err(|e| format!("Failed to deserialize action_taken: {e}"))?
     } else {
         // Priority 3: default based on state index
-        let is_init = meta.index.map_or(true, |i| i == 0);
+        let is_init = meta.index.is_none_or(|i| i == 0);
         if is_init { "init" } else { "unknown" }.to_string()
     };
 
     let nondet_picks = rec
         .get("nondet_picks")
+        .or_else(|| rec.get("mbt::nondetPicks"))
         .cloned()
         .unwrap_or(itf::Value::Tuple(vec![].into()));
 
@@ -288,6 +429,42 @@ pub fn load_traces_from_dir(dir: &std::path::Path) -> Result<Vec<itf::Trace<itf:
     Ok(entries.into_iter().map(|(_, t)| t).collect())
 }
 
+/// Replay traces in parallel using rayon.
+///
+/// Each trace is replayed independently in its own thread.
+/// Returns on first error encountered.
+#[cfg(feature = "parallel")]
+pub fn replay_traces_parallel<D: Driver + Send>(
+    driver_factory: impl Fn() -> D + Sync,
+    traces: &[itf::Trace<itf::Value>],
+) -> Result<ReplayStats, Error> {
+    use rayon::prelude::*;
+
+    let start = std::time::Instant::now();
+    let total_traces = traces.len();
+
+    let results: Result<Vec<(usize, usize)>, Error> = traces
+        .par_iter()
+        .enumerate()
+        .map(|(trace_idx, trace)| {
+            let mut driver = driver_factory();
+            let states = replay_single_trace(&mut driver, trace, trace_idx, total_traces, ReplayOptions::default(), &None)?;
+            Ok((1, states))
+        })
+        .collect();
+
+    let stats_vec = results?;
+    let (traces_replayed, total_states) = stats_vec
+        .iter()
+        .fold((0, 0), |acc, x| (acc.0 + x.0, acc.1 + x.1));
+
+    Ok(ReplayStats {
+        traces_replayed,
+        total_states,
+        duration: start.elapsed(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -327,6 +504,24 @@ mod tests {
         assert!(matches!(nondet, itf::Value::Record(_)));
     }
 
+    #[test]
+    fn extract_mbt_vars_from_quint_fields() {
+        let state = itf::Value::Record(
+            vec![
+                ("mbt::actionTaken".to_string(), itf::Value::String("deposit".into())),
+                ("mbt::nondetPicks".to_string(), itf::Value::Record(
+                    vec![("amount".to_string(), itf::Value::Number(5))].into_iter().collect(),
+                )),
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        let (action, nondet) = extract_mbt_vars(&state, &meta_at(1)).unwrap();
+        assert_eq!(action, "deposit");
+        assert!(matches!(nondet, itf::Value::Record(_)));
+    }
+
     #[test]
     fn extract_mbt_vars_defaults_init_at_index_0() {
         let state = itf::Value::Record(
@@ -410,39 +605,3 @@ mod tests {
         assert!(result.contains("+world"));
     }
 }
-
-/// Replay traces in parallel using rayon.
-///
-/// Each trace is replayed independently in its own thread.
-/// Returns on first error encountered.
-#[cfg(feature = "parallel")]
-pub fn replay_traces_parallel<D: Driver + Send>(
-    driver_factory: impl Fn() -> D + Sync,
-    traces: &[itf::Trace<itf::Value>],
-) -> Result<ReplayStats, Error> {
-    use rayon::prelude::*;
-
-    let start = std::time::Instant::now();
-    let total_traces = traces.len();
-
-    let results: Result<Vec<(usize, usize)>, Error> = traces
-        .par_iter()
-        .enumerate()
-        .map(|(trace_idx, trace)| {
-            let mut driver = driver_factory();
-            let states = replay_single_trace(&mut driver, trace, trace_idx, total_traces, &None)?;
-            Ok((1, states))
-        })
-        .collect();
-
-    let stats_vec = results?;
-    let (traces_replayed, total_states) = stats_vec
-        .iter()
-        .fold((0, 0), |acc, x| (acc.0 + x.0, acc.1 + x.1));
-
-    Ok(ReplayStats {
-        traces_replayed,
-        total_states,
-        duration: start.elapsed(),
-    })
-}
@@ -34,7 +34,8 @@ impl Default for RetryConfig {
 
 /// Client for Apalache's JSON-RPC explorer server.
 ///
-/// The server must be started separately:
+/// The server must be started separately, or by
+/// [`ApalacheServer`](super::ApalacheServer):
 /// ```bash
 /// apalache-mc server --port=8822 --server-type=explorer
 /// ```
@@ -5,13 +5,16 @@
 //! Rust implementation execution.
 
 pub mod client;
+pub mod server;
 pub mod types;
 
 pub use client::{ApalacheRpcClient, RetryConfig};
+pub use server::ApalacheServer;
 pub use types::{SpecParameters, TransitionStatus};
 
 use crate::driver::{Driver, ExtractState, State, Step};
 use crate::error::{Error, RpcError, StepContext, StepError};
+use crate::itf_writer::ItfTraceWriter;
 use rand::prelude::*;
 use rand::SeedableRng;
 use std::path::Path;
@@ -71,6 +74,10 @@ pub struct InteractiveConfig {
     /// Random seed for reproducible test runs.
     /// If None, uses entropy from the system.
     pub seed: Option<u64>,
+
+    /// Directory to record each run's spec states into, as
+    /// `run-<n>.itf.json`. Failing runs are recorded up to the failing step.
+    pub record_dir: Option<std::path::PathBuf>,
 }
 
 impl Default for InteractiveConfig {
@@ -84,6 +91,7 @@ impl Default for InteractiveConfig {
             num_runs: 50,
             constants: serde_json::Value::Object(serde_json::Map::new()),
             seed: None,
+            record_dir: None,
         }
     }
 }
@@ -92,7 +100,7 @@ crate::builder::impl_builder!(InteractiveConfig, InteractiveConfigBuilder {
     required { spec: std::path::PathBuf }
     optional { aux_files: Vec<std::path::PathBuf>, init: String, next: String,
                max_steps: usize, num_runs: usize, constants: serde_json::Value }
-    optional_or { seed: u64 }
+    optional_or { seed: u64, record_dir: std::path::PathBuf }
 });
 
 fn collect_spec_sources(spec: &Path, aux_files: &[std::path::PathBuf]) -> Result<Vec<String>, Error> {
@@ -258,14 +266,26 @@ pub async fn interactive_test_with_progress<D: Driver>(
             progress: &progress,
         };
 
+        let mut recorder = config.record_dir.as_ref().map(|_| {
+            ItfTraceWriter::new().source(config.spec.display().to_string())
+        });
+
         let result = run_single_test(
             &mut driver,
             &ctx,
             &mut *rng,
             &mut stats,
+            &mut recorder,
         )
         .await;
 
+        if let (Some(dir), Some(recorder)) = (&config.record_dir, &recorder) {
+            let path = dir.join(format!("run-{run}.itf.json"));
+            std::fs::create_dir_all(dir)?;
+            recorder.write_to(&path)?;
+            debug!(run, path = %path.display(), "Recorded run");
+        }
+
         if let Err(e) = client.dispose_spec(&session).await {
             debug!(run, error = %e, "Failed to dispose spec (non-fatal)");
         }
@@ -298,6 +318,7 @@ async fn run_single_test<D: Driver>(
     ctx: &RunContext<'_>,
     rng: &mut dyn RngCore,
     stats: &mut InteractiveStats,
+    recorder: &mut Option<ItfTraceWriter>,
 ) -> Result<(), Error> {
     let next_transitions = &ctx.load_result.spec_parameters.next_transitions;
 
@@ -338,6 +359,9 @@ async fn run_single_test<D: Driver>(
     let trace = query.trace.ok_or(RpcError::MissingStates)?;
     let init_state_json = extract_last_state(&trace)?;
     let init_itf = json_state_to_itf(&init_state_json)?;
+    if let Some(recorder) = recorder {
+        recorder.record_value("init", init_itf.clone())?;
+    }
 
     if let Some(ref cb) = ctx.progress {
         cb(InteractiveProgress {
@@ -351,6 +375,7 @@ async fn run_single_test<D: Driver>(
     let init_step = Step {
         action_taken: "init".to_string(),
         nondet_picks: itf::Value::Tuple(vec![].into()),
+        trace_meta: None,
         state: init_itf.clone(),
     };
 
@@ -394,6 +419,9 @@ async fn run_single_test<D: Driver>(
         let state_json = extract_last_state(&trace)?;
         let state_itf = json_state_to_itf(&state_json)?;
         let action_taken = resolve_action(chosen_idx, next_transitions, &state_json);
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.record_value(&action_taken, state_itf.clone())?;
+        }
 
         if let Some(ref cb) = ctx.progress {
             cb(InteractiveProgress {
@@ -407,6 +435,7 @@ async fn run_single_test<D: Driver>(
         let step = Step {
             action_taken: action_taken.clone(),
             nondet_picks: extract_nondet(&state_itf),
+            trace_meta: None,
             state: state_itf.clone(),
         };
 
@@ -0,0 +1,115 @@
+//! Managed Apalache explorer server.
+//!
+//! Starts `apalache server --server-type=explorer` through an
+//! [`ApalacheRunner`], waits until it accepts connections and stops it on
+//! drop, so tests don't need a server running beforehand.
+
+use super::ApalacheRpcClient;
+use crate::error::{ApalacheError, Error, RpcError};
+use crate::runner::{ApalacheRunner, ApalacheVersion};
+use std::net::{SocketAddr, TcpStream};
+use std::process::{Child, Stdio};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// Oldest Apalache release with the JSON-RPC explorer server.
+pub const MIN_SERVER_VERSION: ApalacheVersion = ApalacheVersion::new(0, 52, 0);
+
+/// Default time allowed for the server to start accepting connections.
+const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// An Apalache explorer server owned by this process.
+///
+/// The server is killed when this value is dropped.
+pub struct ApalacheServer {
+    child: Child,
+    url: String,
+    _out_dir: tempfile::TempDir,
+}
+
+impl ApalacheServer {
+    /// Start a server on `port` and wait until it accepts connections.
+    #[must_use = "the server is stopped when dropped"]
+    pub fn start(runner: &ApalacheRunner, port: u16) -> Result<Self, Error> {
+        Self::start_with_timeout(runner, port, DEFAULT_STARTUP_TIMEOUT)
+    }
+
+    /// Like [`start`](Self::start), with a custom startup timeout.
+    #[must_use = "the server is stopped when dropped"]
+    pub fn start_with_timeout(runner: &ApalacheRunner, port: u16, timeout: Duration) -> Result<Self, Error> {
+        runner
+            .ensure_at_least(Some(MIN_SERVER_VERSION))
+            .map_err(RpcError::from)?;
+
+        let out_dir = tempfile::Builder::new()
+            .prefix("tla_server_")
+            .tempdir()
+            .map_err(|e| RpcError::from(ApalacheError::Execution {
+                exit_code: None,
+                message: format!("failed to create output directory: {e}"),
+            }))?;
+
+        let mut cmd = runner.command();
+        cmd.arg("server")
+            .arg(format!("--port={port}"))
+            .arg("--server-type=explorer")
+            .arg(format!("--out-dir={}", out_dir.path().display()))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+
+        info!(port, "Starting Apalache explorer server");
+        debug!("Command: {:?}", cmd);
+
+        let child = cmd
+            .spawn()
+            .map_err(|e| RpcError::from(ApalacheError::NotFound(e.to_string())))?;
+        let mut server = Self {
+            child,
+            url: format!("http://127.0.0.1:{port}"),
+            _out_dir: out_dir,
+        };
+        server.wait_ready(port, timeout)?;
+        info!(url = %server.url, "Apalache explorer server ready");
+        Ok(server)
+    }
+
+    fn wait_ready(&mut self, port: u16, timeout: Duration) -> Result<(), Error> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], port));
+        let start = Instant::now();
+
+        loop {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                return Err(RpcError::from(ApalacheError::Execution {
+                    exit_code: status.code(),
+                    message: "server exited during startup".to_string(),
+                })
+                .into());
+            }
+            if TcpStream::connect_timeout(&addr, Duration::from_millis(200)).is_ok() {
+                return Ok(());
+            }
+            if start.elapsed() >= timeout {
+                return Err(RpcError::from(ApalacheError::Timeout { duration: timeout }).into());
+            }
+            std::thread::sleep(Duration::from_millis(200));
+        }
+    }
+
+    /// Base URL of the server, e.g. `http://127.0.0.1:8822`.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// A client connected to this server.
+    pub fn client(&self) -> Result<ApalacheRpcClient, Error> {
+        ApalacheRpcClient::new(&self.url)
+    }
+}
+
+impl Drop for ApalacheServer {
+    fn drop(&mut self) {
+        debug!(url = %self.url, "Stopping Apalache explorer server");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,314 @@
+//! How tla-connect launches Apalache.
+//!
+//! An [`ApalacheRunner`] builds the base command for every Apalache
+//! invocation: an `apalache-mc` executable, `java -jar` on a release JAR
+//! with its own JVM and heap settings, or an arbitrary wrapper command such
+//! as a container invocation. It also detects the Apalache version and
+//! rejects versions older than what the operation needs, or than
+//! [`ApalacheRunner::min_version`], before anything else runs.
+//!
+//! # Example
+//!
+//! ```
+//! use tla_connect::{ApalacheRunner, ApalacheVersion};
+//!
+//! let runner = ApalacheRunner::jar("/opt/apalache/lib/apalache.jar")
+//!     .heap("8g")
+//!     .min_version(ApalacheVersion::new(0, 44, 0));
+//!
+//! let docker = ApalacheRunner::wrapper(
+//!     "docker",
+//!     ["run", "--rm", "-v", "/specs:/specs", "ghcr.io/apalache-mc/apalache"],
+//! );
+//! # let _ = (runner, docker);
+//! ```
+
+use crate::error::ApalacheError;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+use tracing::debug;
+
+/// An Apalache release version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ApalacheVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl ApalacheVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self { major, minor, patch }
+    }
+}
+
+impl std::fmt::Display for ApalacheVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl std::str::FromStr for ApalacheVersion {
+    type Err = ApalacheError;
+
+    /// Parse `0.44.0`, `v0.44.0` or `0.44.0-SNAPSHOT` style versions.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unknown = || ApalacheError::UnknownVersion(s.to_string());
+        let core = s.trim().trim_start_matches('v');
+        let core = core.split(['-', '+', ' ']).next().unwrap_or_default();
+        let mut parts = core.split('.').map(|p| p.parse::<u32>().map_err(|_| unknown()));
+        let major = parts.next().ok_or_else(unknown)??;
+        let minor = parts.next().ok_or_else(unknown)??;
+        let patch = parts.next().transpose()?.unwrap_or(0);
+        Ok(Self::new(major, minor, patch))
+    }
+}
+
+/// How the Apalache process is started.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Launcher {
+    /// An `apalache-mc` executable, by name on PATH or by path.
+    Binary(String),
+
+    /// `java -jar` on an Apalache release JAR.
+    Jar { java: String, jar: PathBuf },
+
+    /// Any command that forwards its remaining arguments to Apalache.
+    Wrapper { program: String, args: Vec<String> },
+}
+
+/// Launcher for Apalache, shared by trace generation, validation, spec
+/// checks and the managed RPC server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ApalacheRunner {
+    /// How the process is started.
+    pub launcher: Launcher,
+
+    /// Maximum JVM heap, e.g. "4g", passed as `-Xmx`. Applied to `Jar`
+    /// directly and to `Binary` through the `JVM_ARGS` environment
+    /// variable honored by `apalache-mc`; ignored for `Wrapper`.
+    pub heap: Option<String>,
+
+    /// Extra JVM options, applied like `heap`.
+    pub jvm_args: Vec<String>,
+
+    /// Oldest supported Apalache version. Older versions fail with
+    /// [`ApalacheError::UnsupportedVersion`] before Apalache runs. Trace
+    /// generation, validation and the RPC server also enforce their own
+    /// minimum, such as `trace_gen::MIN_GENERATION_VERSION`, which this can
+    /// only raise. The version is detected once per launcher.
+    pub min_version: Option<ApalacheVersion>,
+}
+
+impl Default for ApalacheRunner {
+    fn default() -> Self {
+        Self::binary("apalache-mc")
+    }
+}
+
+impl ApalacheRunner {
+    /// Run an `apalache-mc` executable.
+    pub fn binary(bin: impl Into<String>) -> Self {
+        Self::with_launcher(Launcher::Binary(bin.into()))
+    }
+
+    /// Run `java -jar <jar>` using `java` from PATH.
+    pub fn jar(jar: impl Into<PathBuf>) -> Self {
+        Self::with_launcher(Launcher::Jar {
+            java: "java".into(),
+            jar: jar.into(),
+        })
+    }
+
+    /// Run `program args... <apalache args>`, e.g. a container invocation.
+    pub fn wrapper(program: impl Into<String>, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self::with_launcher(Launcher::Wrapper {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    fn with_launcher(launcher: Launcher) -> Self {
+        Self {
+            launcher,
+            heap: None,
+            jvm_args: Vec::new(),
+            min_version: None,
+        }
+    }
+
+    /// Use this `java` executable for a `Jar` launcher (no-op otherwise).
+    pub fn java(mut self, java: impl Into<String>) -> Self {
+        if let Launcher::Jar { java: ref mut current, .. } = self.launcher {
+            *current = java.into();
+        }
+        self
+    }
+
+    /// Set the maximum JVM heap, e.g. "4g".
+    pub fn heap(mut self, heap: impl Into<String>) -> Self {
+        self.heap = Some(heap.into());
+        self
+    }
+
+    /// Add a JVM option.
+    pub fn jvm_arg(mut self, arg: impl Into<String>) -> Self {
+        self.jvm_args.push(arg.into());
+        self
+    }
+
+    /// Require at least `version`.
+    pub fn min_version(mut self, version: ApalacheVersion) -> Self {
+        self.min_version = Some(version);
+        self
+    }
+
+    /// The base command, ready for Apalache's subcommand and options.
+    pub fn command(&self) -> Command {
+        let mut jvm_args: Vec<String> = self.heap.iter().map(|h| format!("-Xmx{h}")).collect();
+        jvm_args.extend(self.jvm_args.iter().cloned());
+
+        match self.launcher {
+            Launcher::Binary(ref bin) => {
+                let mut cmd = Command::new(bin);
+                if !jvm_args.is_empty() {
+                    cmd.env("JVM_ARGS", jvm_args.join(" "));
+                }
+                cmd
+            }
+            Launcher::Jar { ref java, ref jar } => {
+                let mut cmd = Command::new(java);
+                cmd.args(&jvm_args).arg("-jar").arg(jar);
+                cmd
+            }
+            Launcher::Wrapper { ref program, ref args } => {
+                let mut cmd = Command::new(program);
+                cmd.args(args);
+                cmd
+            }
+        }
+    }
+
+    /// Detect the Apalache version via `apalache version`.
+    ///
+    /// The result is cached per launcher for the lifetime of the process.
+    pub fn version(&self) -> Result<ApalacheVersion, ApalacheError> {
+        static VERSIONS: OnceLock<Mutex<HashMap<Launcher, ApalacheVersion>>> = OnceLock::new();
+        let versions = VERSIONS.get_or_init(Default::default);
+
+        if let Some(version) = versions.lock().ok().and_then(|v| v.get(&self.launcher).copied()) {
+            return Ok(version);
+        }
+
+        let output = self
+            .command()
+            .arg("version")
+            .output()
+            .map_err(|e| ApalacheError::NotFound(e.to_string()))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if !output.status.success() {
+            return Err(ApalacheError::Execution {
+                exit_code: output.status.code(),
+                message: format!("stdout: {stdout}\nstderr: {}", String::from_utf8_lossy(&output.stderr)),
+            });
+        }
+
+        // JVM warnings may precede the version line.
+        let version: ApalacheVersion = stdout
+            .lines()
+            .map(str::trim)
+            .find(|l| l.trim_start_matches('v').starts_with(|c: char| c.is_ascii_digit()))
+            .unwrap_or_else(|| stdout.trim())
+            .parse()?;
+        debug!(launcher = ?self.launcher, %version, "Detected Apalache version");
+
+        if let Ok(mut versions) = versions.lock() {
+            versions.insert(self.launcher.clone(), version);
+        }
+        Ok(version)
+    }
+
+    /// Fail if the installed version is older than `min_version`.
+    pub fn ensure_supported(&self) -> Result<(), ApalacheError> {
+        self.ensure_at_least(self.min_version)
+    }
+
+    /// Fail if the installed version is older than the larger of
+    /// `min_version` and `required`. No-op if both are None.
+    pub(crate) fn ensure_at_least(&self, required: Option<ApalacheVersion>) -> Result<(), ApalacheError> {
+        let Some(required) = self.min_version.max(required) else {
+            return Ok(());
+        };
+        let found = self.version()?;
+        if found < required {
+            return Err(ApalacheError::UnsupportedVersion { found, required });
+        }
+        Ok(())
+    }
+
+    /// [`command`](Self::command) after checking `min_version`.
+    pub(crate) fn checked_command(&self) -> Result<Command, ApalacheError> {
+        self.ensure_supported()?;
+        Ok(self.command())
+    }
+}
+
+/// The runner configured alongside a plain `apalache_bin` setting:
+/// `runner` if set, otherwise the binary `apalache_bin`.
+pub(crate) fn resolve(runner: &Option<ApalacheRunner>, apalache_bin: &str) -> ApalacheRunner {
+    runner.clone().unwrap_or_else(|| ApalacheRunner::binary(apalache_bin))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program_and_args(cmd: &Command) -> Vec<String> {
+        std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(|s| s.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn version_parsing() {
+        assert_eq!("0.44.0".parse::<ApalacheVersion>().unwrap(), ApalacheVersion::new(0, 44, 0));
+        assert_eq!("v0.52.1-SNAPSHOT".parse::<ApalacheVersion>().unwrap(), ApalacheVersion::new(0, 52, 1));
+        assert!(ApalacheVersion::new(0, 9, 0) < ApalacheVersion::new(0, 44, 0));
+        assert!(matches!(
+            "Picked up JAVA_TOOL_OPTIONS".parse::<ApalacheVersion>(),
+            Err(ApalacheError::UnknownVersion(_))
+        ));
+    }
+
+    #[test]
+    fn jar_command_passes_jvm_options() {
+        let runner = ApalacheRunner::jar("/opt/apalache.jar")
+            .java("/usr/lib/jvm/bin/java")
+            .heap("8g")
+            .jvm_arg("-XX:+UseG1GC");
+        assert_eq!(
+            program_and_args(&runner.command()),
+            ["/usr/lib/jvm/bin/java", "-Xmx8g", "-XX:+UseG1GC", "-jar", "/opt/apalache.jar"]
+        );
+    }
+
+    #[test]
+    fn binary_command_uses_jvm_args_env() {
+        let cmd = ApalacheRunner::binary("apalache-mc").heap("2g").command();
+        assert_eq!(program_and_args(&cmd), ["apalache-mc"]);
+        let env: Vec<_> = cmd.get_envs().collect();
+        assert_eq!(env, [(std::ffi::OsStr::new("JVM_ARGS"), Some(std::ffi::OsStr::new("-Xmx2g")))]);
+    }
+
+    #[test]
+    fn wrapper_command_prepends_args() {
+        let cmd = ApalacheRunner::wrapper("docker", ["run", "--rm", "apalache"]).command();
+        assert_eq!(program_and_args(&cmd), ["docker", "run", "--rm", "apalache"]);
+    }
+}
@@ -0,0 +1,105 @@
+//! Rendering of values as TLA+ expressions.
+//!
+//! Shared by trace generation (constant assignments in generated wrapper
+//! modules) and trace validation (the generated `TraceData` module).
+
+/// Render an ITF value as a TLA+ expression.
+///
+/// Sequences and tuples both render as `<<...>>`, records as `[f |-> v]`,
+/// and maps as `(k :> v @@ ...)`, which requires the enclosing module to
+/// extend `TLC`.
+#[cfg(feature = "trace-gen")]
+pub(crate) fn itf_to_tla(value: &itf::Value) -> String {
+    match value {
+        itf::Value::Bool(b) => if *b { "TRUE" } else { "FALSE" }.to_string(),
+        itf::Value::Number(n) => n.to_string(),
+        itf::Value::BigInt(n) => n.to_string(),
+        itf::Value::String(s) => format!("\"{}\"", escape_tla_string(s)),
+        itf::Value::List(elems) => {
+            let elems: Vec<String> = elems.iter().map(itf_to_tla).collect();
+            format!("<<{}>>", elems.join(", "))
+        }
+        itf::Value::Tuple(elems) => {
+            let elems: Vec<String> = elems.iter().map(itf_to_tla).collect();
+            format!("<<{}>>", elems.join(", "))
+        }
+        itf::Value::Set(elems) => {
+            let elems: Vec<String> = elems.iter().map(itf_to_tla).collect();
+            format!("{{{}}}", elems.join(", "))
+        }
+        itf::Value::Map(map) => {
+            if map.is_empty() {
+                return "[x \\in {} |-> x]".to_string();
+            }
+            let pairs: Vec<String> = map
+                .iter()
+                .map(|(k, v)| format!("{} :> {}", itf_to_tla(k), itf_to_tla(v)))
+                .collect();
+            format!("({})", pairs.join(" @@ "))
+        }
+        itf::Value::Record(rec) => {
+            let fields: Vec<String> = rec
+                .iter()
+                .map(|(k, v)| format!("{k} |-> {}", itf_to_tla(v)))
+                .collect();
+            format!("[{}]", fields.join(", "))
+        }
+        itf::Value::Unserializable(u) => serde_json::to_value(u)
+            .ok()
+            .and_then(|v| v.get("#unserializable").and_then(|s| s.as_str()).map(String::from))
+            .unwrap_or_default(),
+    }
+}
+
+/// Escape a string for use in a TLA+ string literal.
+pub(crate) fn escape_tla_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                out.push_str(&format!("\\u{:04x}", c as u32));
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(all(test, feature = "trace-gen"))]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> itf::Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn itf_to_tla_scalars() {
+        assert_eq!(itf_to_tla(&parse("true")), "TRUE");
+        assert_eq!(itf_to_tla(&parse(r##"{"#bigint": "-3"}"##)), "-3");
+        assert_eq!(itf_to_tla(&parse(r#""a\"b""#)), "\"a\\\"b\"");
+    }
+
+    #[test]
+    fn itf_to_tla_collections() {
+        assert_eq!(itf_to_tla(&parse(r##"{"#set": [1, 2]}"##)), "{1, 2}");
+        assert_eq!(itf_to_tla(&parse(r##"{"#tup": [1, "x"]}"##)), "<<1, \"x\">>");
+        assert_eq!(itf_to_tla(&parse(r#"[1, 2]"#)), "<<1, 2>>");
+        assert_eq!(
+            itf_to_tla(&parse(r##"{"#map": [["a", 1], ["b", 2]]}"##)),
+            "(\"a\" :> 1 @@ \"b\" :> 2)"
+        );
+        assert_eq!(itf_to_tla(&parse(r#"{"x": 1, "y": true}"#)), "[x |-> 1, y |-> TRUE]");
+    }
+
+    #[test]
+    fn itf_to_tla_unserializable() {
+        let int: itf::value::Unserializable = serde_json::from_str(r##"{"#unserializable": "Int"}"##).unwrap();
+        assert_eq!(itf_to_tla(&itf::Value::Unserializable(int)), "Int");
+    }
+}
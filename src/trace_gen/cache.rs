@@ -0,0 +1,349 @@
+//! Content-addressed cache of generated ITF traces.
+//!
+//! Entries are keyed by a hash of every `.tla` file in the spec directory,
+//! the output-relevant fields of [`ApalacheConfig`] and the detected
+//! Apalache version. The version is detected at most once per launcher and
+//! process (see [`ApalacheRunner::version`](crate::ApalacheRunner::version)),
+//! so repeated lookups start no further process, and moving or reinstalling
+//! the same release keeps existing entries valid.
+//! Each entry is a plain directory of pretty-printed `.itf.json` files plus a
+//! small manifest, so a cache directory can be committed to the repository
+//! or shared between CI jobs as an artifact.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use tla_connect::{generate_traces, ApalacheConfig, TraceCache};
+//!
+//! let config = ApalacheConfig::builder()
+//!     .spec("specs/Counter.tla")
+//!     .cache(TraceCache::new("tests/trace-cache").max_entries(20usize))
+//!     .build()?;
+//!
+//! // Runs Apalache on the first call, loads from disk afterwards.
+//! let generated = generate_traces(&config)?;
+//! ```
+
+use super::ApalacheConfig;
+use crate::error::{Error, TraceGenError};
+use serde::{Deserialize, Serialize};
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info};
+
+/// Name of the manifest file written into every cache entry.
+const MANIFEST: &str = "manifest.json";
+
+/// Configuration of the on-disk trace cache.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TraceCache {
+    /// Directory holding one subdirectory per cache entry.
+    pub dir: PathBuf,
+
+    /// Maximum number of entries to keep; the oldest are evicted first.
+    /// If None, the cache grows without bound.
+    pub max_entries: Option<usize>,
+
+    /// Entries older than this are treated as misses and regenerated.
+    /// If None, entries never expire.
+    pub max_age: Option<Duration>,
+
+    /// Ignore existing entries and regenerate (the new result is stored).
+    pub refresh: bool,
+}
+
+impl TraceCache {
+    /// Cache entries under `dir`, without size or age limits.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_entries: None,
+            max_age: None,
+            refresh: false,
+        }
+    }
+
+    /// Keep at most `max_entries` entries.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    /// Expire entries older than `max_age`.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Force regeneration, replacing any existing entry.
+    pub fn refresh(mut self, refresh: bool) -> Self {
+        self.refresh = refresh;
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    key: String,
+    apalache_version: String,
+    #[serde(default)]
+    seed: Option<u64>,
+    created_at: u64,
+    traces: usize,
+}
+
+/// 64-bit FNV-1a, stable across platforms and Rust releases.
+struct Fnv64(u64);
+
+impl Default for Fnv64 {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv64 {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+fn cache_error(path: &Path, e: impl std::fmt::Display) -> Error {
+    TraceGenError::Cache {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    }
+    .into()
+}
+
+/// Compute the cache key for `config`.
+///
+/// Covers the spec directory's `.tla` files, every config field that
+/// affects Apalache's output and the Apalache `version`. `out_dir`,
+/// `keep_outputs`, `timeout`, `preflight`, `best_effort` and `cache` itself
+/// are excluded.
+pub(crate) fn cache_key(config: &ApalacheConfig, spec_path: &Path, version: &str) -> Result<String, Error> {
+    let spec_dir = spec_path
+        .parent()
+        .ok_or_else(|| TraceGenError::SpecNotFound(spec_path.to_path_buf()))?;
+
+    let mut files: Vec<PathBuf> = std::fs::read_dir(spec_dir)
+        .map_err(|e| cache_error(spec_dir, e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("tla"))
+        .collect();
+    files.sort();
+
+    let mut hasher = Fnv64::default();
+    for file in &files {
+        let content = std::fs::read(file).map_err(|e| cache_error(file, e))?;
+        hasher.write(file.file_name().unwrap_or_default().as_encoded_bytes());
+        hasher.write_u8(0);
+        hasher.write(&content);
+        hasher.write_u8(0);
+    }
+
+    let settings = format!(
+        "spec={:?}\ninv={}\nmax_traces={}\nmax_length={}\nview={:?}\ncinit={:?}\nmode={:?}\n\
+         constants={}\ninstrument={:?}\ncover={:?}\nseed={:?}\napalache={}\n",
+        spec_path.file_name(),
+        config.inv,
+        config.max_traces,
+        config.max_length,
+        config.view,
+        config.cinit,
+        config.mode,
+        config.constants,
+        config.instrument,
+        config.cover,
+        config.seed,
+        version,
+    );
+    hasher.write(settings.as_bytes());
+
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn read_manifest(entry: &Path) -> Option<Manifest> {
+    let content = std::fs::read_to_string(entry.join(MANIFEST)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Look up `key`, returning the entry directory and the seed the traces
+/// were generated with on a hit.
+///
+/// Expired entries are removed and reported as misses.
+pub(crate) fn lookup(cache: &TraceCache, key: &str) -> Option<(PathBuf, Option<u64>)> {
+    if cache.refresh {
+        return None;
+    }
+
+    let entry = cache.dir.join(key);
+    let manifest = read_manifest(&entry)?;
+    if manifest.key != key {
+        debug!(key, found = %manifest.key, "Trace cache entry has another key");
+        return None;
+    }
+
+    if let Some(max_age) = cache.max_age {
+        let age = now_secs().saturating_sub(manifest.created_at);
+        if age > max_age.as_secs() {
+            debug!(key, age, "Trace cache entry expired");
+            let _ = std::fs::remove_dir_all(&entry);
+            return None;
+        }
+    }
+
+    info!(key, traces = manifest.traces, "Trace cache hit");
+    Some((entry, manifest.seed))
+}
+
+/// Store `traces` under `key`, then evict entries beyond `max_entries`.
+pub(crate) fn store(
+    cache: &TraceCache,
+    key: &str,
+    apalache_version: &str,
+    seed: Option<u64>,
+    traces: &[itf::Trace<itf::Value>],
+) -> Result<(), Error> {
+    std::fs::create_dir_all(&cache.dir).map_err(|e| cache_error(&cache.dir, e))?;
+
+    // Write into a staging directory first so concurrent readers never see
+    // a partially written entry.
+    let staging = cache.dir.join(format!(".{key}.{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&staging);
+    std::fs::create_dir_all(&staging).map_err(|e| cache_error(&staging, e))?;
+
+    for (idx, trace) in traces.iter().enumerate() {
+        let path = staging.join(format!("trace{idx:04}.itf.json"));
+        let json = serde_json::to_string_pretty(trace)?;
+        std::fs::write(&path, json).map_err(|e| cache_error(&path, e))?;
+    }
+
+    let manifest = Manifest {
+        key: key.to_string(),
+        apalache_version: apalache_version.to_string(),
+        seed,
+        created_at: now_secs(),
+        traces: traces.len(),
+    };
+    let manifest_path = staging.join(MANIFEST);
+    std::fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)
+        .map_err(|e| cache_error(&manifest_path, e))?;
+
+    let entry = cache.dir.join(key);
+    let _ = std::fs::remove_dir_all(&entry);
+    std::fs::rename(&staging, &entry).map_err(|e| cache_error(&entry, e))?;
+    info!(key, traces = traces.len(), "Stored traces in cache");
+
+    if let Some(max_entries) = cache.max_entries {
+        evict(cache, max_entries)?;
+    }
+    Ok(())
+}
+
+/// Remove the oldest entries until at most `max_entries` remain.
+fn evict(cache: &TraceCache, max_entries: usize) -> Result<(), Error> {
+    let mut entries: Vec<(u64, PathBuf)> = std::fs::read_dir(&cache.dir)
+        .map_err(|e| cache_error(&cache.dir, e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter_map(|path| read_manifest(&path).map(|m| (m.created_at, path)))
+        .collect();
+
+    if entries.len() <= max_entries {
+        return Ok(());
+    }
+
+    entries.sort();
+    let excess = entries.len() - max_entries;
+    for (_, path) in entries.into_iter().take(excess) {
+        debug!(path = %path.display(), "Evicting trace cache entry");
+        std::fs::remove_dir_all(&path).map_err(|e| cache_error(&path, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec_dir() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let spec = dir.path().join("Counter.tla");
+        std::fs::write(&spec, "---- MODULE Counter ----\n====\n").unwrap();
+        (dir, spec)
+    }
+
+    fn trace() -> itf::Trace<itf::Value> {
+        serde_json::from_str(r##"{"#meta": {}, "vars": [], "states": [{"#meta": {"index": 0}}]}"##).unwrap()
+    }
+
+    #[test]
+    fn cache_key_tracks_specs_config_and_version() {
+        let (dir, spec) = spec_dir();
+        let config = ApalacheConfig::from(spec.clone());
+        let key = cache_key(&config, &spec, "0.44.0").unwrap();
+
+        assert_eq!(key, cache_key(&config, &spec, "0.44.0").unwrap());
+        assert_ne!(key, cache_key(&config, &spec, "0.45.0").unwrap());
+
+        let mut other = config.clone();
+        other.max_length = 7;
+        assert_ne!(key, cache_key(&other, &spec, "0.44.0").unwrap());
+
+        let mut irrelevant = config.clone();
+        irrelevant.timeout = Some(Duration::from_secs(1));
+        assert_eq!(key, cache_key(&irrelevant, &spec, "0.44.0").unwrap());
+
+        std::fs::write(dir.path().join("Helpers.tla"), "---- MODULE Helpers ----\n====\n").unwrap();
+        assert_ne!(key, cache_key(&config, &spec, "0.44.0").unwrap());
+    }
+
+    #[test]
+    fn store_then_lookup_round_trips() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cache = TraceCache::new(tmp.path());
+
+        assert!(lookup(&cache, "abc").is_none());
+        store(&cache, "abc", "0.44.0", Some(7), &[trace(), trace()]).unwrap();
+
+        let (entry, seed) = lookup(&cache, "abc").unwrap();
+        assert!(entry.join("trace0001.itf.json").is_file());
+        assert_eq!(seed, Some(7));
+        assert!(lookup(&cache.clone().refresh(true), "abc").is_none());
+
+        // An entry copied under another key is not trusted.
+        std::fs::rename(tmp.path().join("abc"), tmp.path().join("def")).unwrap();
+        assert!(lookup(&cache, "def").is_none());
+    }
+
+    #[test]
+    fn store_evicts_oldest_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cache = TraceCache::new(tmp.path()).max_entries(1);
+
+        store(&cache, "old", "0.44.0", None, &[trace()]).unwrap();
+        let manifest_path = tmp.path().join("old").join(MANIFEST);
+        let mut manifest = read_manifest(&tmp.path().join("old")).unwrap();
+        manifest.created_at = 0;
+        std::fs::write(&manifest_path, serde_json::to_string(&manifest).unwrap()).unwrap();
+
+        store(&cache, "new", "0.44.0", None, &[trace()]).unwrap();
+        assert!(lookup(&cache, "old").is_none());
+        assert!(lookup(&cache, "new").is_some());
+    }
+}
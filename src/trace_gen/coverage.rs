@@ -0,0 +1,129 @@
+//! Coverage-guided trace generation.
+//!
+//! Random simulation rarely reaches actions that need a long or specific
+//! prefix. [`generate_coverage_traces`] instead runs Apalache `check` once
+//! per action against a generated invariant meaning "this action has not
+//! been taken yet", so each counterexample is a trace ending in that action.
+//! Actions without a counterexample are unreachable within `max_length`
+//! steps and are reported in [`CoverageTraces::unreachable`].
+//!
+//! # Example
+//!
+//! ```ignore
+//! use tla_connect::{generate_coverage_traces, replay_traces, ApalacheConfig};
+//!
+//! let config = ApalacheConfig::builder()
+//!     .spec("specs/Counter.tla")
+//!     .max_length(20usize)
+//!     .build()?;
+//!
+//! let coverage = generate_coverage_traces(&config, &["Increment", "Reset"])?;
+//! coverage.ensure_complete()?;
+//! replay_traces(|| CounterDriver::default(), coverage.traces())?;
+//! ```
+
+use super::{generate_traces, ApalacheConfig, ApalacheMode, GeneratedTraces};
+use crate::error::{Error, TraceGenError};
+use std::collections::BTreeMap;
+use tracing::{info, warn};
+
+/// Traces generated to cover a set of actions.
+#[non_exhaustive]
+pub struct CoverageTraces {
+    /// Traces reaching each covered action, keyed by action name.
+    pub covered: BTreeMap<String, GeneratedTraces>,
+
+    /// Actions Apalache found no way to take within `max_length` steps.
+    pub unreachable: Vec<String>,
+}
+
+impl CoverageTraces {
+    /// All generated traces, in action name order.
+    pub fn traces(&self) -> impl Iterator<Item = &itf::Trace<itf::Value>> {
+        self.covered.values().flat_map(|g| &g.traces)
+    }
+
+    /// Fail with [`TraceGenError::UnreachableActions`] if any action was
+    /// not covered.
+    pub fn ensure_complete(&self) -> Result<(), Error> {
+        if self.unreachable.is_empty() {
+            Ok(())
+        } else {
+            Err(TraceGenError::UnreachableActions(self.unreachable.clone()).into())
+        }
+    }
+}
+
+/// Generate at least one trace per reachable action.
+///
+/// Runs Apalache in [`ApalacheMode::Check`] once per action, using `base`
+/// with `cover` set to the action and `max_traces` set to 1. The spec must
+/// record `action_taken`, either itself or through `base.instrument`. If
+/// `actions` is empty, the actions of `base.instrument` are used. If
+/// `base.out_dir` is set, each run writes to its own `action-<i>`
+/// subdirectory. Fails on the first run that fails for any reason other
+/// than the action being unreachable.
+#[must_use = "contains generated traces that should be used for replay"]
+pub fn generate_coverage_traces(base: &ApalacheConfig, actions: &[&str]) -> Result<CoverageTraces, Error> {
+    let actions: Vec<String> = if actions.is_empty() {
+        base.instrument
+            .iter()
+            .flat_map(|mbt| &mbt.actions)
+            .map(|a| a.name.clone())
+            .collect()
+    } else {
+        actions.iter().map(|a| (*a).to_string()).collect()
+    };
+    if actions.is_empty() {
+        return Err(TraceGenError::Instrumentation("no actions to cover".to_string()).into());
+    }
+
+    info!(actions = actions.len(), "Generating coverage traces");
+
+    let run = |(idx, action): (usize, &String)| {
+        let mut config = base.clone();
+        config.mode = ApalacheMode::Check;
+        config.max_traces = 1;
+        config.cover = Some(action.clone());
+        if let Some(ref dir) = base.out_dir {
+            config.out_dir = Some(dir.join(format!("action-{idx}")));
+        }
+        match generate_traces(&config) {
+            Ok(generated) => Ok((action.clone(), Some(generated))),
+            Err(Error::TraceGen(TraceGenError::NoViolation { .. })) => Ok((action.clone(), None)),
+            Err(e) => Err(e),
+        }
+    };
+
+    #[cfg(feature = "parallel")]
+    let results: Result<Vec<_>, Error> = {
+        use rayon::prelude::*;
+        actions.par_iter().enumerate().map(run).collect()
+    };
+
+    #[cfg(not(feature = "parallel"))]
+    let results: Result<Vec<_>, Error> = actions.iter().enumerate().map(run).collect();
+
+    let mut coverage = CoverageTraces {
+        covered: BTreeMap::new(),
+        unreachable: Vec::new(),
+    };
+    for (action, generated) in results? {
+        match generated {
+            Some(generated) => {
+                coverage.covered.insert(action, generated);
+            }
+            None => {
+                warn!(action = %action, max_length = base.max_length, "Action unreachable");
+                coverage.unreachable.push(action);
+            }
+        }
+    }
+
+    info!(
+        covered = coverage.covered.len(),
+        unreachable = coverage.unreachable.len(),
+        "Coverage trace generation finished"
+    );
+    Ok(coverage)
+}
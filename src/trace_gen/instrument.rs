@@ -0,0 +1,371 @@
+//! Spec instrumentation for trace generation.
+//!
+//! Apalache runs against a generated wrapper module that `EXTENDS` the user's
+//! spec. The wrapper carries constant assignments and, optionally, an MBT
+//! instrumentation of `Init`/`Next` that records `action_taken` and
+//! `nondet_picks`, so plain specs can be used without an MBT variant.
+//!
+//! # Example
+//!
+//! ```
+//! use tla_connect::{MbtAction, MbtInstrumentation};
+//!
+//! let instrumentation = MbtInstrumentation::new(vec![
+//!     MbtAction::new("Send").param("n", "Nodes", "Int"),
+//!     MbtAction::new("Tick"),
+//! ]);
+//! assert_eq!(instrumentation.next, "Next");
+//! ```
+
+use super::ConstantAssignment;
+use crate::error::{Error, TraceGenError};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Module name of the generated wrapper that extends the user's spec.
+pub(crate) const WRAPPER_MODULE: &str = "TlaConnectWrapper";
+
+/// Constant initialization predicate defined by the wrapper.
+pub(crate) const WRAPPER_CINIT: &str = "TlaConnectConstInit";
+
+/// Instrumented `Init` predicate defined by the wrapper.
+pub(crate) const WRAPPER_INIT: &str = "TlaConnectInit";
+
+/// Instrumented `Next` relation defined by the wrapper.
+pub(crate) const WRAPPER_NEXT: &str = "TlaConnectNext";
+
+/// Coverage goal invariant defined by the wrapper.
+pub(crate) const WRAPPER_GOAL: &str = "TlaConnectNotTaken";
+
+/// MBT instrumentation of a plain spec.
+///
+/// Generates an `Init`/`Next` pair that behaves like the spec's own but also
+/// records `action_taken` (the action's name, `"init"` initially) and
+/// `nondet_picks`. The latter is a record with one field per action
+/// parameter, each holding `Variant("Some", value)` when the step's action
+/// binds that parameter and `Variant("None", UNIT)` otherwise, mirroring
+/// Quint's MBT mode. Use [`Step::nondet`](crate::Step::nondet) to read it.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MbtInstrumentation {
+    /// Name of the spec's Init predicate (default: "Init").
+    pub init: String,
+
+    /// Name of the spec's Next relation (default: "Next"). Its top-level
+    /// disjuncts must be exactly `actions`; the generated relation replaces it.
+    pub next: String,
+
+    /// The top-level disjunct actions of `next`.
+    pub actions: Vec<MbtAction>,
+}
+
+impl MbtInstrumentation {
+    /// Instrument `Init`/`Next` with the given top-level actions.
+    pub fn new(actions: Vec<MbtAction>) -> Self {
+        Self {
+            init: "Init".into(),
+            next: "Next".into(),
+            actions,
+        }
+    }
+
+    /// Set the name of the spec's Init predicate.
+    pub fn init(mut self, init: impl Into<String>) -> Self {
+        self.init = init.into();
+        self
+    }
+
+    /// Set the name of the spec's Next relation.
+    pub fn next(mut self, next: impl Into<String>) -> Self {
+        self.next = next.into();
+        self
+    }
+}
+
+/// A top-level action of the spec's Next relation.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MbtAction {
+    /// Operator name of the action, recorded as `action_taken`.
+    pub name: String,
+
+    /// Parameters the action is applied to, in operator argument order.
+    pub params: Vec<MbtParam>,
+}
+
+impl MbtAction {
+    /// An action operator without parameters.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            params: Vec::new(),
+        }
+    }
+
+    /// Add a parameter, quantified as `\E name \in domain` and recorded in
+    /// `nondet_picks` with Snowcat type `ty`.
+    pub fn param(mut self, name: impl Into<String>, domain: impl Into<String>, ty: impl Into<String>) -> Self {
+        self.params.push(MbtParam {
+            name: name.into(),
+            domain: domain.into(),
+            ty: ty.into(),
+        });
+        self
+    }
+}
+
+/// A parameter of an [`MbtAction`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MbtParam {
+    /// Parameter name, also the `nondet_picks` field name.
+    pub name: String,
+
+    /// TLA+ expression for the set the parameter ranges over.
+    pub domain: String,
+
+    /// Snowcat type of the parameter (e.g. "Int", "Str").
+    pub ty: String,
+}
+
+/// A generated module that `EXTENDS` the user's spec with tla-connect
+/// definitions. Apalache runs against the wrapper instead of the spec.
+#[derive(Default)]
+pub(crate) struct SpecWrapper {
+    extends: BTreeSet<&'static str>,
+    definitions: Vec<String>,
+}
+
+/// A copy of the spec directory plus the generated wrapper module.
+pub(crate) struct StagedSpec {
+    /// Path to the wrapper module, the main module for Apalache.
+    pub(crate) main: PathBuf,
+    _dir: tempfile::TempDir,
+}
+
+impl SpecWrapper {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.definitions.is_empty()
+    }
+
+    /// Define a constant initialization predicate assigning `constants`,
+    /// conjoined with `base_cinit` if given. Returns the predicate name.
+    pub(crate) fn constants(&mut self, constants: &ConstantAssignment, base_cinit: Option<&str>) -> String {
+        let mut def = format!("{WRAPPER_CINIT} ==\n");
+        if let Some(cinit) = base_cinit {
+            def.push_str(&format!("  /\\ {cinit}\n"));
+        }
+        for (name, value) in constants.iter() {
+            def.push_str(&format!("  /\\ {name} = {}\n", crate::tla::itf_to_tla(value)));
+        }
+        self.extends.insert("TLC");
+        self.definitions.push(def);
+        WRAPPER_CINIT.to_string()
+    }
+
+    /// Declare the MBT variables and define instrumented Init/Next.
+    /// Returns the `(init, next)` operator names.
+    pub(crate) fn instrument(&mut self, mbt: &MbtInstrumentation) -> Result<(String, String), Error> {
+        if mbt.actions.is_empty() {
+            return Err(TraceGenError::Instrumentation(format!(
+                "no actions given for '{}'",
+                mbt.next
+            ))
+            .into());
+        }
+
+        let mut picks: BTreeMap<&str, &str> = BTreeMap::new();
+        for param in mbt.actions.iter().flat_map(|a| &a.params) {
+            if let Some(ty) = picks.insert(&param.name, &param.ty) {
+                if ty != param.ty {
+                    return Err(TraceGenError::Instrumentation(format!(
+                        "parameter '{}' has conflicting types {ty} and {}",
+                        param.name, param.ty
+                    ))
+                    .into());
+                }
+            }
+        }
+
+        let picks_value = |bound: &[MbtParam]| -> String {
+            let fields: Vec<String> = picks
+                .keys()
+                .map(|name| match bound.iter().find(|p| p.name == *name) {
+                    Some(_) => format!("{name} |-> Variant(\"Some\", {name})"),
+                    None => format!("{name} |-> Variant(\"None\", UNIT)"),
+                })
+                .collect();
+            format!("[{}]", fields.join(", "))
+        };
+
+        let mut def = String::from("VARIABLES\n  \\* @type: Str;\n  action_taken");
+        if !picks.is_empty() {
+            let types: Vec<String> = picks
+                .iter()
+                .map(|(name, ty)| format!("{name}: Some({ty}) | None(UNIT)"))
+                .collect();
+            def.push_str(&format!(",\n  \\* @type: {{ {} }};\n  nondet_picks", types.join(", ")));
+            self.extends.insert("Variants");
+        }
+        def.push_str("\n\n");
+
+        def.push_str(&format!("{WRAPPER_INIT} ==\n  /\\ {}\n  /\\ action_taken = \"init\"\n", mbt.init));
+        if !picks.is_empty() {
+            def.push_str(&format!("  /\\ nondet_picks = {}\n", picks_value(&[])));
+        }
+
+        def.push_str(&format!("\n\\* Instrumented from {}\n{WRAPPER_NEXT} ==\n", mbt.next));
+        for action in &mbt.actions {
+            let args: Vec<&str> = action.params.iter().map(|p| p.name.as_str()).collect();
+            let call = if args.is_empty() {
+                action.name.clone()
+            } else {
+                format!("{}({})", action.name, args.join(", "))
+            };
+
+            let mut body = format!(
+                "/\\ {call}\n     /\\ action_taken' = \"{}\"\n",
+                crate::tla::escape_tla_string(&action.name)
+            );
+            if !picks.is_empty() {
+                body.push_str(&format!("     /\\ nondet_picks' = {}\n", picks_value(&action.params)));
+            }
+
+            if action.params.is_empty() {
+                def.push_str(&format!("  \\/ {body}"));
+            } else {
+                let binders: Vec<String> = action
+                    .params
+                    .iter()
+                    .map(|p| format!("{} \\in {}", p.name, p.domain))
+                    .collect();
+                def.push_str(&format!("  \\/ \\E {}:\n     {body}", binders.join(", ")));
+            }
+        }
+
+        self.definitions.push(def);
+        Ok((WRAPPER_INIT.to_string(), WRAPPER_NEXT.to_string()))
+    }
+
+    /// Define an invariant violated once `action` has been taken.
+    /// Returns the invariant name.
+    pub(crate) fn cover(&mut self, action: &str) -> String {
+        self.definitions.push(format!(
+            "{WRAPPER_GOAL} == action_taken # \"{}\"\n",
+            crate::tla::escape_tla_string(action)
+        ));
+        WRAPPER_GOAL.to_string()
+    }
+
+    pub(crate) fn render(&self, spec_module: &str) -> String {
+        let mut out = format!("---- MODULE {WRAPPER_MODULE} ----\n");
+        let mut extends = vec![spec_module];
+        extends.extend(self.extends.iter().copied());
+        out.push_str(&format!("EXTENDS {}\n\n", extends.join(", ")));
+        for def in &self.definitions {
+            out.push_str(def);
+            out.push('\n');
+        }
+        out.push_str("====\n");
+        out
+    }
+
+    /// Copy the spec's directory into a temp directory and write the wrapper
+    /// module next to it.
+    pub(crate) fn stage(&self, spec_path: &Path) -> Result<StagedSpec, Error> {
+        let spec_dir = spec_path
+            .parent()
+            .ok_or_else(|| TraceGenError::SpecNotFound(spec_path.to_path_buf()))?;
+        let spec_module = spec_path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| TraceGenError::SpecNotFound(spec_path.to_path_buf()))?;
+
+        let dir = tempfile::Builder::new()
+            .prefix("tla_spec_")
+            .tempdir()
+            .map_err(|e| TraceGenError::TempDir(e.to_string()))?;
+        crate::util::copy_tla_files(spec_dir, dir.path()).map_err(|(path, e)| TraceGenError::SpecStaging {
+            path,
+            reason: e.to_string(),
+        })?;
+
+        let main = dir.path().join(format!("{WRAPPER_MODULE}.tla"));
+        let module = self.render(spec_module);
+        debug!(module = %module, "Generated wrapper module");
+        std::fs::write(&main, module).map_err(|e| TraceGenError::SpecStaging {
+            path: main.clone(),
+            reason: e.to_string(),
+        })?;
+
+        Ok(StagedSpec { main, _dir: dir })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrapper_constants_conjoins_base_cinit() {
+        let mut wrapper = SpecWrapper::default();
+        let cinit = wrapper.constants(&ConstantAssignment::new().with_int("N", 2), Some("ConstInit"));
+        assert_eq!(cinit, WRAPPER_CINIT);
+
+        let module = wrapper.render("Counter");
+        assert!(module.starts_with("---- MODULE TlaConnectWrapper ----\nEXTENDS Counter, TLC\n"));
+        assert!(module.contains("TlaConnectConstInit ==\n  /\\ ConstInit\n  /\\ N = 2\n"));
+        assert!(module.ends_with("====\n"));
+    }
+
+    #[test]
+    fn cover_defines_goal_invariant() {
+        let mut wrapper = SpecWrapper::default();
+        assert_eq!(wrapper.cover("Reset"), WRAPPER_GOAL);
+        assert!(wrapper.render("Counter").contains("TlaConnectNotTaken == action_taken # \"Reset\"\n"));
+    }
+
+    #[test]
+    fn instrument_records_action_and_picks() {
+        let mut wrapper = SpecWrapper::default();
+        let mbt = MbtInstrumentation::new(vec![
+            MbtAction::new("Send").param("n", "Nodes", "Int"),
+            MbtAction::new("Tick"),
+        ]);
+        let (init, next) = wrapper.instrument(&mbt).unwrap();
+        assert_eq!((init.as_str(), next.as_str()), (WRAPPER_INIT, WRAPPER_NEXT));
+
+        let module = wrapper.render("Net");
+        assert!(module.contains("EXTENDS Net, Variants\n"));
+        assert!(module.contains("\\* @type: { n: Some(Int) | None(UNIT) };\n  nondet_picks"));
+        assert!(module.contains("  /\\ Init\n  /\\ action_taken = \"init\"\n  /\\ nondet_picks = [n |-> Variant(\"None\", UNIT)]\n"));
+        assert!(module.contains(
+            "  \\/ \\E n \\in Nodes:\n     /\\ Send(n)\n     /\\ action_taken' = \"Send\"\n     /\\ nondet_picks' = [n |-> Variant(\"Some\", n)]\n"
+        ));
+        assert!(module.contains(
+            "  \\/ /\\ Tick\n     /\\ action_taken' = \"Tick\"\n     /\\ nondet_picks' = [n |-> Variant(\"None\", UNIT)]\n"
+        ));
+    }
+
+    #[test]
+    fn instrument_without_params_omits_nondet_picks() {
+        let mut wrapper = SpecWrapper::default();
+        wrapper
+            .instrument(&MbtInstrumentation::new(vec![MbtAction::new("Inc")]))
+            .unwrap();
+        let module = wrapper.render("Counter");
+        assert!(!module.contains("nondet_picks"));
+        assert!(!module.contains("Variants"));
+    }
+
+    #[test]
+    fn instrument_rejects_conflicting_param_types() {
+        let mut wrapper = SpecWrapper::default();
+        let mbt = MbtInstrumentation::new(vec![
+            MbtAction::new("A").param("x", "S", "Int"),
+            MbtAction::new("B").param("x", "T", "Str"),
+        ]);
+        assert!(wrapper.instrument(&mbt).is_err());
+    }
+}
@@ -0,0 +1,924 @@
+//! Apalache trace generation (Approach 1).
+//!
+//! Invokes Apalache CLI to generate ITF traces from TLA+ specs via bounded
+//! model checking or random simulation. The [`tlc`] and [`quint`] modules
+//! provide TLC and Quint as alternative backends behind the same
+//! [`TraceGenerator`] trait.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use tla_connect::{generate_traces, ApalacheConfig, ApalacheMode};
+//!
+//! let config = ApalacheConfig::builder()
+//!     .spec("specs/Counter.tla")
+//!     .inv("TraceComplete")
+//!     .max_traces(10)
+//!     .mode(ApalacheMode::Simulate)
+//!     .build();
+//!
+//! let generated = generate_traces(&config)?;
+//! println!("Generated {} traces", generated.traces.len());
+//! ```
+
+pub mod cache;
+pub mod coverage;
+pub mod instrument;
+pub mod parallel;
+pub mod quint;
+pub mod stream;
+pub mod tlc;
+
+pub use cache::TraceCache;
+pub use coverage::{generate_coverage_traces, CoverageTraces};
+pub use instrument::{MbtAction, MbtInstrumentation, MbtParam};
+pub use parallel::{generate_traces_parallel, ParallelOptions};
+pub use quint::{generate_quint_traces, QuintConfig, QuintConfigBuilder, QuintMode};
+pub use stream::{stream_traces, TraceStream};
+pub use tlc::{generate_tlc_traces, TlcConfig, TlcConfigBuilder, TlcMode};
+
+use crate::apalache::{parse_outcome, ApalacheOutcome};
+use crate::error::{ApalacheError, DriverError, Error, TraceGenError};
+use crate::runner::{ApalacheRunner, ApalacheVersion};
+use instrument::SpecWrapper;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+/// Oldest Apalache release trace generation supports: ITF output of `check`
+/// and `simulate` with `--max-error`, `--view` and the `Variants` module.
+/// [`ApalacheRunner::min_version`] can only raise it.
+pub const MIN_GENERATION_VERSION: ApalacheVersion = ApalacheVersion::new(0, 40, 0);
+
+/// Configuration for Apalache trace generation.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ApalacheConfig {
+    /// Path to the TLA+ spec file.
+    pub spec: PathBuf,
+
+    /// Invariant to violate for trace generation (e.g., "TraceComplete").
+    /// Counterexamples to this invariant become test traces.
+    pub inv: String,
+
+    /// Maximum number of traces to generate.
+    pub max_traces: usize,
+
+    /// Maximum trace length (number of steps).
+    pub max_length: usize,
+
+    /// View operator for trace diversity (optional).
+    /// Ensures generated traces differ in this projection.
+    pub view: Option<String>,
+
+    /// Constant initialization predicate (optional, e.g., "ConstInit").
+    /// Used with `--cinit` to set CONSTANTS from a TLA+ predicate.
+    pub cinit: Option<String>,
+
+    /// Apalache execution mode.
+    pub mode: ApalacheMode,
+
+    /// Path to the Apalache binary (default: "apalache-mc").
+    /// Ignored when `runner` is set.
+    pub apalache_bin: String,
+
+    /// How to launch Apalache (optional): a binary, a JAR with JVM
+    /// options or a wrapper command, with an optional minimum version.
+    /// If None, `apalache_bin` is run directly.
+    pub runner: Option<ApalacheRunner>,
+
+    /// Output directory override (default: temp directory).
+    /// If None, a temp directory is created and owned by the returned `GeneratedTraces`.
+    pub out_dir: Option<PathBuf>,
+
+    /// Whether to keep the output directory after `GeneratedTraces` is dropped.
+    /// Only relevant when `out_dir` is None (temp directory).
+    pub keep_outputs: bool,
+
+    /// Timeout for the Apalache subprocess. If None, no timeout is applied.
+    pub timeout: Option<std::time::Duration>,
+
+    /// Values for the spec's CONSTANTS (default: empty).
+    ///
+    /// When non-empty, Apalache runs against a generated wrapper module whose
+    /// constant initialization predicate assigns these values (conjoined with
+    /// `cinit`, if set).
+    pub constants: ConstantAssignment,
+
+    /// MBT instrumentation for specs without `action_taken`/`nondet_picks`
+    /// variables (optional). When set, Apalache runs against a generated
+    /// wrapper whose Init/Next record them.
+    pub instrument: Option<MbtInstrumentation>,
+
+    /// On-disk trace cache (optional). When set, traces are loaded from the
+    /// cache if the spec files, this config and the Apalache version are
+    /// unchanged. A hit only runs `apalache version`, at most once per
+    /// launcher and process. Only [`generate_traces`] uses the cache.
+    pub cache: Option<TraceCache>,
+
+    /// Action to reach (optional). When set, `inv` is replaced by a
+    /// generated invariant stating that `action_taken` is not this action,
+    /// so every counterexample ends by taking it. Requires an
+    /// `action_taken` variable, either in the spec or via `instrument`.
+    /// See [`generate_coverage_traces`].
+    pub cover: Option<String>,
+
+    /// Random seed for `simulate` runs (optional). If None, a random seed
+    /// is chosen per run and recorded in [`GeneratedTraces::seed`], so any
+    /// trace set can be regenerated exactly. Not used in `Check` mode.
+    pub seed: Option<u64>,
+
+    /// Parse and type-check the spec before generating traces (default:
+    /// false). Spec errors are then reported as [`ApalacheError::Failed`]
+    /// with diagnostics instead of surfacing from the generation run.
+    pub preflight: bool,
+
+    /// Keep the traces written so far when `timeout` fires (default:
+    /// false). The result is then marked [`GeneratedTraces::partial`]; a
+    /// timeout before any complete trace was written is still an error.
+    pub best_effort: bool,
+}
+
+impl Default for ApalacheConfig {
+    fn default() -> Self {
+        Self {
+            spec: PathBuf::new(),
+            inv: "TraceComplete".into(),
+            max_traces: 100,
+            max_length: 50,
+            view: None,
+            cinit: None,
+            mode: ApalacheMode::Simulate,
+            apalache_bin: "apalache-mc".into(),
+            runner: None,
+            out_dir: None,
+            keep_outputs: false,
+            timeout: None,
+            constants: ConstantAssignment::default(),
+            instrument: None,
+            cache: None,
+            cover: None,
+            seed: None,
+            preflight: false,
+            best_effort: false,
+        }
+    }
+}
+
+crate::builder::impl_builder!(ApalacheConfig, ApalacheConfigBuilder {
+    required { spec: PathBuf }
+    optional { inv: String, max_traces: usize, max_length: usize,
+               mode: ApalacheMode, apalache_bin: String, keep_outputs: bool,
+               constants: ConstantAssignment, preflight: bool, best_effort: bool }
+    optional_or { view: String, cinit: String, out_dir: PathBuf, timeout: std::time::Duration,
+                  instrument: MbtInstrumentation, cache: TraceCache, cover: String,
+                  runner: ApalacheRunner, seed: u64 }
+});
+
+/// Apalache execution mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ApalacheMode {
+    /// Bounded model checking (systematic exploration).
+    /// Uses `apalache-mc check`. Exhaustive up to `max_length` steps.
+    Check,
+
+    /// Random simulation (like Quint `run`).
+    /// Uses `apalache-mc simulate`. Faster but not exhaustive.
+    Simulate,
+}
+
+/// An instantiation of the spec's CONSTANTS, e.g. `N = 3, Capacity = 5`.
+///
+/// Values are ITF values, so drivers can [`decode`](Self::decode) them with
+/// the same serde machinery used for spec states.
+///
+/// # Example
+///
+/// ```
+/// use tla_connect::ConstantAssignment;
+///
+/// let constants = ConstantAssignment::new().with_int("N", 3).with_str("Mode", "fast");
+/// assert_eq!(constants.to_string(), "Mode = \"fast\", N = 3");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConstantAssignment {
+    values: BTreeMap<String, itf::Value>,
+}
+
+impl ConstantAssignment {
+    /// Create an empty assignment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Assign an arbitrary ITF value to a constant.
+    pub fn with(mut self, name: impl Into<String>, value: itf::Value) -> Self {
+        self.values.insert(name.into(), value);
+        self
+    }
+
+    /// Assign an integer to a constant.
+    pub fn with_int(self, name: impl Into<String>, value: i64) -> Self {
+        self.with(name, itf::Value::BigInt(itf::value::BigInt::new(value)))
+    }
+
+    /// Assign a string to a constant.
+    pub fn with_str(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.with(name, itf::Value::String(value.into()))
+    }
+
+    /// Assign a boolean to a constant.
+    pub fn with_bool(self, name: impl Into<String>, value: bool) -> Self {
+        self.with(name, itf::Value::Bool(value))
+    }
+
+    /// Get the value assigned to a constant.
+    pub fn get(&self, name: &str) -> Option<&itf::Value> {
+        self.values.get(name)
+    }
+
+    /// Iterate over `(name, value)` pairs in name order.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &itf::Value)> {
+        self.values.iter()
+    }
+
+    /// Whether no constants are assigned.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The assignment as an ITF record, keyed by constant name.
+    pub fn to_value(&self) -> itf::Value {
+        itf::Value::Record(self.values.clone().into())
+    }
+
+    /// Deserialize the assignment into a user-defined type.
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T, DriverError> {
+        T::deserialize(self.to_value()).map_err(|e| DriverError::StateExtraction(e.to_string()))
+    }
+}
+
+impl FromIterator<(String, itf::Value)> for ConstantAssignment {
+    fn from_iter<I: IntoIterator<Item = (String, itf::Value)>>(iter: I) -> Self {
+        Self {
+            values: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for ConstantAssignment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let assignments: Vec<String> = self
+            .values
+            .iter()
+            .map(|(name, value)| format!("{name} = {}", crate::tla::itf_to_tla(value)))
+            .collect();
+        write!(f, "{}", assignments.join(", "))
+    }
+}
+
+/// Result of trace generation, owning the output directory.
+///
+/// The temp directory (if created) is cleaned up when this struct is dropped,
+/// unless `keep_outputs` was set in the config.
+#[non_exhaustive]
+pub struct GeneratedTraces {
+    /// The generated ITF traces.
+    pub traces: Vec<itf::Trace<itf::Value>>,
+
+    /// Path to the output directory containing raw Apalache output.
+    pub out_dir: PathBuf,
+
+    /// How the Apalache run ended (None when loaded from the trace cache or
+    /// generated by another backend).
+    pub outcome: Option<ApalacheOutcome>,
+
+    /// Whether the traces were loaded from the trace cache instead of
+    /// running Apalache. If so, `out_dir` is the cache entry directory.
+    pub cached: bool,
+
+    /// The seed `simulate` ran with (None in `Check` mode). Set it as
+    /// [`ApalacheConfig::seed`] to regenerate the same traces.
+    pub seed: Option<u64>,
+
+    /// Whether Apalache was stopped by `timeout` before finishing, leaving
+    /// only the traces written so far (see [`ApalacheConfig::best_effort`]).
+    /// Partial results are never stored in the trace cache.
+    pub partial: bool,
+
+    /// Owned temp directory (cleaned up on drop unless persisted).
+    _temp: Option<tempfile::TempDir>,
+}
+
+impl GeneratedTraces {
+    /// Persist the output directory, preventing cleanup on drop.
+    ///
+    /// Returns the path to the persisted directory.
+    pub fn persist(mut self) -> PathBuf {
+        if let Some(temp) = self._temp.take() {
+            temp.keep()
+        } else {
+            self.out_dir.clone()
+        }
+    }
+}
+
+/// A model checker backend producing ITF traces for replay.
+///
+/// Implemented by [`ApalacheConfig`], [`TlcConfig`] and [`QuintConfig`], so
+/// test code can switch backends per spec without changing how traces are
+/// replayed.
+pub trait TraceGenerator {
+    /// Run the backend and collect its traces.
+    fn generate(&self) -> Result<GeneratedTraces, Error>;
+}
+
+impl TraceGenerator for ApalacheConfig {
+    fn generate(&self) -> Result<GeneratedTraces, Error> {
+        generate_traces(self)
+    }
+}
+
+/// Generate ITF traces by invoking Apalache on a TLA+ spec.
+///
+/// Returns a `GeneratedTraces` struct containing the parsed traces and
+/// owning the output directory (cleaned up on drop unless persisted).
+/// If `config.cache` is set, a cache hit returns the stored traces without
+/// running Apalache beyond detecting its version, and a miss stores the
+/// newly generated ones.
+#[must_use = "contains generated traces that should be used for replay"]
+pub fn generate_traces(config: &ApalacheConfig) -> Result<GeneratedTraces, Error> {
+    let Some(ref trace_cache) = config.cache else {
+        return run_apalache(config);
+    };
+
+    let (key, hit) = cache_lookup(config, trace_cache)?;
+    if let Some(cached) = hit {
+        return Ok(cached);
+    }
+    let generated = run_apalache(config)?;
+    cache_store(config, trace_cache, &key, &generated)?;
+    Ok(generated)
+}
+
+/// The cache key for `config` and the cached traces, if any. Runs Apalache
+/// only to detect its version, if not yet known.
+fn cache_lookup(config: &ApalacheConfig, trace_cache: &TraceCache) -> Result<(String, Option<GeneratedTraces>), Error> {
+    let spec_path = config
+        .spec
+        .canonicalize()
+        .map_err(|_| TraceGenError::SpecNotFound(config.spec.clone()))?;
+    let runner = crate::runner::resolve(&config.runner, &config.apalache_bin);
+    let version = runner.version().map_err(TraceGenError::from)?;
+    let key = cache::cache_key(config, &spec_path, &version.to_string())?;
+
+    let Some((entry, seed)) = cache::lookup(trace_cache, &key) else {
+        return Ok((key, None));
+    };
+    let traces = collect_itf_traces(&entry)?;
+    Ok((
+        key,
+        Some(GeneratedTraces {
+            traces,
+            out_dir: entry,
+            outcome: None,
+            cached: true,
+            seed,
+            partial: false,
+            _temp: None,
+        }),
+    ))
+}
+
+/// Store the traces of a complete run under `key`; partial runs are not
+/// cached.
+fn cache_store(
+    config: &ApalacheConfig,
+    trace_cache: &TraceCache,
+    key: &str,
+    generated: &GeneratedTraces,
+) -> Result<(), Error> {
+    if generated.partial {
+        return Ok(());
+    }
+    let runner = crate::runner::resolve(&config.runner, &config.apalache_bin);
+    let version = runner.version().map_err(TraceGenError::from)?.to_string();
+    cache::store(trace_cache, key, &version, generated.seed, &generated.traces)
+}
+
+/// Run Apalache for `config`, bypassing the trace cache.
+fn run_apalache(config: &ApalacheConfig) -> Result<GeneratedTraces, Error> {
+    let (mut cmd, run) = prepare_run(config)?;
+    match crate::util::run_with_timeout(&mut cmd, config.timeout) {
+        Ok(output) => finish_run(run, &output),
+        Err(ApalacheError::Timeout { duration }) if config.best_effort => finish_partial_run(run, duration),
+        Err(e) => Err(TraceGenError::from(e).into()),
+    }
+}
+
+/// Async variant of [`generate_traces`] based on `tokio::process`.
+///
+/// Apalache's stdout and stderr are read concurrently while it runs.
+/// Dropping the returned future cancels generation and kills Apalache's
+/// process group, as does hitting `config.timeout`. Cache lookups, version
+/// detection and the optional pre-flight check run on tokio's blocking
+/// thread pool. The trace cache is used as in [`generate_traces`].
+#[cfg(feature = "async")]
+#[must_use = "contains generated traces that should be used for replay"]
+pub async fn generate_traces_async(config: &ApalacheConfig) -> Result<GeneratedTraces, Error> {
+    // The cache and key to store the traces under after a miss.
+    let mut pending = None;
+    if let Some(ref trace_cache) = config.cache {
+        let (lookup_config, lookup_cache) = (config.clone(), trace_cache.clone());
+        let (key, hit) = crate::util::spawn_blocking(move || cache_lookup(&lookup_config, &lookup_cache)).await?;
+        if let Some(cached) = hit {
+            return Ok(cached);
+        }
+        pending = Some((trace_cache.clone(), key));
+    }
+
+    let prepare_config = config.clone();
+    let (cmd, run) = crate::util::spawn_blocking(move || prepare_run(&prepare_config)).await?;
+
+    info!(
+        spec = %config.spec.display(),
+        inv = %run.inv,
+        "Running Apalache asynchronously"
+    );
+
+    let generated = match crate::util::run_with_timeout_async(cmd, config.timeout).await {
+        Ok(output) => finish_run(run, &output)?,
+        Err(ApalacheError::Timeout { duration }) if config.best_effort => finish_partial_run(run, duration)?,
+        Err(e) => return Err(TraceGenError::from(e).into()),
+    };
+    let Some((trace_cache, key)) = pending else {
+        return Ok(generated);
+    };
+    let store_config = config.clone();
+    crate::util::spawn_blocking(move || cache_store(&store_config, &trace_cache, &key, &generated).map(|()| generated))
+        .await
+}
+
+/// Turn a finished Apalache run into its traces.
+fn finish_run(run: PreparedRun, output: &std::process::Output) -> Result<GeneratedTraces, Error> {
+    let PreparedRun {
+        inv,
+        seed,
+        out_dir,
+        temp,
+        spec_dir,
+        ..
+    } = run;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let outcome = check_outcome(&stdout, &stderr, output.status.code(), &spec_dir)?;
+
+    let traces = match collect_itf_traces(&out_dir) {
+        Err(Error::TraceGen(TraceGenError::NoTracesFound(_))) if outcome == ApalacheOutcome::NoError => {
+            return Err(TraceGenError::NoViolation { inv }.into());
+        }
+        result => result?,
+    };
+
+    Ok(GeneratedTraces {
+        traces,
+        out_dir,
+        outcome: Some(outcome),
+        cached: false,
+        seed,
+        partial: false,
+        _temp: temp,
+    })
+}
+
+/// Keep the traces of a run stopped by its timeout.
+///
+/// Apalache may have been killed while writing the last trace file, so
+/// files that fail to parse are skipped. Without any complete trace, the
+/// timeout is reported as usual.
+fn finish_partial_run(run: PreparedRun, duration: std::time::Duration) -> Result<GeneratedTraces, Error> {
+    let PreparedRun {
+        seed, out_dir, temp, ..
+    } = run;
+
+    let mut paths = walkdir(&out_dir)?;
+    paths.sort();
+    let traces: Vec<_> = paths
+        .iter()
+        .filter(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.ends_with(".itf.json")))
+        .filter_map(|p| match read_itf_trace(p) {
+            Ok(trace) => Some(trace),
+            Err(e) => {
+                debug!(error = %e, "Skipping incomplete ITF trace");
+                None
+            }
+        })
+        .collect();
+
+    if traces.is_empty() {
+        return Err(TraceGenError::from(ApalacheError::Timeout { duration }).into());
+    }
+
+    warn!(
+        count = traces.len(),
+        timeout = ?duration,
+        "Apalache timed out, keeping partial traces"
+    );
+    Ok(GeneratedTraces {
+        traces,
+        out_dir,
+        outcome: None,
+        cached: false,
+        seed,
+        partial: true,
+        _temp: temp,
+    })
+}
+
+/// State of an Apalache invocation needed after it exits.
+struct PreparedRun {
+    /// The invariant passed to Apalache.
+    inv: String,
+    /// The `--seed` passed to Apalache, if any.
+    seed: Option<u64>,
+    out_dir: PathBuf,
+    /// Owned temp output directory, if `config.out_dir` was not set.
+    temp: Option<tempfile::TempDir>,
+    /// Directory of the user's spec, for resolving diagnostics.
+    spec_dir: PathBuf,
+    /// Staged wrapper module; must outlive the Apalache process.
+    _staged: Option<instrument::StagedSpec>,
+}
+
+/// The output directory: `out_dir` if set, otherwise a temp directory,
+/// returned as owned unless `keep_outputs` is set.
+fn output_dir(out_dir: Option<&PathBuf>, keep_outputs: bool) -> Result<(PathBuf, Option<tempfile::TempDir>), Error> {
+    Ok(match out_dir {
+        Some(dir) => (dir.clone(), None),
+        None => {
+            let tmp = tempfile::tempdir()
+                .map_err(|e| TraceGenError::TempDir(e.to_string()))?;
+            if keep_outputs {
+                let path = tmp.keep();
+                (path, None)
+            } else {
+                let path = tmp.path().to_path_buf();
+                (path, Some(tmp))
+            }
+        }
+    })
+}
+
+/// Resolve the output directory, stage the wrapper module if needed and
+/// build the Apalache command line for `config`.
+fn prepare_run(config: &ApalacheConfig) -> Result<(std::process::Command, PreparedRun), Error> {
+    let (out_dir, temp) = output_dir(config.out_dir.as_ref(), config.keep_outputs)?;
+
+    let spec_path = config
+        .spec
+        .canonicalize()
+        .map_err(|_| TraceGenError::SpecNotFound(config.spec.clone()))?;
+
+    let mut wrapper = SpecWrapper::default();
+    let cinit = if config.constants.is_empty() {
+        config.cinit.clone()
+    } else {
+        Some(wrapper.constants(&config.constants, config.cinit.as_deref()))
+    };
+    let init_next = config
+        .instrument
+        .as_ref()
+        .map(|mbt| wrapper.instrument(mbt))
+        .transpose()?;
+    let inv = match config.cover {
+        Some(ref action) => wrapper.cover(action),
+        None => config.inv.clone(),
+    };
+    let staged = if wrapper.is_empty() {
+        None
+    } else {
+        Some(wrapper.stage(&spec_path)?)
+    };
+    let main_spec = staged.as_ref().map_or(&spec_path, |s| &s.main);
+    let spec_dir = spec_path.parent().unwrap_or(Path::new(".")).to_path_buf();
+
+    let runner = crate::runner::resolve(&config.runner, &config.apalache_bin);
+    runner
+        .ensure_at_least(Some(MIN_GENERATION_VERSION))
+        .map_err(TraceGenError::from)?;
+    let mut cmd = runner.command();
+
+    if config.preflight {
+        crate::apalache::run_spec_check(&runner, main_spec, &spec_dir, true, config.timeout)
+            .map_err(TraceGenError::from)?;
+    }
+
+    let mut seed = None;
+    match config.mode {
+        ApalacheMode::Simulate => {
+            let run_seed = config.seed.unwrap_or_else(random_seed);
+            seed = Some(run_seed);
+            cmd.arg("simulate")
+                .arg(format!("--inv={inv}"))
+                .arg(format!("--max-run={}", config.max_traces))
+                .arg(format!("--length={}", config.max_length))
+                .arg(format!("--seed={run_seed}"));
+        }
+        ApalacheMode::Check => {
+            cmd.arg("check")
+                .arg(format!("--inv={inv}"))
+                .arg(format!("--max-error={}", config.max_traces))
+                .arg(format!("--length={}", config.max_length));
+        }
+    }
+
+    if let Some((ref init, ref next)) = init_next {
+        cmd.arg(format!("--init={init}")).arg(format!("--next={next}"));
+    }
+
+    if let Some(ref cinit) = cinit {
+        cmd.arg(format!("--cinit={cinit}"));
+    }
+
+    if let Some(ref view) = config.view {
+        cmd.arg(format!("--view={view}"));
+    }
+
+    cmd.arg(format!("--out-dir={}", out_dir.display()))
+        .arg::<&std::path::Path>(main_spec);
+
+    info!(
+        mode = ?config.mode,
+        spec = %spec_path.display(),
+        inv = %inv,
+        seed = ?seed,
+        constants = %config.constants,
+        "Running Apalache trace generation"
+    );
+    debug!("Command: {:?}", cmd);
+
+    Ok((
+        cmd,
+        PreparedRun {
+            inv,
+            seed,
+            out_dir,
+            temp,
+            spec_dir,
+            _staged: staged,
+        },
+    ))
+}
+
+/// A fresh seed for a `simulate` run, kept within Apalache's positive
+/// 32-bit range.
+fn random_seed() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos()),
+    );
+    hasher.finish() % (i32::MAX as u64)
+}
+
+/// Classify a finished Apalache run, failing unless it may have written
+/// usable traces.
+fn check_outcome(
+    stdout: &str,
+    stderr: &str,
+    exit_code: Option<i32>,
+    spec_dir: &Path,
+) -> Result<ApalacheOutcome, Error> {
+    let outcome = parse_outcome(stdout, stderr, exit_code).with_spec_dir(spec_dir);
+    debug!(outcome = %outcome, "Apalache finished");
+
+    match outcome {
+        ApalacheOutcome::InvariantViolated | ApalacheOutcome::Deadlock | ApalacheOutcome::NoError => Ok(outcome),
+        ApalacheOutcome::Unknown { exit_code } => Err(TraceGenError::from(ApalacheError::Execution {
+            exit_code,
+            message: format!("stdout: {stdout}\nstderr: {stderr}"),
+        })
+        .into()),
+        outcome => Err(TraceGenError::from(ApalacheError::Failed { outcome }).into()),
+    }
+}
+
+/// Traces generated for one entry of a constant matrix.
+#[non_exhaustive]
+pub struct ConfiguredTraces {
+    /// The constant assignment Apalache was run with.
+    pub constants: ConstantAssignment,
+
+    /// The traces generated under that assignment.
+    pub generated: GeneratedTraces,
+}
+
+/// Generate traces for each constant assignment in `matrix`.
+///
+/// Runs Apalache once per assignment using `base` with its `constants`
+/// replaced. With the `parallel` feature the runs execute concurrently.
+/// If `base.out_dir` is set, each run writes to its own `config-<i>`
+/// subdirectory. Fails on the first run that fails.
+///
+/// Replay the result with [`replay_traces_matrix`](crate::replay_traces_matrix)
+/// to build each driver for its matching configuration.
+#[must_use = "contains generated traces that should be used for replay"]
+pub fn generate_traces_matrix(
+    base: &ApalacheConfig,
+    matrix: &[ConstantAssignment],
+) -> Result<Vec<ConfiguredTraces>, Error> {
+    let run = |(idx, constants): (usize, &ConstantAssignment)| {
+        let mut config = base.clone();
+        config.constants = constants.clone();
+        if let Some(ref dir) = base.out_dir {
+            config.out_dir = Some(dir.join(format!("config-{idx}")));
+        }
+        generate_traces(&config).map(|generated| ConfiguredTraces {
+            constants: constants.clone(),
+            generated,
+        })
+    };
+
+    info!(configurations = matrix.len(), "Generating traces over constant matrix");
+
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        matrix.par_iter().enumerate().map(run).collect()
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    {
+        matrix.iter().enumerate().map(run).collect()
+    }
+}
+
+/// Collect all `.itf.json` files from an Apalache output directory.
+fn collect_itf_traces(out_dir: &Path) -> Result<Vec<itf::Trace<itf::Value>>, Error> {
+    let mut traces = Vec::new();
+    let mut paths = walkdir(out_dir)?;
+    paths.sort();
+
+    for path in paths {
+        let filename = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        if filename.ends_with(".itf.json") {
+            debug!(path = %path.display(), "Found ITF trace file");
+            traces.push(read_itf_trace(&path)?);
+        }
+    }
+
+    if traces.is_empty() {
+        return Err(TraceGenError::NoTracesFound(out_dir.to_path_buf()).into());
+    }
+
+    info!(count = traces.len(), "Collected ITF traces");
+    Ok(traces)
+}
+
+/// Read and parse a single `.itf.json` file.
+fn read_itf_trace(path: &Path) -> Result<itf::Trace<itf::Value>, Error> {
+    let content = std::fs::read_to_string(path).map_err(|e| TraceGenError::TraceParse {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+    let trace = serde_json::from_str(&content).map_err(|e| TraceGenError::TraceParse {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+    Ok(trace)
+}
+
+impl From<PathBuf> for ApalacheConfig {
+    fn from(spec: PathBuf) -> Self {
+        Self {
+            spec,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&str> for ApalacheConfig {
+    fn from(spec: &str) -> Self {
+        Self {
+            spec: PathBuf::from(spec),
+            ..Default::default()
+        }
+    }
+}
+
+/// Simple recursive directory walker.
+fn walkdir(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+    for entry in std::fs::read_dir(dir).map_err(|e| TraceGenError::from(crate::error::DirectoryReadError {
+        path: dir.to_path_buf(),
+        reason: e.to_string(),
+    }))? {
+        let entry = entry.map_err(|e| TraceGenError::from(crate::error::DirectoryReadError {
+            path: dir.to_path_buf(),
+            reason: e.to_string(),
+        }))?;
+        let path = entry.path();
+        if path.is_dir() {
+            files.extend(walkdir(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walkdir_empty_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let files = walkdir(tmp.path()).unwrap();
+        assert!(files.is_empty());
+    }
+
+    #[test]
+    fn walkdir_with_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("a.txt"), "").unwrap();
+        std::fs::write(tmp.path().join("b.txt"), "").unwrap();
+
+        let files = walkdir(tmp.path()).unwrap();
+        assert_eq!(files.len(), 2);
+    }
+
+    #[test]
+    fn walkdir_recursive() {
+        let tmp = tempfile::tempdir().unwrap();
+        let sub = tmp.path().join("sub");
+        std::fs::create_dir(&sub).unwrap();
+        std::fs::write(tmp.path().join("top.txt"), "").unwrap();
+        std::fs::write(sub.join("nested.txt"), "").unwrap();
+
+        let files = walkdir(tmp.path()).unwrap();
+        assert_eq!(files.len(), 2);
+    }
+
+    #[test]
+    fn walkdir_nonexistent_returns_empty() {
+        let files = walkdir(Path::new("/nonexistent/dir")).unwrap();
+        assert!(files.is_empty());
+    }
+
+    #[test]
+    fn builder_missing_spec_returns_error() {
+        let result = ApalacheConfig::builder().build();
+        assert!(result.is_err());
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("spec"));
+    }
+
+    #[test]
+    fn builder_with_spec_succeeds() {
+        let config = ApalacheConfig::builder()
+            .spec("test.tla")
+            .build()
+            .unwrap();
+        assert_eq!(config.spec, PathBuf::from("test.tla"));
+        assert_eq!(config.inv, "TraceComplete");
+        assert_eq!(config.max_traces, 100);
+    }
+
+    #[test]
+    fn constant_assignment_display_sorted() {
+        let constants = ConstantAssignment::new()
+            .with_int("N", 3)
+            .with_bool("Fair", true);
+        assert_eq!(constants.to_string(), "Fair = TRUE, N = 3");
+    }
+
+    #[test]
+    fn constant_assignment_decode() {
+        #[derive(serde::Deserialize)]
+        struct Params {
+            #[serde(rename = "N")]
+            n: i64,
+        }
+
+        let params: Params = ConstantAssignment::new().with_int("N", 5).decode().unwrap();
+        assert_eq!(params.n, 5);
+    }
+
+    #[test]
+    fn config_from_str() {
+        let config: ApalacheConfig = "test.tla".into();
+        assert_eq!(config.spec, PathBuf::from("test.tla"));
+    }
+}
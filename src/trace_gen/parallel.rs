@@ -0,0 +1,152 @@
+//! Multi-process trace generation.
+//!
+//! A single `apalache-mc simulate` process keeps roughly one core busy.
+//! [`generate_traces_parallel`] runs several of them at once, each with its
+//! own seed, output directory and share of `max_traces`, and merges their
+//! traces into one [`GeneratedTraces`].
+//!
+//! # Example
+//!
+//! ```ignore
+//! use tla_connect::{generate_traces_parallel, ApalacheConfig, ParallelOptions};
+//!
+//! let config = ApalacheConfig::builder()
+//!     .spec("specs/Counter.tla")
+//!     .max_traces(1600usize)
+//!     .build()?;
+//!
+//! let generated = generate_traces_parallel(&config, ParallelOptions::new(16).dedup(true))?;
+//! ```
+
+use super::{run_apalache, ApalacheConfig, ApalacheMode, GeneratedTraces};
+use crate::error::{Error, TraceGenError};
+use std::collections::HashSet;
+use tracing::{debug, info};
+
+/// Options for [`generate_traces_parallel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ParallelOptions {
+    /// Number of Apalache processes to run at once.
+    pub workers: usize,
+
+    /// Drop traces whose states duplicate an earlier trace.
+    pub dedup: bool,
+}
+
+impl ParallelOptions {
+    /// Run `workers` processes without deduplication.
+    pub fn new(workers: usize) -> Self {
+        Self { workers, dedup: false }
+    }
+
+    /// Enable or disable deduplication of identical traces.
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+}
+
+/// Generate traces with several Apalache `simulate` processes.
+///
+/// Worker `i` runs with seed `base + i`, where `base` is `config.seed` or a
+/// random seed, recorded as the merged result's [`GeneratedTraces::seed`].
+/// `max_traces` is split evenly between workers, and each writes to its
+/// own `worker-<i>` subdirectory of the merged `out_dir`. `timeout` applies
+/// to each worker; with `best_effort`, the merged result is partial if any
+/// worker's was. A worker finding no violation contributes no traces;
+/// any other worker failure fails the whole run with
+/// [`TraceGenError::Worker`]. The trace cache is not used.
+///
+/// In [`ApalacheMode::Check`] the search is deterministic, so a single
+/// worker runs.
+#[must_use = "contains generated traces that should be used for replay"]
+pub fn generate_traces_parallel(config: &ApalacheConfig, options: ParallelOptions) -> Result<GeneratedTraces, Error> {
+    let workers = match config.mode {
+        ApalacheMode::Simulate => options.workers.clamp(1, config.max_traces.max(1)),
+        ApalacheMode::Check => 1,
+    };
+
+    let (out_dir, temp) = super::output_dir(config.out_dir.as_ref(), config.keep_outputs)?;
+    let base_seed = config.seed.unwrap_or_else(super::random_seed);
+
+    info!(workers, base_seed, max_traces = config.max_traces, "Generating traces in parallel");
+
+    let worker_configs: Vec<ApalacheConfig> = (0..workers)
+        .map(|idx| {
+            let mut worker = config.clone();
+            worker.max_traces = config.max_traces / workers + usize::from(idx < config.max_traces % workers);
+            worker.seed = Some(base_seed.wrapping_add(idx as u64));
+            worker.out_dir = Some(out_dir.join(format!("worker-{idx}")));
+            worker.cache = None;
+            worker
+        })
+        .collect();
+
+    let results: Vec<Result<GeneratedTraces, Error>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = worker_configs
+            .iter()
+            .map(|worker| scope.spawn(move || run_apalache(worker)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+            .collect()
+    });
+
+    let mut traces = Vec::new();
+    let mut outcome = None;
+    let mut partial = false;
+    let mut seen = HashSet::new();
+    let mut duplicates = 0;
+
+    for (worker, result) in results.into_iter().enumerate() {
+        let generated = match result {
+            Ok(generated) => generated,
+            Err(Error::TraceGen(TraceGenError::NoViolation { .. })) => {
+                debug!(worker, "Worker found no violation");
+                continue;
+            }
+            Err(e) => {
+                return Err(TraceGenError::Worker {
+                    worker,
+                    seed: worker_configs[worker].seed,
+                    source: Box::new(e),
+                }
+                .into())
+            }
+        };
+
+        debug!(worker, traces = generated.traces.len(), "Worker finished");
+        outcome = outcome.or(generated.outcome.clone());
+        partial |= generated.partial;
+        for trace in generated.traces {
+            if options.dedup {
+                let states: Vec<&itf::Value> = trace.states.iter().map(|s| &s.value).collect();
+                if !seen.insert(serde_json::to_string(&states)?) {
+                    duplicates += 1;
+                    continue;
+                }
+            }
+            traces.push(trace);
+        }
+    }
+
+    if traces.is_empty() {
+        return Err(TraceGenError::NoViolation {
+            inv: config.inv.clone(),
+        }
+        .into());
+    }
+
+    info!(count = traces.len(), duplicates, "Merged parallel traces");
+    Ok(GeneratedTraces {
+        traces,
+        out_dir,
+        outcome,
+        cached: false,
+        seed: matches!(config.mode, ApalacheMode::Simulate).then_some(base_seed),
+        partial,
+        _temp: temp,
+    })
+}
@@ -0,0 +1,237 @@
+//! Quint trace generation backend.
+//!
+//! [`generate_quint_traces`] runs `quint run` (random simulation) or
+//! `quint test` with ITF output and collects the written traces. With
+//! `mbt` enabled, Quint records `mbt::actionTaken` and `mbt::nondetPicks`,
+//! which replay reads like `action_taken` and `nondet_picks`, so the same
+//! [`Driver`](crate::Driver) replays traces from TLA+ and Quint specs.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use tla_connect::{replay_traces, QuintConfig, TraceGenerator};
+//!
+//! let config = QuintConfig::builder()
+//!     .spec("specs/counter.qnt")
+//!     .max_samples(1000usize)
+//!     .max_traces(50usize)
+//!     .build()?;
+//!
+//! let generated = config.generate()?;
+//! replay_traces(|| CounterDriver::default(), &generated.traces)?;
+//! ```
+
+use super::{collect_itf_traces, output_dir, GeneratedTraces, TraceGenerator};
+use crate::error::{ApalacheError, Error, TraceGenError};
+use std::path::PathBuf;
+use tracing::{debug, info};
+
+/// Configuration for Quint trace generation.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct QuintConfig {
+    /// Path to the Quint spec file (`.qnt`).
+    pub spec: PathBuf,
+
+    /// Quint subcommand to run.
+    pub mode: QuintMode,
+
+    /// Path to the Quint executable (default: "quint").
+    pub quint_bin: String,
+
+    /// Main module (`--main`, optional).
+    pub main: Option<String>,
+
+    /// Init action (`--init`, optional). Only used in `Run` mode.
+    pub init: Option<String>,
+
+    /// Step action (`--step`, optional). Only used in `Run` mode.
+    pub step: Option<String>,
+
+    /// Invariant to check (`--invariant`, optional). Only used in `Run`
+    /// mode. When set, a run without a violation fails with
+    /// [`TraceGenError::NoViolation`] instead of returning sampled traces.
+    pub invariant: Option<String>,
+
+    /// Test name pattern (`--match`, optional). Only used in `Test` mode.
+    pub test_match: Option<String>,
+
+    /// Number of runs to sample (`--max-samples`).
+    pub max_samples: usize,
+
+    /// Maximum steps per run (`--max-steps`). Only used in `Run` mode.
+    pub max_steps: usize,
+
+    /// Number of traces to write (`--n-traces`). Only used in `Run` mode.
+    pub max_traces: usize,
+
+    /// Record `mbt::actionTaken` and `mbt::nondetPicks` (`--mbt`,
+    /// default: true). Only used in `Run` mode.
+    pub mbt: bool,
+
+    /// Output directory override (default: temp directory).
+    /// If None, a temp directory is created and owned by the returned `GeneratedTraces`.
+    pub out_dir: Option<PathBuf>,
+
+    /// Whether to keep the output directory after `GeneratedTraces` is dropped.
+    /// Only relevant when `out_dir` is None (temp directory).
+    pub keep_outputs: bool,
+
+    /// Timeout for the Quint subprocess. If None, no timeout is applied.
+    pub timeout: Option<std::time::Duration>,
+
+    /// Random seed (`--seed`, optional). If None, a random seed is chosen
+    /// and recorded in [`GeneratedTraces::seed`].
+    pub seed: Option<u64>,
+}
+
+impl Default for QuintConfig {
+    fn default() -> Self {
+        Self {
+            spec: PathBuf::new(),
+            mode: QuintMode::Run,
+            quint_bin: "quint".into(),
+            main: None,
+            init: None,
+            step: None,
+            invariant: None,
+            test_match: None,
+            max_samples: 100,
+            max_steps: 50,
+            max_traces: 100,
+            mbt: true,
+            out_dir: None,
+            keep_outputs: false,
+            timeout: None,
+            seed: None,
+        }
+    }
+}
+
+crate::builder::impl_builder!(QuintConfig, QuintConfigBuilder {
+    required { spec: PathBuf }
+    optional { mode: QuintMode, quint_bin: String, max_samples: usize, max_steps: usize,
+               max_traces: usize, mbt: bool, keep_outputs: bool }
+    optional_or { main: String, init: String, step: String, invariant: String,
+                  test_match: String, out_dir: PathBuf, timeout: std::time::Duration, seed: u64 }
+});
+
+/// Quint subcommand used to produce traces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QuintMode {
+    /// Random simulation with `quint run`.
+    Run,
+
+    /// The spec's `run` tests with `quint test`, one trace per test.
+    Test,
+}
+
+impl TraceGenerator for QuintConfig {
+    fn generate(&self) -> Result<GeneratedTraces, Error> {
+        generate_quint_traces(self)
+    }
+}
+
+/// Generate ITF traces by running Quint on a Quint spec.
+///
+/// Returns every `.itf.json` file Quint wrote to the output directory.
+#[must_use = "contains generated traces that should be used for replay"]
+pub fn generate_quint_traces(config: &QuintConfig) -> Result<GeneratedTraces, Error> {
+    let spec_path = config
+        .spec
+        .canonicalize()
+        .map_err(|_| TraceGenError::SpecNotFound(config.spec.clone()))?;
+
+    let (out_dir, temp) = output_dir(config.out_dir.as_ref(), config.keep_outputs)?;
+    std::fs::create_dir_all(&out_dir).map_err(|e| TraceGenError::TempDir(e.to_string()))?;
+
+    let seed = config.seed.unwrap_or_else(super::random_seed);
+    let mut cmd = std::process::Command::new(&config.quint_bin);
+    match config.mode {
+        QuintMode::Run => {
+            cmd.arg("run")
+                .arg(format!("--max-samples={}", config.max_samples))
+                .arg(format!("--max-steps={}", config.max_steps))
+                .arg(format!("--n-traces={}", config.max_traces))
+                .arg(format!("--out-itf={}", out_dir.join("trace_{seq}.itf.json").display()));
+            for (flag, value) in [("init", &config.init), ("step", &config.step), ("invariant", &config.invariant)] {
+                if let Some(value) = value {
+                    cmd.arg(format!("--{flag}={value}"));
+                }
+            }
+            if config.mbt {
+                cmd.arg("--mbt");
+            }
+        }
+        QuintMode::Test => {
+            cmd.arg("test")
+                .arg(format!("--max-samples={}", config.max_samples))
+                .arg(format!("--out-itf={}", out_dir.join("{test}_{seq}.itf.json").display()));
+            if let Some(ref pattern) = config.test_match {
+                cmd.arg(format!("--match={pattern}"));
+            }
+        }
+    }
+    if let Some(ref main) = config.main {
+        cmd.arg(format!("--main={main}"));
+    }
+    cmd.arg(format!("--seed={seed}")).arg(&spec_path);
+
+    info!(
+        spec = %spec_path.display(),
+        mode = ?config.mode,
+        seed,
+        "Running Quint"
+    );
+    debug!("Command: {:?}", cmd);
+
+    let output = crate::util::run_with_timeout(&mut cmd, config.timeout).map_err(|e| match e {
+        ApalacheError::Timeout { duration } => TraceGenError::Quint {
+            exit_code: None,
+            message: format!("timed out after {duration:?}"),
+        },
+        e => TraceGenError::Quint {
+            exit_code: None,
+            message: format!("failed to start {}: {e}", config.quint_bin),
+        },
+    })?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    debug!("Quint stdout:\n{}", stdout);
+
+    if output.status.success() {
+        if let (QuintMode::Run, Some(ref inv)) = (config.mode, &config.invariant) {
+            return Err(TraceGenError::NoViolation { inv: inv.clone() }.into());
+        }
+    }
+
+    let traces = match collect_itf_traces(&out_dir) {
+        Err(Error::TraceGen(TraceGenError::NoTracesFound(_))) if !output.status.success() => {
+            let message = stderr
+                .lines()
+                .chain(stdout.lines())
+                .filter(|l| !l.trim().is_empty())
+                .take(20)
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Err(TraceGenError::Quint {
+                exit_code: output.status.code(),
+                message,
+            }
+            .into());
+        }
+        result => result?,
+    };
+
+    Ok(GeneratedTraces {
+        traces,
+        out_dir,
+        outcome: None,
+        cached: false,
+        seed: Some(seed),
+        partial: false,
+        _temp: temp,
+    })
+}
@@ -0,0 +1,293 @@
+//! Streaming trace generation.
+//!
+//! [`stream_traces`] starts Apalache in the background and yields each ITF
+//! trace as soon as it appears in the output directory, so replay can begin
+//! while a long simulation is still running. Dropping the [`TraceStream`]
+//! stops Apalache, which lets a consumer abort generation on the first
+//! failing trace.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use tla_connect::{replay_trace_stream, stream_traces, ApalacheConfig, ApalacheMode};
+//!
+//! let config = ApalacheConfig::builder()
+//!     .spec("specs/Counter.tla")
+//!     .mode(ApalacheMode::Simulate)
+//!     .max_traces(1000usize)
+//!     .build()?;
+//!
+//! // Replays each trace as it is written; a mismatch stops Apalache.
+//! replay_trace_stream(|| CounterDriver::default(), stream_traces(&config)?)?;
+//! ```
+
+use super::{check_outcome, prepare_run, read_itf_trace, walkdir, ApalacheConfig, PreparedRun};
+use crate::apalache::ApalacheOutcome;
+use crate::error::{ApalacheError, Error, TraceGenError};
+use crate::trace_gen::instrument::StagedSpec;
+use crate::util::ProcessGroupGuard;
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// How often the output directory is scanned for new traces.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+enum Event {
+    Trace(itf::Trace<itf::Value>),
+    Done(Result<ApalacheOutcome, Error>),
+}
+
+/// Traces yielded while Apalache is still running.
+///
+/// Iterates over `Result<itf::Trace<itf::Value>, Error>` in the order the
+/// trace files appear. A failed Apalache run is reported as the final item.
+/// Dropping the stream kills the Apalache process, and on Unix its process
+/// group, if it is still running.
+#[must_use = "Apalache is stopped when the stream is dropped"]
+pub struct TraceStream {
+    events: mpsc::Receiver<Event>,
+    stop: Arc<AtomicBool>,
+    watcher: Option<JoinHandle<()>>,
+    outcome: Option<ApalacheOutcome>,
+    finished: bool,
+    seed: Option<u64>,
+    out_dir: PathBuf,
+    /// Owned temp output directory (cleaned up on drop).
+    _temp: Option<tempfile::TempDir>,
+    /// Staged wrapper module, kept until the watcher has stopped Apalache.
+    _staged: Option<StagedSpec>,
+}
+
+impl TraceStream {
+    /// Path to the output directory Apalache writes into.
+    pub fn out_dir(&self) -> &Path {
+        &self.out_dir
+    }
+
+    /// The seed `simulate` runs with (None in `Check` mode).
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// How the Apalache run ended, once the stream is exhausted.
+    pub fn outcome(&self) -> Option<&ApalacheOutcome> {
+        self.outcome.as_ref()
+    }
+}
+
+impl Iterator for TraceStream {
+    type Item = Result<itf::Trace<itf::Value>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.events.recv() {
+            Ok(Event::Trace(trace)) => Some(Ok(trace)),
+            Ok(Event::Done(Ok(outcome))) => {
+                self.finished = true;
+                self.outcome = Some(outcome);
+                None
+            }
+            Ok(Event::Done(Err(e))) => {
+                self.finished = true;
+                Some(Err(e))
+            }
+            Err(_) => {
+                self.finished = true;
+                None
+            }
+        }
+    }
+}
+
+impl Drop for TraceStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(watcher) = self.watcher.take() {
+            let _ = watcher.join();
+        }
+    }
+}
+
+/// Start Apalache for `config` and stream traces as they are written.
+///
+/// Unlike [`generate_traces`](super::generate_traces), this returns as soon
+/// as the process has started. `config.timeout` still applies. The trace
+/// cache is not consulted: streaming always runs Apalache.
+pub fn stream_traces(config: &ApalacheConfig) -> Result<TraceStream, Error> {
+    let (
+        mut cmd,
+        PreparedRun {
+            inv,
+            seed,
+            out_dir,
+            temp,
+            spec_dir,
+            _staged,
+        },
+    ) = prepare_run(config)?;
+
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    // Run Apalache in its own process group so stopping the stream also
+    // stops a JVM started by a launcher script.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+    let child = cmd
+        .spawn()
+        .map_err(|e| TraceGenError::from(ApalacheError::NotFound(e.to_string())))?;
+
+    let (tx, events) = mpsc::channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let watch = Watch {
+        out_dir: out_dir.clone(),
+        spec_dir,
+        inv,
+        timeout: config.timeout,
+        stop: Arc::clone(&stop),
+        tx,
+    };
+    let watcher = std::thread::spawn(move || watch.run(child));
+
+    Ok(TraceStream {
+        events,
+        stop,
+        watcher: Some(watcher),
+        outcome: None,
+        finished: false,
+        seed,
+        out_dir,
+        _temp: temp,
+        _staged,
+    })
+}
+
+/// Kill Apalache and its process group, then reap it.
+fn stop(child: &mut Child, group: &mut ProcessGroupGuard) {
+    group.kill();
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Background state polling the output directory while Apalache runs.
+struct Watch {
+    out_dir: PathBuf,
+    spec_dir: PathBuf,
+    inv: String,
+    timeout: Option<Duration>,
+    stop: Arc<AtomicBool>,
+    tx: mpsc::Sender<Event>,
+}
+
+impl Watch {
+    fn run(self, mut child: Child) {
+        let mut group = ProcessGroupGuard(Some(child.id()));
+        // Drain the pipes concurrently so a chatty Apalache never blocks on
+        // a full pipe buffer.
+        let stdout = child.stdout.take().map(drain);
+        let stderr = child.stderr.take().map(drain);
+
+        let start = Instant::now();
+        let mut seen = HashSet::new();
+
+        let status = loop {
+            if self.stop.load(Ordering::Relaxed) {
+                debug!("Trace stream dropped, stopping Apalache");
+                stop(&mut child, &mut group);
+                return;
+            }
+
+            let status = match child.try_wait() {
+                Ok(status) => status,
+                Err(e) => {
+                    stop(&mut child, &mut group);
+                    self.done(Err(TraceGenError::from(ApalacheError::NotFound(e.to_string())).into()));
+                    return;
+                }
+            };
+
+            // Files that fail to parse may still be in the middle of being
+            // written; retry them on the next scan unless Apalache is done.
+            if let Err(e) = self.scan(&mut seen, status.is_some()) {
+                stop(&mut child, &mut group);
+                self.done(Err(e));
+                return;
+            }
+
+            if let Some(status) = status {
+                break status;
+            }
+
+            if let Some(timeout) = self.timeout {
+                if start.elapsed() >= timeout {
+                    stop(&mut child, &mut group);
+                    self.done(Err(TraceGenError::from(ApalacheError::Timeout { duration: timeout }).into()));
+                    return;
+                }
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        };
+        group.0 = None;
+
+        let stdout = stdout.and_then(|h| h.join().ok()).unwrap_or_default();
+        let stderr = stderr.and_then(|h| h.join().ok()).unwrap_or_default();
+
+        let result = check_outcome(&stdout, &stderr, status.code(), &self.spec_dir).and_then(|outcome| {
+            if !seen.is_empty() {
+                info!(count = seen.len(), "Streamed ITF traces");
+                Ok(outcome)
+            } else if outcome == ApalacheOutcome::NoError {
+                Err(TraceGenError::NoViolation { inv: self.inv.clone() }.into())
+            } else {
+                Err(TraceGenError::NoTracesFound(self.out_dir.clone()).into())
+            }
+        });
+        self.done(result);
+    }
+
+    /// Send every new, fully written trace file under `out_dir`.
+    fn scan(&self, seen: &mut HashSet<PathBuf>, final_scan: bool) -> Result<(), Error> {
+        let mut paths = walkdir(&self.out_dir)?;
+        paths.sort();
+
+        for path in paths {
+            let is_trace = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.ends_with(".itf.json"));
+            if !is_trace || seen.contains(&path) {
+                continue;
+            }
+            match read_itf_trace(&path) {
+                Ok(trace) => {
+                    debug!(path = %path.display(), "Streaming ITF trace");
+                    seen.insert(path);
+                    let _ = self.tx.send(Event::Trace(trace));
+                }
+                Err(e) if final_scan => return Err(e),
+                Err(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn done(&self, result: Result<ApalacheOutcome, Error>) {
+        let _ = self.tx.send(Event::Done(result));
+    }
+}
+
+fn drain(mut pipe: impl Read + Send + 'static) -> JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe.read_to_end(&mut buf);
+        String::from_utf8_lossy(&buf).into_owned()
+    })
+}
@@ -0,0 +1,621 @@
+//! TLC trace generation backend.
+//!
+//! Some specs use constructs Apalache cannot handle, such as unbounded
+//! recursion. [`generate_tlc_traces`] runs TLC from `tla2tools.jar` instead,
+//! parses the counterexamples it prints and converts them to ITF traces, so
+//! they replay exactly like Apalache's.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use tla_connect::{replay_traces, TlcConfig, TlcMode, TraceGenerator};
+//!
+//! let config = TlcConfig::builder()
+//!     .spec("specs/Counter.tla")
+//!     .tla2tools("/opt/tla/tla2tools.jar")
+//!     .mode(TlcMode::Simulate)
+//!     .max_traces(100usize)
+//!     .build()?;
+//!
+//! let generated = config.generate()?;
+//! replay_traces(|| CounterDriver::default(), &generated.traces)?;
+//! ```
+
+use super::{output_dir, GeneratedTraces, TraceGenerator};
+use crate::error::{ApalacheError, Error, TraceGenError};
+use std::path::PathBuf;
+use tracing::{debug, info};
+
+/// Name of the model config written next to the staged spec.
+const STAGED_CFG: &str = "TlcConnect.cfg";
+
+/// Configuration for TLC trace generation.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TlcConfig {
+    /// Path to the TLA+ spec file.
+    pub spec: PathBuf,
+
+    /// Path to `tla2tools.jar` (default: "tla2tools.jar").
+    pub tla2tools: PathBuf,
+
+    /// TLC model config (optional). Defaults to the spec's `.cfg` file if
+    /// one exists; otherwise a config using `init` and `next` is generated.
+    /// The goal invariant `inv` is always added.
+    pub cfg: Option<PathBuf>,
+
+    /// Init predicate, used when there is no model config (default: "Init").
+    pub init: String,
+
+    /// Next-state relation, used when there is no model config
+    /// (default: "Next").
+    pub next: String,
+
+    /// Invariant to violate for trace generation (default: "TraceComplete").
+    /// Counterexamples to this invariant become test traces.
+    pub inv: String,
+
+    /// Number of behaviors to simulate. Only used in `Simulate` mode.
+    pub max_traces: usize,
+
+    /// Maximum behavior length. Only used in `Simulate` mode.
+    pub max_length: usize,
+
+    /// TLC execution mode.
+    pub mode: TlcMode,
+
+    /// Java executable (default: "java").
+    pub java: String,
+
+    /// Maximum JVM heap, e.g. "4g", passed as `-Xmx` (optional).
+    pub heap: Option<String>,
+
+    /// Output directory override (default: temp directory). Receives one
+    /// `.itf.json` file per trace and TLC's output as `tlc.log`.
+    pub out_dir: Option<PathBuf>,
+
+    /// Whether to keep the output directory after `GeneratedTraces` is dropped.
+    /// Only relevant when `out_dir` is None (temp directory).
+    pub keep_outputs: bool,
+
+    /// Timeout for the TLC subprocess. If None, no timeout is applied.
+    pub timeout: Option<std::time::Duration>,
+
+    /// Random seed for `Simulate` runs (optional). If None, a random seed
+    /// is chosen and recorded in [`GeneratedTraces::seed`].
+    pub seed: Option<u64>,
+}
+
+impl Default for TlcConfig {
+    fn default() -> Self {
+        Self {
+            spec: PathBuf::new(),
+            tla2tools: PathBuf::from("tla2tools.jar"),
+            cfg: None,
+            init: "Init".into(),
+            next: "Next".into(),
+            inv: "TraceComplete".into(),
+            max_traces: 100,
+            max_length: 50,
+            mode: TlcMode::Simulate,
+            java: "java".into(),
+            heap: None,
+            out_dir: None,
+            keep_outputs: false,
+            timeout: None,
+            seed: None,
+        }
+    }
+}
+
+crate::builder::impl_builder!(TlcConfig, TlcConfigBuilder {
+    required { spec: PathBuf }
+    optional { tla2tools: PathBuf, init: String, next: String, inv: String,
+               max_traces: usize, max_length: usize, mode: TlcMode, java: String,
+               keep_outputs: bool }
+    optional_or { cfg: PathBuf, heap: String, out_dir: PathBuf, timeout: std::time::Duration,
+                  seed: u64 }
+});
+
+/// TLC execution mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TlcMode {
+    /// Breadth-first model checking. Yields the shortest counterexample.
+    Check,
+
+    /// Random simulation (`-simulate`), continuing after each violation.
+    Simulate,
+}
+
+impl TraceGenerator for TlcConfig {
+    fn generate(&self) -> Result<GeneratedTraces, Error> {
+        generate_tlc_traces(self)
+    }
+}
+
+/// Generate ITF traces by running TLC on a TLA+ spec.
+///
+/// The spec directory's `.tla` files are staged in a temp directory with a
+/// model config adding `inv` as an invariant. Deadlock checking is off.
+/// Each counterexample TLC prints becomes one trace; TLA+ sequences and
+/// tuples both become ITF lists, functions become maps and model values
+/// become strings.
+#[must_use = "contains generated traces that should be used for replay"]
+pub fn generate_tlc_traces(config: &TlcConfig) -> Result<GeneratedTraces, Error> {
+    let spec_path = config
+        .spec
+        .canonicalize()
+        .map_err(|_| TraceGenError::SpecNotFound(config.spec.clone()))?;
+    let spec_dir = spec_path
+        .parent()
+        .ok_or_else(|| TraceGenError::SpecNotFound(spec_path.clone()))?;
+    let spec_file = spec_path
+        .file_name()
+        .ok_or_else(|| TraceGenError::SpecNotFound(spec_path.clone()))?;
+
+    let (out_dir, temp) = output_dir(config.out_dir.as_ref(), config.keep_outputs)?;
+    std::fs::create_dir_all(&out_dir).map_err(|e| TraceGenError::TempDir(e.to_string()))?;
+
+    let staged = tempfile::Builder::new()
+        .prefix("tla_tlc_")
+        .tempdir()
+        .map_err(|e| TraceGenError::TempDir(e.to_string()))?;
+    let staging_error = |path: PathBuf, e: std::io::Error| TraceGenError::SpecStaging {
+        path,
+        reason: e.to_string(),
+    };
+    crate::util::copy_tla_files(spec_dir, staged.path()).map_err(|(path, e)| staging_error(path, e))?;
+
+    let user_cfg = match config.cfg {
+        Some(ref cfg) => Some(cfg.clone()),
+        None => Some(spec_path.with_extension("cfg")).filter(|p| p.is_file()),
+    };
+    let mut cfg = match user_cfg {
+        Some(path) => std::fs::read_to_string(&path).map_err(|e| staging_error(path, e))?,
+        None => format!("INIT {}\nNEXT {}\n", config.init, config.next),
+    };
+    cfg.push_str(&format!("\nINVARIANT {}\n", config.inv));
+    let cfg_path = staged.path().join(STAGED_CFG);
+    std::fs::write(&cfg_path, cfg).map_err(|e| staging_error(cfg_path.clone(), e))?;
+
+    // TLC runs in the staging directory, so a relative JAR path must be
+    // resolved first.
+    let tla2tools = std::path::absolute(&config.tla2tools).map_err(|e| staging_error(config.tla2tools.clone(), e))?;
+
+    let mut cmd = std::process::Command::new(&config.java);
+    cmd.current_dir(staged.path());
+    if let Some(ref heap) = config.heap {
+        cmd.arg(format!("-Xmx{heap}"));
+    }
+    cmd.arg("-cp")
+        .arg(tla2tools)
+        .arg("tlc2.TLC")
+        .arg("-config")
+        .arg(STAGED_CFG)
+        .arg("-deadlock")
+        .arg("-noGenerateSpecTE")
+        .arg("-metadir")
+        .arg(staged.path().join("states"));
+
+    let seed = match config.mode {
+        TlcMode::Check => None,
+        TlcMode::Simulate => {
+            let seed = config.seed.unwrap_or_else(super::random_seed);
+            cmd.arg("-simulate")
+                .arg(format!("num={}", config.max_traces))
+                .arg("-depth")
+                .arg(config.max_length.to_string())
+                .arg("-seed")
+                .arg(seed.to_string())
+                .arg("-continue");
+            Some(seed)
+        }
+    };
+    cmd.arg(spec_file);
+
+    info!(
+        spec = %spec_path.display(),
+        inv = %config.inv,
+        mode = ?config.mode,
+        "Running TLC"
+    );
+    debug!("Command: {:?}", cmd);
+
+    let output = crate::util::run_with_timeout(&mut cmd, config.timeout).map_err(|e| match e {
+        ApalacheError::Timeout { duration } => TraceGenError::Tlc {
+            exit_code: None,
+            message: format!("timed out after {duration:?}"),
+        },
+        e => TraceGenError::Tlc {
+            exit_code: None,
+            message: format!("failed to start {}: {e}", config.java),
+        },
+    })?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let log_path = out_dir.join("tlc.log");
+    std::fs::write(&log_path, stdout.as_bytes()).map_err(|e| TraceGenError::Tlc {
+        exit_code: None,
+        message: format!("failed to write {}: {e}", log_path.display()),
+    })?;
+
+    let traces = parse_tlc_traces(&stdout).map_err(|message| TraceGenError::Tlc {
+        exit_code: output.status.code(),
+        message,
+    })?;
+
+    if traces.is_empty() {
+        if output.status.success() {
+            return Err(TraceGenError::NoViolation { inv: config.inv.clone() }.into());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let errors: Vec<&str> = stdout
+            .lines()
+            .filter(|l| l.starts_with("Error") || l.contains("error"))
+            .chain(stderr.lines().filter(|l| !l.is_empty()))
+            .take(20)
+            .collect();
+        return Err(TraceGenError::Tlc {
+            exit_code: output.status.code(),
+            message: errors.join("\n"),
+        }
+        .into());
+    }
+
+    for (idx, trace) in traces.iter().enumerate() {
+        let path = out_dir.join(format!("trace{idx}.itf.json"));
+        std::fs::write(&path, serde_json::to_vec_pretty(trace)?).map_err(|e| TraceGenError::Tlc {
+            exit_code: None,
+            message: format!("failed to write {}: {e}", path.display()),
+        })?;
+    }
+
+    info!(count = traces.len(), "Converted TLC counterexamples to ITF");
+    Ok(GeneratedTraces {
+        traces,
+        out_dir,
+        outcome: None,
+        cached: false,
+        seed,
+        partial: false,
+        _temp: temp,
+    })
+}
+
+/// Extract every counterexample from TLC's standard output.
+///
+/// A counterexample starts with "The behavior up to this point is:" and
+/// consists of `State <n>: <action>` headers, each followed by the state's
+/// `/\ var = value` lines and a blank line. A lasso's "Back to state <n>"
+/// line becomes the trace's `loop_index`.
+fn parse_tlc_traces(stdout: &str) -> Result<Vec<itf::Trace<itf::Value>>, String> {
+    let mut traces = Vec::new();
+    let mut current: Option<itf::Trace<itf::Value>> = None;
+    let mut body: Option<Vec<&str>> = None;
+
+    for line in stdout.lines() {
+        if let Some(ref mut lines) = body {
+            if !line.trim().is_empty() {
+                lines.push(line);
+                continue;
+            }
+            let lines = body.take().unwrap_or_default();
+            if let Some(ref mut trace) = current {
+                push_state(trace, &lines)?;
+            }
+            continue;
+        }
+
+        if line.ends_with("The behavior up to this point is:") {
+            traces.extend(current.take());
+            current = Some(empty_trace());
+        } else if let Some(ref mut trace) = current {
+            let Some((_, header)) = line.split_once(": ") else {
+                if !line.trim().is_empty() {
+                    traces.extend(current.take());
+                }
+                continue;
+            };
+            if line.starts_with("State ") {
+                body = Some(Vec::new());
+            } else if let Some(target) = header.strip_prefix("Back to state ") {
+                let target: u64 = target
+                    .split(|c: char| !c.is_ascii_digit())
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(|| format!("malformed lasso line: {line}"))?;
+                trace.loop_index = Some(target.saturating_sub(1));
+            } else if !header.starts_with("Stuttering") {
+                traces.extend(current.take());
+            }
+        }
+    }
+
+    if let (Some(lines), Some(trace)) = (body, current.as_mut()) {
+        push_state(trace, &lines)?;
+    }
+    traces.extend(current);
+    Ok(traces)
+}
+
+fn push_state(trace: &mut itf::Trace<itf::Value>, lines: &[&str]) -> Result<(), String> {
+    let state = parse_state(lines)?;
+    if trace.vars.is_empty() {
+        trace.vars = state.iter().map(|(name, _)| name.clone()).collect();
+    }
+    trace.states.push(itf::state::State {
+        meta: itf::state::Meta {
+            index: Some(trace.states.len() as u64),
+            ..Default::default()
+        },
+        value: itf::Value::Record(state.into_iter().collect()),
+    });
+    Ok(())
+}
+
+fn empty_trace() -> itf::Trace<itf::Value> {
+    itf::Trace {
+        meta: itf::trace::Meta {
+            source: Some("TLC".to_string()),
+            ..Default::default()
+        },
+        params: Vec::new(),
+        vars: Vec::new(),
+        loop_index: None,
+        states: Vec::new(),
+    }
+}
+
+/// Parse a state's `/\ var = value` lines, which may wrap. A spec with a
+/// single variable prints it without the leading `/\`.
+fn parse_state(lines: &[&str]) -> Result<Vec<(String, itf::Value)>, String> {
+    let mut assignments: Vec<String> = Vec::new();
+    for line in lines {
+        match line.trim_start().strip_prefix("/\\ ") {
+            Some(rest) => assignments.push(rest.to_string()),
+            None => match assignments.last_mut() {
+                Some(last) => {
+                    last.push(' ');
+                    last.push_str(line.trim());
+                }
+                None => assignments.push(line.trim().to_string()),
+            },
+        }
+    }
+
+    assignments
+        .iter()
+        .map(|assignment| {
+            let (name, value) = assignment
+                .split_once(" = ")
+                .ok_or_else(|| format!("malformed state line: {assignment}"))?;
+            let value = ValueParser::new(value)
+                .parse_all()
+                .map_err(|e| format!("cannot parse value of {}: {e}", name.trim()))?;
+            Ok((name.trim().to_string(), value))
+        })
+        .collect()
+}
+
+/// Recursive-descent parser for values as TLC prints them.
+struct ValueParser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> ValueParser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, pos: 0 }
+    }
+
+    fn parse_all(mut self) -> Result<itf::Value, String> {
+        let value = self.value()?;
+        self.skip_ws();
+        if self.pos < self.input.len() {
+            return Err(format!("unexpected input at `{}`", self.rest()));
+        }
+        Ok(value)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_ws(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_ws();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(format!("expected `{token}` at `{}`", self.rest()))
+        }
+    }
+
+    fn ident(&mut self) -> Option<&'a str> {
+        self.skip_ws();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        self.pos += len;
+        Some(&rest[..len])
+    }
+
+    /// Comma-separated items up to `close`.
+    fn items<T>(&mut self, close: &str, mut item: impl FnMut(&mut Self) -> Result<T, String>) -> Result<Vec<T>, String> {
+        let mut items = Vec::new();
+        if self.eat(close) {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.eat(close) {
+                return Ok(items);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn value(&mut self) -> Result<itf::Value, String> {
+        self.skip_ws();
+        if self.eat("<<") {
+            return Ok(itf::Value::List(self.items(">>", Self::value)?));
+        }
+        if self.eat("{") {
+            return Ok(itf::Value::Set(self.items("}", Self::value)?.into_iter().collect()));
+        }
+        if self.eat("[") {
+            let fields = self.items("]", |p| {
+                let name = p.ident().ok_or_else(|| format!("expected field name at `{}`", p.rest()))?;
+                p.expect("|->")?;
+                Ok((name.to_string(), p.value()?))
+            })?;
+            return Ok(itf::Value::Record(fields.into_iter().collect()));
+        }
+        if self.eat("(") {
+            let first = self.value()?;
+            if self.eat(")") {
+                return Ok(first);
+            }
+            self.expect(":>")?;
+            let mut map = itf::value::Map::new();
+            map.insert(first, self.value()?);
+            while self.eat("@@") {
+                let key = self.value()?;
+                self.expect(":>")?;
+                map.insert(key, self.value()?);
+            }
+            self.expect(")")?;
+            return Ok(itf::Value::Map(map));
+        }
+        if self.rest().starts_with('"') {
+            return self.string();
+        }
+        if self.rest().starts_with(|c: char| c == '-' || c.is_ascii_digit()) {
+            return self.integer();
+        }
+        match self.ident() {
+            Some("TRUE") => Ok(itf::Value::Bool(true)),
+            Some("FALSE") => Ok(itf::Value::Bool(false)),
+            Some(model_value) => Ok(itf::Value::String(model_value.to_string())),
+            None => Err(format!("unexpected input at `{}`", self.rest())),
+        }
+    }
+
+    fn string(&mut self) -> Result<itf::Value, String> {
+        let mut out = String::new();
+        let mut chars = self.rest().char_indices().skip(1);
+        while let Some((idx, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += idx + 1;
+                    return Ok(itf::Value::String(out));
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some(other) => out.push(other),
+                    None => break,
+                },
+                c => out.push(c),
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+
+    fn integer(&mut self) -> Result<itf::Value, String> {
+        let rest = self.rest();
+        let len = rest
+            .char_indices()
+            .find(|&(i, c)| !(c.is_ascii_digit() || (i == 0 && c == '-')))
+            .map_or(rest.len(), |(i, _)| i);
+        let n: i64 = rest[..len]
+            .parse()
+            .map_err(|_| format!("invalid integer `{}`", &rest[..len]))?;
+        self.pos += len;
+        Ok(itf::Value::BigInt(itf::value::BigInt::new(n)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = r#"TLC2 Version 2.19
+Running Random Simulation with seed 42.
+Error: Invariant TraceComplete is violated.
+Error: The behavior up to this point is:
+State 1: <Initial predicate>
+/\ count = 0
+/\ log = <<>>
+/\ owners = (1 :> "a" @@ 2 :> NoOwner)
+
+State 2: <Inc line 12, col 5 to line 14, col 30 of module Counter>
+/\ count = 1
+/\ log = <<[op |-> "inc",
+     ok |-> TRUE]>>
+/\ owners = (1 :> "a" @@ 2 :> NoOwner)
+
+3: Back to state 1
+
+Error: Invariant TraceComplete is violated.
+Error: The behavior up to this point is:
+State 1: <Initial predicate>
+/\ count = -2
+/\ log = <<>>
+/\ owners = {}
+
+The number of states generated: 12
+"#;
+
+    #[test]
+    fn parses_tlc_counterexamples() {
+        let traces = parse_tlc_traces(OUTPUT).unwrap();
+        assert_eq!(traces.len(), 2);
+
+        let first = &traces[0];
+        assert_eq!(first.vars, ["count", "log", "owners"]);
+        assert_eq!(first.states.len(), 2);
+        assert_eq!(first.loop_index, Some(0));
+
+        let json = serde_json::to_value(&first.states[1].value).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "count": {"#bigint": "1"},
+                "log": [{"ok": true, "op": "inc"}],
+                "owners": {"#map": [[{"#bigint": "1"}, "a"], [{"#bigint": "2"}, "NoOwner"]]},
+            })
+        );
+
+        assert_eq!(traces[1].states.len(), 1);
+        assert_eq!(traces[1].loop_index, None);
+    }
+
+    #[test]
+    fn parses_single_variable_states() {
+        let traces = parse_tlc_traces(
+            "Error: The behavior up to this point is:\nState 1: <Initial predicate>\nx = {1, 2}\n",
+        )
+        .unwrap();
+        let json = serde_json::to_value(&traces[0].states[0].value).unwrap();
+        assert_eq!(json, serde_json::json!({"x": {"#set": [{"#bigint": "1"}, {"#bigint": "2"}]}}));
+    }
+}
@@ -3,10 +3,11 @@
 //! Records state transitions as newline-delimited JSON, one object per line.
 //! The resulting trace file is validated against a TLA+ TraceSpec by Apalache.
 
+use super::{TraceSink, WriterSink};
 use crate::error::{Error, ValidationError};
+use crate::itf_writer::ItfTraceWriter;
 use serde::Serialize;
-use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Records state transitions as NDJSON for Apalache trace validation.
 ///
@@ -18,8 +19,16 @@ use std::path::Path;
 /// Call [`finish()`](Self::finish) when done to flush buffered output.
 /// If dropped without calling `finish()`, the destructor will attempt to
 /// flush but any errors will be silently ignored.
+///
+/// [`new()`](Self::new) writes to a file; [`with_sink()`](Self::with_sink)
+/// writes to memory, any `std::io::Write` through a `WriterSink`, compressed
+/// or rotated files (see [`sink`](super::sink)).
+///
+/// With [`with_itf_output()`](Self::with_itf_output), the same states are
+/// also recorded as an ITF trace, written when the emitter is finished.
 pub struct StateEmitter {
-    writer: Option<std::io::BufWriter<std::fs::File>>,
+    sink: Option<Box<dyn TraceSink>>,
+    itf: Option<(ItfTraceWriter, PathBuf)>,
     count: usize,
     finished: bool,
 }
@@ -29,11 +38,26 @@ impl StateEmitter {
     #[must_use = "emitter should be used to emit states and then finished"]
     pub fn new(path: &Path) -> Result<Self, Error> {
         let file = std::fs::File::create(path).map_err(ValidationError::Io)?;
-        Ok(Self {
-            writer: Some(std::io::BufWriter::new(file)),
+        Ok(Self::with_sink(WriterSink::new(std::io::BufWriter::new(file))))
+    }
+
+    /// Create a new emitter writing to `sink`.
+    #[must_use = "emitter should be used to emit states and then finished"]
+    pub fn with_sink(sink: impl TraceSink + 'static) -> Self {
+        Self {
+            sink: Some(Box::new(sink)),
+            itf: None,
             count: 0,
             finished: false,
-        })
+        }
+    }
+
+    /// Also record emitted states as an ITF trace, written to `path` by
+    /// [`finish()`](Self::finish). Each state's action is kept in its
+    /// `#meta` object rather than as a state variable.
+    pub fn with_itf_output(mut self, path: &Path) -> Self {
+        self.itf = Some((ItfTraceWriter::new(), path.to_path_buf()));
+        self
     }
 
     /// Emit a state transition as an NDJSON line.
@@ -53,20 +77,29 @@ impl StateEmitter {
             serde_json::Value::String(action.to_string()),
         );
 
-        let writer = self.writer.as_mut().ok_or(ValidationError::EmitterFinished)?;
-        serde_json::to_writer(&mut *writer, &obj)?;
-        writer
-            .write_all(b"\n")
-            .map_err(ValidationError::Io)?;
+        let sink = self.sink.as_mut().ok_or(ValidationError::EmitterFinished)?;
+        if let Some((ref mut itf, _)) = self.itf {
+            itf.record(action, state)?;
+        }
+        let mut line = serde_json::to_vec(&obj)?;
+        line.push(b'\n');
+        sink.write_line(&line).map_err(ValidationError::Io)?;
 
         self.count += 1;
         Ok(())
     }
 
-    /// Flush buffered output and return the number of states emitted.
+    /// Flush buffered output, complete the sink (e.g. write a compression
+    /// trailer) and return the number of states emitted.
+    ///
+    /// Also writes the ITF trace if [`with_itf_output()`](Self::with_itf_output)
+    /// was set.
     #[must_use = "finish result should be checked for errors"]
     pub fn finish(mut self) -> Result<usize, Error> {
-        self.flush_inner()?;
+        self.finish_sink()?;
+        if let Some((ref itf, ref path)) = self.itf {
+            itf.write_to(path)?;
+        }
         self.finished = true;
         Ok(self.count)
     }
@@ -76,9 +109,9 @@ impl StateEmitter {
         self.count
     }
 
-    fn flush_inner(&mut self) -> Result<(), Error> {
-        if let Some(ref mut writer) = self.writer {
-            writer.flush().map_err(ValidationError::Io)?;
+    fn finish_sink(&mut self) -> Result<(), Error> {
+        if let Some(mut sink) = self.sink.take() {
+            sink.finish().map_err(ValidationError::Io)?;
         }
         Ok(())
     }
@@ -93,7 +126,7 @@ impl Drop for StateEmitter {
                     "StateEmitter dropped without calling finish() – flushing buffered output"
                 );
             }
-            let _ = self.flush_inner();
+            let _ = self.finish_sink();
         }
     }
 }
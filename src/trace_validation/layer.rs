@@ -0,0 +1,344 @@
+//! `tracing` layer that records NDJSON states (Approach 3).
+//!
+//! [`StateLayer`] turns `tracing` events into [`StateEmitter`] lines, so a
+//! service that already logs its state transitions records a trace for
+//! [`validate_trace`](super::validate_trace) with subscriber setup alone.
+//!
+//! An event is recorded when its target is [`StateLayerConfig::target`] or
+//! it has the [`marker`](StateLayerConfig::marker) field. Its `action` field,
+//! or else its name set with `name:`, becomes the row's action, and its other
+//! fields become state variables. An event with neither is not recorded, and
+//! [`StateLayerHandle::finish`] reports it. Integers, booleans and strings
+//! are recorded as JSON values; `%display` and `?debug` values as strings,
+//! and floats too, as TLA+ has no floating-point values.
+//!
+//! # Example
+//!
+//! ```
+//! use tla_connect::{StateEmitter, StateLayer, StateLayerConfig};
+//! use tracing_subscriber::layer::SubscriberExt;
+//!
+//! # let dir = tempfile::tempdir()?;
+//! # let path = dir.path().join("trace.ndjson");
+//! let emitter = StateEmitter::new(&path)?;
+//! let layer = StateLayer::new(StateLayerConfig::default(), emitter);
+//! let handle = layer.handle();
+//!
+//! let subscriber = tracing_subscriber::registry().with(layer);
+//! tracing::subscriber::with_default(subscriber, || {
+//!     tracing::info!(target: "tla_state", action = "init", count = 0);
+//!     tracing::info!(name: "increment", target: "tla_state", count = 1);
+//!     tracing::info!(count = 2, "not a state: other target");
+//! });
+//!
+//! assert_eq!(handle.finish()?, 2);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use super::StateEmitter;
+use crate::error::{Error, ValidationError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Configuration for [`StateLayer`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct StateLayerConfig {
+    /// Target of the events to record (default: "tla_state").
+    pub target: String,
+
+    /// Field that marks events to record whatever their target (optional).
+    /// The field itself is not recorded.
+    pub marker: Option<String>,
+
+    /// Field holding the action (default: "action"). Events without it use
+    /// their name, set with `name:` in the `tracing` macros; events with
+    /// neither are not recorded.
+    pub action_field: String,
+
+    /// Fields recorded as state variables. If empty, every field but the
+    /// action, the marker and `message` is recorded.
+    pub fields: Vec<String>,
+}
+
+impl Default for StateLayerConfig {
+    fn default() -> Self {
+        Self {
+            target: "tla_state".into(),
+            marker: None,
+            action_field: "action".into(),
+            fields: Vec::new(),
+        }
+    }
+}
+
+crate::builder::impl_builder!(StateLayerConfig, StateLayerConfigBuilder {
+    required {}
+    optional { target: String, action_field: String, fields: Vec<String> }
+    optional_or { marker: String }
+});
+
+/// The emitter shared by a layer and its handles.
+struct Recording {
+    emitter: Option<StateEmitter>,
+    /// First error from emitting, reported by [`StateLayerHandle::finish`].
+    error: Option<Error>,
+}
+
+/// A `tracing_subscriber` layer that records matching events with a
+/// [`StateEmitter`].
+///
+/// The layer is moved into the subscriber; use [`handle()`](Self::handle)
+/// beforehand to finish the recording.
+pub struct StateLayer {
+    config: StateLayerConfig,
+    recording: Arc<Mutex<Recording>>,
+}
+
+/// Handle to finish a [`StateLayer`]'s recording after the layer has been
+/// installed.
+#[derive(Clone)]
+pub struct StateLayerHandle {
+    recording: Arc<Mutex<Recording>>,
+}
+
+impl StateLayer {
+    /// Create a layer recording matching events with `emitter`.
+    pub fn new(config: StateLayerConfig, emitter: StateEmitter) -> Self {
+        Self {
+            config,
+            recording: Arc::new(Mutex::new(Recording {
+                emitter: Some(emitter),
+                error: None,
+            })),
+        }
+    }
+
+    /// A handle to the layer's recording.
+    pub fn handle(&self) -> StateLayerHandle {
+        StateLayerHandle {
+            recording: Arc::clone(&self.recording),
+        }
+    }
+
+    fn is_state(&self, event: &Event<'_>) -> bool {
+        let meta = event.metadata();
+        meta.target() == self.config.target
+            || self
+                .config
+                .marker
+                .as_ref()
+                .is_some_and(|marker| meta.fields().field(marker).is_some())
+    }
+}
+
+impl<S: Subscriber> Layer<S> for StateLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if !self.is_state(event) {
+            return;
+        }
+
+        let mut visitor = JsonVisitor::default();
+        event.record(&mut visitor);
+        let mut state = visitor.0;
+
+        let meta = event.metadata();
+        let action = match state.remove(&self.config.action_field) {
+            Some(serde_json::Value::String(action)) => action,
+            Some(action) => action.to_string(),
+            None if !has_default_name(meta) => meta.name().to_string(),
+            None => {
+                let location = meta.name().trim_start_matches("event ").to_string();
+                lock(&self.recording).error.get_or_insert(
+                    ValidationError::MissingAction {
+                        location,
+                        action_field: self.config.action_field.clone(),
+                    }
+                    .into(),
+                );
+                return;
+            }
+        };
+        if let Some(ref marker) = self.config.marker {
+            state.remove(marker);
+        }
+        if self.config.fields.is_empty() {
+            state.remove("message");
+        } else {
+            state.retain(|key, _| self.config.fields.contains(key));
+        }
+
+        let mut recording = lock(&self.recording);
+        let Recording { emitter, error } = &mut *recording;
+        if let Some(emitter) = emitter {
+            if let Err(e) = emitter.emit(&action, &state) {
+                error.get_or_insert(e);
+            }
+        }
+    }
+}
+
+impl StateLayerHandle {
+    /// Number of states recorded so far.
+    pub fn count(&self) -> usize {
+        lock(&self.recording).emitter.as_ref().map_or(0, StateEmitter::count)
+    }
+
+    /// Stop recording, flush the emitter and return the number of states
+    /// recorded. Fails with the first error from recording an event, if any.
+    /// Events after this call are ignored.
+    #[must_use = "finish result should be checked for errors"]
+    pub fn finish(&self) -> Result<usize, Error> {
+        let mut recording = lock(&self.recording);
+        let emitter = recording.emitter.take().ok_or(ValidationError::EmitterFinished)?;
+        let count = emitter.finish()?;
+        match recording.error.take() {
+            Some(e) => Err(e),
+            None => Ok(count),
+        }
+    }
+}
+
+/// Whether the event kept the name the `tracing` macros give events without
+/// `name:`, "event file:line".
+fn has_default_name(meta: &tracing::Metadata<'_>) -> bool {
+    match (meta.file(), meta.line()) {
+        (Some(file), Some(line)) => meta.name() == format!("event {file}:{line}"),
+        _ => false,
+    }
+}
+
+fn lock(recording: &Mutex<Recording>) -> MutexGuard<'_, Recording> {
+    recording.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Collects an event's fields as JSON values.
+#[derive(Default)]
+struct JsonVisitor(serde_json::Map<String, serde_json::Value>);
+
+impl JsonVisitor {
+    fn insert(&mut self, field: &Field, value: impl Into<serde_json::Value>) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    /// Integers beyond JSON's range use the ITF `#bigint` encoding.
+    fn insert_bigint(&mut self, field: &Field, value: impl ToString) {
+        self.insert(field, serde_json::json!({ "#bigint": value.to_string() }));
+    }
+}
+
+impl Visit for JsonVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value);
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value);
+    }
+
+    fn record_i128(&mut self, field: &Field, value: i128) {
+        match i64::try_from(value) {
+            Ok(value) => self.insert(field, value),
+            Err(_) => self.insert_bigint(field, value),
+        }
+    }
+
+    fn record_u128(&mut self, field: &Field, value: u128) {
+        match u64::try_from(value) {
+            Ok(value) => self.insert(field, value),
+            Err(_) => self.insert_bigint(field, value),
+        }
+    }
+
+    /// Floats are recorded as strings: trace validation rejects JSON floats.
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, value.to_string());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value);
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.insert(field, format!("{value:?}"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn marker_and_selected_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.ndjson");
+        let config = StateLayerConfig::builder()
+            .marker("tla")
+            .fields(vec!["count".to_string(), "big".to_string()])
+            .build()
+            .unwrap();
+        let layer = StateLayer::new(config, StateEmitter::new(&path).unwrap());
+        let handle = layer.handle();
+
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            tracing::info!(tla = true, action = "init", count = 0u32, big = u128::MAX, peer = "a");
+            tracing::info!(count = 5, "unmarked");
+        });
+        assert_eq!(handle.count(), 1);
+        assert_eq!(handle.finish().unwrap(), 1);
+        assert!(handle.finish().is_err());
+
+        let line = std::fs::read_to_string(&path).unwrap();
+        let row: serde_json::Value = serde_json::from_str(line.trim()).unwrap();
+        assert_eq!(
+            row,
+            serde_json::json!({
+                "action": "init",
+                "big": {"#bigint": u128::MAX.to_string()},
+                "count": 0,
+            })
+        );
+    }
+
+    #[test]
+    fn events_without_action_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.ndjson");
+        let layer = StateLayer::new(StateLayerConfig::default(), StateEmitter::new(&path).unwrap());
+        let handle = layer.handle();
+
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            tracing::info!(target: "tla_state", count = 0);
+            tracing::info!(name: "step", target: "tla_state", count = 1);
+        });
+        assert_eq!(handle.count(), 1);
+        let err = handle.finish().unwrap_err().to_string();
+        assert!(err.contains(&format!("State event at {}:", file!())), "got: {err}");
+        assert!(err.contains("set its `action` field or `name:`"), "got: {err}");
+    }
+
+    #[test]
+    fn floats_are_recorded_as_strings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.ndjson");
+        let layer = StateLayer::new(StateLayerConfig::default(), StateEmitter::new(&path).unwrap());
+        let handle = layer.handle();
+
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            tracing::info!(target: "tla_state", action = "init", ratio = 0.5, count = 1);
+        });
+        assert_eq!(handle.finish().unwrap(), 1);
+
+        let row: serde_json::Value = serde_json::from_str(std::fs::read_to_string(&path).unwrap().trim()).unwrap();
+        assert_eq!(row, serde_json::json!({"action": "init", "count": 1, "ratio": "0.5"}));
+        let (module, _) = super::super::ndjson_to_tla_module(&path).unwrap();
+        assert!(module.contains(r#"ratio |-> "0.5""#));
+    }
+}
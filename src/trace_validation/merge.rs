@@ -0,0 +1,416 @@
+//! Merging per-node NDJSON traces by logical clock.
+//!
+//! In a distributed system each node records its own trace with a
+//! [`StateEmitter`], stamping every row with a Lamport timestamp or a vector
+//! clock. [`merge_traces`] orders the rows of all nodes consistently with
+//! those clocks and writes global traces for
+//! [`validate_trace`](super::validate_trace).
+//!
+//! Each node's first row is its initial state; together they form the
+//! merged trace's first row, with action `init`. Every later row is one
+//! node's step: its `action`, the stepping node in the `node` field, and
+//! for each state variable a function from node names to that node's
+//! latest value, in ITF `#map` form (type `Str -> T`). The clock field is
+//! dropped.
+//!
+//! Rows whose clocks do not order them, concurrent vector clocks or equal
+//! Lamport timestamps on different nodes, may be merged in any order. Each
+//! order is a candidate linearization; up to
+//! [`max_linearizations`](TraceMergeConfig::max_linearizations) of them are
+//! written, and the run is valid if any candidate is.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use std::collections::BTreeMap;
+//! use tla_connect::*;
+//!
+//! let config = TraceMergeConfig::builder()
+//!     .nodes(BTreeMap::from([
+//!         ("n1".to_string(), "n1.ndjson".into()),
+//!         ("n2".to_string(), "n2.ndjson".into()),
+//!     ]))
+//!     .clock(LogicalClock::Vector("vc".into()))
+//!     .build()?;
+//!
+//! let merged = merge_traces(&config)?;
+//! let valid = merged
+//!     .traces
+//!     .iter()
+//!     .any(|trace| matches!(validate_trace(&validator, trace), Ok(TraceResult::Valid)));
+//! ```
+
+use super::validator::read_rows;
+use super::StateEmitter;
+use crate::error::{Error, ValidationError};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+type Row = serde_json::Map<String, serde_json::Value>;
+
+/// The logical clock stamped on each row of a node's trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LogicalClock {
+    /// A Lamport timestamp: a non-negative integer field.
+    Lamport(String),
+
+    /// A vector clock: an object field mapping node names to counters.
+    /// Missing entries count as 0.
+    Vector(String),
+}
+
+/// Configuration for merging per-node traces.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TraceMergeConfig {
+    /// Each node's NDJSON trace, keyed by node name.
+    pub nodes: BTreeMap<String, PathBuf>,
+
+    /// Clock field of the rows (default: Lamport timestamps in "clock").
+    pub clock: LogicalClock,
+
+    /// Field naming the stepping node in merged rows (default: "node").
+    pub node_field: String,
+
+    /// Maximum number of linearizations to write (default: 16).
+    pub max_linearizations: usize,
+
+    /// Output directory override (default: temp directory).
+    /// If None, a temp directory is created and owned by the returned `MergedTraces`.
+    pub out_dir: Option<PathBuf>,
+}
+
+impl Default for TraceMergeConfig {
+    fn default() -> Self {
+        Self {
+            nodes: BTreeMap::new(),
+            clock: LogicalClock::Lamport("clock".into()),
+            node_field: "node".into(),
+            max_linearizations: 16,
+            out_dir: None,
+        }
+    }
+}
+
+crate::builder::impl_builder!(TraceMergeConfig, TraceMergeConfigBuilder {
+    required { nodes: BTreeMap<String, PathBuf> }
+    optional { clock: LogicalClock, node_field: String, max_linearizations: usize }
+    optional_or { out_dir: PathBuf }
+});
+
+/// Merged traces written by [`merge_traces`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct MergedTraces {
+    /// NDJSON files of the candidate linearizations. The first orders
+    /// concurrent rows by clock, then by node name.
+    pub traces: Vec<PathBuf>,
+
+    /// Whether `traces` holds every linearization, rather than the first
+    /// `max_linearizations`.
+    pub exhaustive: bool,
+
+    /// Directory containing `traces`.
+    pub out_dir: PathBuf,
+
+    /// Owned temp directory (cleaned up on drop unless persisted).
+    _temp: Option<tempfile::TempDir>,
+}
+
+impl MergedTraces {
+    /// Persist the output directory, preventing cleanup on drop.
+    ///
+    /// Returns the path to the persisted directory.
+    pub fn persist(mut self) -> PathBuf {
+        if let Some(temp) = self._temp.take() {
+            temp.keep()
+        } else {
+            self.out_dir.clone()
+        }
+    }
+}
+
+/// A row's clock value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Clock {
+    Lamport(u64),
+    Vector(BTreeMap<String, u64>),
+}
+
+impl Clock {
+    /// Whether `self` happened before `other`.
+    fn before(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Lamport(a), Self::Lamport(b)) => a < b,
+            (Self::Vector(a), Self::Vector(b)) => {
+                a != b && a.iter().all(|(node, n)| *n <= b.get(node).copied().unwrap_or(0))
+            }
+            _ => false,
+        }
+    }
+
+    /// Sort key among concurrent rows.
+    fn key(&self) -> u64 {
+        match self {
+            Self::Lamport(n) => *n,
+            Self::Vector(v) => v.values().sum(),
+        }
+    }
+}
+
+struct Step {
+    action: String,
+    clock: Clock,
+    state: Row,
+}
+
+/// One node's trace: its initial state and later steps.
+struct NodeTrace {
+    name: String,
+    init: Row,
+    steps: Vec<Step>,
+}
+
+/// Merge per-node NDJSON traces into global traces ordered by their
+/// logical clocks, one file per candidate linearization.
+#[must_use = "contains merged traces that should be validated"]
+pub fn merge_traces(config: &TraceMergeConfig) -> Result<MergedTraces, Error> {
+    let merge_error = |reason: String| Error::from(ValidationError::TraceMerge(reason));
+    if config.nodes.is_empty() {
+        return Err(merge_error("no node traces".to_string()));
+    }
+
+    let nodes = config
+        .nodes
+        .iter()
+        .map(|(name, path)| read_node(name, path, &config.clock))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let fields: BTreeSet<&String> = nodes
+        .iter()
+        .flat_map(|node| node.init.keys().chain(node.steps.iter().flat_map(|s| s.state.keys())))
+        .collect();
+    if fields.contains(&config.node_field) {
+        return Err(merge_error(format!("state field '{}' is the node field", config.node_field)));
+    }
+
+    let (orders, exhaustive) = linearizations(&nodes, config.max_linearizations.max(1));
+
+    let (out_dir, temp) = match config.out_dir {
+        Some(ref dir) => {
+            std::fs::create_dir_all(dir).map_err(ValidationError::Io)?;
+            (dir.clone(), None)
+        }
+        None => {
+            let tmp = tempfile::Builder::new()
+                .prefix("tla_merged_")
+                .tempdir()
+                .map_err(|e| ValidationError::WorkDir(e.to_string()))?;
+            (tmp.path().to_path_buf(), Some(tmp))
+        }
+    };
+
+    let mut traces = Vec::new();
+    for (i, order) in orders.iter().enumerate() {
+        let path = out_dir.join(format!("trace-{i}.ndjson"));
+        let mut emitter = StateEmitter::new(&path)?;
+        let mut states: Vec<&Row> = nodes.iter().map(|node| &node.init).collect();
+        emitter.emit("init", &global_row(&nodes, &states, &fields, &config.node_field, ""))?;
+
+        let mut next = vec![0; nodes.len()];
+        for &node in order {
+            let step = &nodes[node].steps[next[node]];
+            next[node] += 1;
+            states[node] = &step.state;
+            let row = global_row(&nodes, &states, &fields, &config.node_field, &nodes[node].name);
+            emitter.emit(&step.action, &row)?;
+        }
+        emitter.finish()?;
+        traces.push(path);
+    }
+
+    Ok(MergedTraces {
+        traces,
+        exhaustive,
+        out_dir,
+        _temp: temp,
+    })
+}
+
+fn read_node(name: &str, path: &std::path::Path, clock: &LogicalClock) -> Result<NodeTrace, Error> {
+    let at = |line: usize| format!("{}:{line}", path.display());
+    let (clock_field, vector) = match clock {
+        LogicalClock::Lamport(field) => (field, false),
+        LogicalClock::Vector(field) => (field, true),
+    };
+
+    let mut rows = read_rows(path)?.into_iter().map(|(line, row)| match row {
+        serde_json::Value::Object(mut row) => {
+            let action = match row.remove("action") {
+                Some(serde_json::Value::String(action)) => action,
+                _ => "unknown".to_string(),
+            };
+            let clock = row.remove(clock_field);
+            Ok((line, action, clock, row))
+        }
+        row => Err(Error::from(ValidationError::NonObjectState {
+            found: format!("{}: {row}", at(line)),
+        })),
+    });
+
+    let (_, _, _, init) = rows
+        .next()
+        .ok_or_else(|| ValidationError::EmptyTrace(path.to_path_buf()))??;
+
+    let mut steps: Vec<Step> = Vec::new();
+    for row in rows {
+        let (line, action, clock, state) = row?;
+        let clock = match (clock, vector) {
+            (Some(serde_json::Value::Number(n)), false) => n.as_u64().map(Clock::Lamport),
+            (Some(serde_json::Value::Object(v)), true) => v
+                .into_iter()
+                .map(|(node, n)| n.as_u64().map(|n| (node, n)))
+                .collect::<Option<_>>()
+                .map(Clock::Vector),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            let kind = if vector { "vector clock" } else { "Lamport timestamp" };
+            ValidationError::TraceMerge(format!("{}: missing or malformed {kind} '{clock_field}'", at(line)))
+        })?;
+        if let Some(prev) = steps.last() {
+            if !prev.clock.before(&clock) {
+                return Err(ValidationError::TraceMerge(format!("{}: clock does not advance", at(line))).into());
+            }
+        }
+        steps.push(Step { action, clock, state });
+    }
+
+    Ok(NodeTrace {
+        name: name.to_string(),
+        init,
+        steps,
+    })
+}
+
+/// Orders of the nodes' steps consistent with their clocks, as the node
+/// taking each step, and whether these are all of them.
+fn linearizations(nodes: &[NodeTrace], max: usize) -> (Vec<Vec<usize>>, bool) {
+    let total: usize = nodes.iter().map(|node| node.steps.len()).sum();
+    let mut pos = vec![0; nodes.len()];
+
+    // Nodes whose next step no other node's next step happened before.
+    let ready = |pos: &[usize]| -> Vec<usize> {
+        let next: Vec<Option<&Clock>> = nodes
+            .iter()
+            .zip(pos)
+            .map(|(node, &i)| node.steps.get(i).map(|s| &s.clock))
+            .collect();
+        let mut ready: Vec<usize> = (0..nodes.len())
+            .filter(|&i| {
+                next[i].is_some_and(|clock| next.iter().flatten().all(|other| !other.before(clock)))
+            })
+            .collect();
+        ready.sort_by_key(|&i| next[i].map(Clock::key));
+        ready
+    };
+
+    if total == 0 {
+        return (vec![Vec::new()], true);
+    }
+
+    // Depth-first search over choices of the next step.
+    let mut orders = Vec::new();
+    let mut order = Vec::new();
+    let mut stack = vec![(ready(&pos), 0)];
+    while let Some((choices, tried)) = stack.last_mut() {
+        let Some(&node) = choices.get(*tried) else {
+            stack.pop();
+            if let Some(node) = order.pop() {
+                pos[node] -= 1;
+            }
+            continue;
+        };
+        *tried += 1;
+        order.push(node);
+        pos[node] += 1;
+        if order.len() < total {
+            stack.push((ready(&pos), 0));
+            continue;
+        }
+        if orders.len() == max {
+            return (orders, false);
+        }
+        orders.push(order.clone());
+        order.pop();
+        pos[node] -= 1;
+    }
+    (orders, true)
+}
+
+/// A merged row: each field as a function from node names to values.
+fn global_row(nodes: &[NodeTrace], states: &[&Row], fields: &BTreeSet<&String>, node_field: &str, node: &str) -> Row {
+    let mut row = Row::new();
+    row.insert(node_field.to_string(), node.into());
+    for field in fields {
+        let pairs: Vec<serde_json::Value> = nodes
+            .iter()
+            .zip(states)
+            .filter_map(|(node, state)| state.get(*field).map(|v| serde_json::json!([node.name, v])))
+            .collect();
+        row.insert(field.to_string(), serde_json::json!({ "#map": pairs }));
+    }
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, clocks: &[Clock]) -> NodeTrace {
+        NodeTrace {
+            name: name.to_string(),
+            init: Row::new(),
+            steps: clocks
+                .iter()
+                .map(|clock| Step {
+                    action: "step".to_string(),
+                    clock: clock.clone(),
+                    state: Row::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn vc(entries: &[(&str, u64)]) -> Clock {
+        Clock::Vector(entries.iter().map(|(n, c)| (n.to_string(), *c)).collect())
+    }
+
+    #[test]
+    fn lamport_ties_are_ambiguous() {
+        let nodes = [
+            node("a", &[Clock::Lamport(1), Clock::Lamport(3)]),
+            node("b", &[Clock::Lamport(2), Clock::Lamport(3)]),
+        ];
+        let (orders, exhaustive) = linearizations(&nodes, 16);
+        assert_eq!(orders, vec![vec![0, 1, 0, 1], vec![0, 1, 1, 0]]);
+        assert!(exhaustive);
+    }
+
+    #[test]
+    fn vector_clocks_order_causally_related_steps() {
+        // b's step receives a's first message; a's second step is concurrent.
+        let nodes = [
+            node("a", &[vc(&[("a", 1)]), vc(&[("a", 2)])]),
+            node("b", &[vc(&[("a", 1), ("b", 1)])]),
+        ];
+        let (orders, exhaustive) = linearizations(&nodes, 16);
+        assert_eq!(orders, vec![vec![0, 0, 1], vec![0, 1, 0]]);
+        assert!(exhaustive);
+
+        let (orders, exhaustive) = linearizations(&nodes, 1);
+        assert_eq!(orders.len(), 1);
+        assert!(!exhaustive);
+    }
+}
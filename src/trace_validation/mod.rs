@@ -7,10 +7,48 @@
 //!
 //! ## Workflow
 //!
-//! 1. Instrument Rust code with `StateEmitter` to record state transitions as NDJSON
-//! 2. Write a TLA+ `TraceSpec` that constrains the original spec using the recorded trace
+//! 1. Instrument Rust code with `StateEmitter` to record state transitions as NDJSON,
+//!    or record existing `tracing` events with `StateLayer` (requires `tracing-layer` feature)
+//! 2. Write a TLA+ `TraceSpec` that constrains the original spec using the recorded trace,
+//!    or generate one from the original spec with [`TraceSpecGenerator`]
 //! 3. Run `validate_trace` to check the trace is a valid behavior of the spec
 //!
+//! ## Sets, functions and variants
+//!
+//! JSON arrays become sequences and objects become records. Values in ITF
+//! form become the matching TLA+ values: `{"#set": [...]}` a set,
+//! `{"#map": [[k, v], ...]}` a function, `{"#tup": [...]}` a tuple,
+//! `{"#bigint": "..."}` an integer, and `{"tag": ..., "value": ...}` a
+//! variant. The `serialize_with` helpers in [`itf_writer`](crate::itf_writer)
+//! produce these from Rust collections.
+//!
+//! ## Types
+//!
+//! `TraceData.tla` annotates `TraceLog` with the Snowcat type of its rows,
+//! merged from all rows: an empty array takes its element type from other
+//! rows, and variants collect the tags seen in any row. Rows whose types
+//! conflict are rejected with the line and field path. For full control,
+//! set [`TraceValidatorConfig::schema`], by hand or with [`TlaType`]; see
+//! the [`schema`] module.
+//!
+//! ## Partial observations
+//!
+//! With [`TraceValidatorConfig::partial_observations`], rows may omit
+//! top-level fields the instrumentation could not observe. A field missing
+//! or `null` in some rows is recorded in every row as an Apalache variant,
+//! `Variant("Some", value)` or `Variant("None", UNIT)`, and `TraceData`
+//! defines `TraceObserved(value, obs)`, which holds when the field was not
+//! observed or was observed as `value`. A hand-written TraceSpec constrains
+//! such a variable with
+//!
+//! ```tla
+//! TraceObserved(mode', TraceLog[i + 1].mode)
+//! ```
+//!
+//! instead of `mode' = TraceLog[i + 1].mode`; [`TraceSpecGenerator`] does so
+//! for every partial field. Nested fields cannot be partial: a record
+//! inside a row must have the same keys, and no `null` values, in every row.
+//!
 //! # Example
 //!
 //! ```ignore
@@ -34,15 +72,41 @@
 //!
 //! match validate_trace(&config, Path::new("trace.ndjson"))? {
 //!     TraceResult::Valid => println!("Valid!"),
-//!     TraceResult::Invalid { reason } => println!("Invalid: {reason}"),
+//!     TraceResult::Invalid { reason, .. } => println!("Invalid: {reason}"),
 //! }
 //! ```
 
 pub mod emitter;
+#[cfg(feature = "tracing-layer")]
+pub mod layer;
+pub mod merge;
+pub mod recorder;
+pub mod schema;
+pub mod sink;
+pub mod trace_spec;
 pub mod validator;
 
 pub use emitter::StateEmitter;
-pub use validator::{validate_trace, TraceResult, TraceValidatorConfig, TraceValidatorConfigBuilder};
+pub use merge::{merge_traces, LogicalClock, MergedTraces, TraceMergeConfig, TraceMergeConfigBuilder};
+pub use recorder::{StateLock, StateRecorder};
+pub use schema::TlaType;
+pub use sink::{MemorySink, RotatingSink, TraceSink, WriterSink};
+pub use trace_spec::{TraceSpecGenerator, TraceSpecGeneratorBuilder};
+pub use validator::{
+    validate_trace, InvalidStep, TraceInput, TraceResult, TraceValidatorConfig, TraceValidatorConfigBuilder,
+    MIN_VALIDATION_VERSION,
+};
+
+#[cfg(feature = "gzip")]
+pub use sink::GzipSink;
+#[cfg(feature = "zstd")]
+pub use sink::ZstdSink;
+
+#[cfg(feature = "async")]
+pub use validator::validate_trace_async;
+
+#[cfg(feature = "tracing-layer")]
+pub use layer::{StateLayer, StateLayerConfig, StateLayerConfigBuilder, StateLayerHandle};
 
 #[doc(hidden)]
 pub use validator::ndjson_to_tla_module;
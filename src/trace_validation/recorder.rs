@@ -0,0 +1,249 @@
+//! Thread-safe state recording (Approach 3).
+//!
+//! [`StateEmitter::emit`] takes `&mut self`. [`StateRecorder`] is a
+//! cloneable `Send + Sync` handle around an emitter for multi-threaded
+//! systems: every emit is written under one lock and stamped with a global
+//! `seq` number and the emitting `thread` (with the `async` feature, the
+//! tokio task inside a task), so rows are in a single total order.
+//!
+//! The TraceSpec must declare or ignore the two added fields; with
+//! [`StateRecorder::with_field_names`] they can be renamed to avoid clashes
+//! with state variables.
+//!
+//! For that order to be a valid behavior, each state must be recorded while
+//! the lock guarding it is held. Threads that change the state emit while
+//! still holding their guard; [`StateRecorder::snapshot`] takes a lock
+//! itself to record a consistent view of the state.
+//!
+//! # Example
+//!
+//! ```
+//! use std::sync::Mutex;
+//! use tla_connect::{StateEmitter, StateRecorder};
+//!
+//! # let dir = tempfile::tempdir()?;
+//! # let path = dir.path().join("trace.ndjson");
+//! let recorder = StateRecorder::new(StateEmitter::new(&path)?);
+//! let counter = Mutex::new(0u64);
+//! recorder.snapshot("init", &counter, |count| serde_json::json!({ "count": count }))?;
+//!
+//! std::thread::scope(|s| {
+//!     for _ in 0..4 {
+//!         let recorder = recorder.clone();
+//!         let counter = &counter;
+//!         s.spawn(move || {
+//!             let mut count = counter.lock().unwrap();
+//!             *count += 1;
+//!             recorder.emit("increment", &serde_json::json!({ "count": *count })).unwrap();
+//!         });
+//!     }
+//! });
+//!
+//! assert_eq!(recorder.finish()?, 5);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use super::StateEmitter;
+use crate::error::{Error, ValidationError};
+use serde::Serialize;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock};
+
+/// A lock guarding shared state, read by [`StateRecorder::snapshot`].
+pub trait StateLock {
+    /// The guarded state.
+    type State: ?Sized;
+
+    /// Run `f` on the state while holding the lock.
+    fn with_locked<R>(&self, f: impl FnOnce(&Self::State) -> R) -> R;
+}
+
+impl<T: ?Sized> StateLock for Mutex<T> {
+    type State = T;
+
+    fn with_locked<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl<T: ?Sized> StateLock for RwLock<T> {
+    type State = T;
+
+    fn with_locked<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.read().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+struct Recording {
+    emitter: Option<StateEmitter>,
+    seq: u64,
+    seq_field: String,
+    thread_field: String,
+}
+
+/// A cloneable, thread-safe handle recording states with a shared
+/// [`StateEmitter`].
+///
+/// Each row gets a `seq` field, counting from 0 in the order rows are
+/// written, and a `thread` field naming the emitting thread or task.
+/// Emitting a state that has a field of either name fails.
+#[derive(Clone)]
+pub struct StateRecorder {
+    recording: Arc<Mutex<Recording>>,
+}
+
+impl StateRecorder {
+    /// Create a recorder writing with `emitter`.
+    pub fn new(emitter: StateEmitter) -> Self {
+        Self::with_field_names(emitter, "seq", "thread")
+    }
+
+    /// Create a recorder writing with `emitter` that names the sequence
+    /// number and thread fields `seq_field` and `thread_field`.
+    pub fn with_field_names(emitter: StateEmitter, seq_field: &str, thread_field: &str) -> Self {
+        Self {
+            recording: Arc::new(Mutex::new(Recording {
+                emitter: Some(emitter),
+                seq: 0,
+                seq_field: seq_field.to_string(),
+                thread_field: thread_field.to_string(),
+            })),
+        }
+    }
+
+    /// Emit a state transition and return its sequence number.
+    ///
+    /// The `state` value must serialize to a JSON object.
+    #[must_use = "emit result should be checked for errors"]
+    pub fn emit<S: Serialize + ?Sized>(&self, action: &str, state: &S) -> Result<u64, Error> {
+        let serde_json::Value::Object(mut row) = serde_json::to_value(state)? else {
+            return Err(ValidationError::NonObjectState {
+                found: format!("{:?}", serde_json::to_value(state).unwrap_or_default()),
+            }
+            .into());
+        };
+        let thread = current_thread();
+
+        let mut recording = self.lock();
+        let seq = recording.seq;
+        for (field, value) in [(&recording.thread_field, thread.into()), (&recording.seq_field, seq.into())] {
+            if row.contains_key(field) {
+                return Err(ValidationError::ReservedField(field.clone()).into());
+            }
+            row.insert(field.clone(), value);
+        }
+        recording
+            .emitter
+            .as_mut()
+            .ok_or(ValidationError::EmitterFinished)?
+            .emit(action, &row)?;
+        recording.seq += 1;
+        Ok(seq)
+    }
+
+    /// Emit the state guarded by `lock`, as seen through `view`, while
+    /// holding the lock. Returns the row's sequence number.
+    #[must_use = "snapshot result should be checked for errors"]
+    pub fn snapshot<L, S>(&self, action: &str, lock: &L, view: impl FnOnce(&L::State) -> S) -> Result<u64, Error>
+    where
+        L: StateLock + ?Sized,
+        S: Serialize,
+    {
+        lock.with_locked(|state| self.emit(action, &view(state)))
+    }
+
+    /// Number of states recorded so far.
+    pub fn count(&self) -> usize {
+        self.lock().emitter.as_ref().map_or(0, StateEmitter::count)
+    }
+
+    /// Stop recording, finish the emitter and return the number of states
+    /// recorded. Later emits from any clone fail.
+    #[must_use = "finish result should be checked for errors"]
+    pub fn finish(&self) -> Result<usize, Error> {
+        let emitter = self.lock().emitter.take().ok_or(ValidationError::EmitterFinished)?;
+        emitter.finish()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Recording> {
+        self.recording.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The current tokio task, or else the current thread's name or id.
+fn current_thread() -> String {
+    #[cfg(feature = "async")]
+    if let Some(id) = tokio::task::try_id() {
+        return format!("task-{id}");
+    }
+    let thread = std::thread::current();
+    match thread.name() {
+        Some(name) => name.to_string(),
+        None => format!("{:?}", thread.id()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorder_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync + Clone>() {}
+        assert_send_sync::<StateRecorder>();
+    }
+
+    #[test]
+    fn snapshots_are_totally_ordered() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.ndjson");
+        let recorder = StateRecorder::new(StateEmitter::new(&path).unwrap());
+        let log = RwLock::new(Vec::<u32>::new());
+
+        std::thread::scope(|s| {
+            for i in 0..8 {
+                let (recorder, log) = (recorder.clone(), &log);
+                std::thread::Builder::new()
+                    .name(format!("worker-{i}"))
+                    .spawn_scoped(s, move || {
+                        log.write().unwrap().push(i);
+                        recorder
+                            .snapshot("push", log, |log| serde_json::json!({ "len": log.len() }))
+                            .unwrap();
+                    })
+                    .unwrap();
+            }
+        });
+        assert_eq!(recorder.finish().unwrap(), 8);
+        assert!(recorder.emit("late", &serde_json::json!({})).is_err());
+
+        let rows: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!(row["seq"], i as u64);
+            assert!(row["thread"].as_str().unwrap().starts_with("worker-"));
+        }
+        // Each snapshot saw every push recorded before it.
+        let lens: Vec<u64> = rows.iter().map(|row| row["len"].as_u64().unwrap()).collect();
+        assert!(lens.windows(2).all(|w| w[0] <= w[1]), "{lens:?}");
+    }
+
+    #[test]
+    fn reserved_fields_are_rejected_or_renamed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.ndjson");
+        let recorder = StateRecorder::new(StateEmitter::new(&path).unwrap());
+        let err = recorder.emit("init", &serde_json::json!({ "seq": 7 })).unwrap_err();
+        assert!(err.to_string().contains("'seq' is reserved"), "got: {err}");
+        assert_eq!(recorder.finish().unwrap(), 0);
+
+        let recorder = StateRecorder::with_field_names(StateEmitter::new(&path).unwrap(), "rec_seq", "rec_thread");
+        assert_eq!(recorder.emit("init", &serde_json::json!({ "seq": 7 })).unwrap(), 0);
+        assert_eq!(recorder.finish().unwrap(), 1);
+        let row: serde_json::Value = serde_json::from_str(std::fs::read_to_string(&path).unwrap().trim()).unwrap();
+        assert_eq!((&row["seq"], &row["rec_seq"]), (&serde_json::json!(7), &serde_json::json!(0)));
+        assert!(row["rec_thread"].is_string() && row.get("thread").is_none());
+    }
+}
@@ -0,0 +1,826 @@
+//! Snowcat types of NDJSON trace rows.
+//!
+//! `TraceData.tla` annotates `TraceLog` with the Snowcat type of its rows.
+//! Without a schema, the type is inferred by merging the types of all rows:
+//! an empty collection takes its element type from other rows and variants
+//! collect the tags seen anywhere. Conflicting types are reported with the
+//! row's line and the field's path. With
+//! [`partial_observations`](super::TraceValidatorConfig::partial_observations),
+//! a top-level field that is `null` or missing in some rows becomes
+//! `Some(T) | None(UNIT)`; otherwise, and always for nested fields, such
+//! rows are rejected, except that `null` is kept as the string `"null"`
+//! where other rows hold strings.
+//!
+//! Where no row tells, an empty collection's element type is `Int` and the
+//! type of `null` is `Str`. Apalache needs a concrete type, and these are
+//! the types earlier versions inferred from the first row alone, so traces
+//! that type-checked before keep the same `TraceData.tla`.
+//!
+//! A schema set with [`TraceValidatorConfig::schema`](super::TraceValidatorConfig::schema)
+//! replaces inference. It can be written by hand or built from Rust types
+//! with [`TlaType`]. Under a schema, plain JSON arrays are accepted for sets
+//! and tuples and plain JSON objects for functions, so `HashSet` and
+//! `HashMap` fields need no `serialize_with` helpers.
+//!
+//! # Example
+//!
+//! ```
+//! use std::collections::{BTreeMap, HashSet};
+//! use tla_connect::TlaType;
+//!
+//! struct Bank {
+//!     balances: BTreeMap<String, u64>,
+//!     frozen: HashSet<String>,
+//!     last_error: Option<String>,
+//! }
+//!
+//! impl TlaType for Bank {
+//!     fn tla_type() -> String {
+//!         format!(
+//!             "{{balances: {}, frozen: {}, last_error: {}}}",
+//!             BTreeMap::<String, u64>::tla_type(),
+//!             HashSet::<String>::tla_type(),
+//!             Option::<String>::tla_type(),
+//!         )
+//!     }
+//! }
+//!
+//! assert_eq!(
+//!     Bank::tla_type(),
+//!     "{balances: Str -> Int, frozen: Set(Str), last_error: Some(Str) | None(UNIT)}"
+//! );
+//! ```
+
+use super::validator::{itf_encoding, json_to_tla_value, ItfEncoded};
+use crate::error::{Error, ValidationError};
+use crate::tla::escape_tla_string;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+
+/// A Rust type with a Snowcat type for trace validation schemas.
+///
+/// Implemented for primitives, `Option`, tuples and standard collections.
+/// There is no derive: implement it by hand for state types, formatting a
+/// record type from the fields' types as in the [module example](self).
+pub trait TlaType {
+    /// The Snowcat type, e.g. `Set(Int)` or `{count: Int}`.
+    fn tla_type() -> String;
+}
+
+macro_rules! impl_tla_type {
+    ($ty:expr => $($t:ty),*) => {
+        $(impl TlaType for $t {
+            fn tla_type() -> String {
+                $ty.to_string()
+            }
+        })*
+    };
+}
+
+impl_tla_type!("Bool" => bool);
+impl_tla_type!("Int" => i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+impl_tla_type!("Str" => String, str, char);
+impl_tla_type!("UNIT" => ());
+
+impl<T: TlaType + ?Sized> TlaType for &T {
+    fn tla_type() -> String {
+        T::tla_type()
+    }
+}
+
+impl<T: TlaType + ?Sized> TlaType for Box<T> {
+    fn tla_type() -> String {
+        T::tla_type()
+    }
+}
+
+impl<T: TlaType> TlaType for Option<T> {
+    fn tla_type() -> String {
+        format!("Some({}) | None(UNIT)", T::tla_type())
+    }
+}
+
+impl<T: TlaType, S> TlaType for HashSet<T, S> {
+    fn tla_type() -> String {
+        format!("Set({})", T::tla_type())
+    }
+}
+
+impl<T: TlaType> TlaType for Vec<T> {
+    fn tla_type() -> String {
+        format!("Seq({})", T::tla_type())
+    }
+}
+
+impl<T: TlaType> TlaType for VecDeque<T> {
+    fn tla_type() -> String {
+        format!("Seq({})", T::tla_type())
+    }
+}
+
+impl<T: TlaType> TlaType for [T] {
+    fn tla_type() -> String {
+        format!("Seq({})", T::tla_type())
+    }
+}
+
+impl<T: TlaType, const N: usize> TlaType for [T; N] {
+    fn tla_type() -> String {
+        format!("Seq({})", T::tla_type())
+    }
+}
+
+impl<T: TlaType> TlaType for BTreeSet<T> {
+    fn tla_type() -> String {
+        format!("Set({})", T::tla_type())
+    }
+}
+
+impl<K: TlaType, V: TlaType> TlaType for BTreeMap<K, V> {
+    fn tla_type() -> String {
+        format!("{} -> {}", K::tla_type(), V::tla_type())
+    }
+}
+
+impl<K: TlaType, V: TlaType, S> TlaType for HashMap<K, V, S> {
+    fn tla_type() -> String {
+        format!("{} -> {}", K::tla_type(), V::tla_type())
+    }
+}
+
+macro_rules! impl_tla_type_tuple {
+    ($($t:ident),+) => {
+        impl<$($t: TlaType),+> TlaType for ($($t,)+) {
+            fn tla_type() -> String {
+                let elems: Vec<String> = vec![$($t::tla_type()),+];
+                format!("<<{}>>", elems.join(", "))
+            }
+        }
+    };
+}
+
+impl_tla_type_tuple!(A);
+impl_tla_type_tuple!(A, B);
+impl_tla_type_tuple!(A, B, C);
+impl_tla_type_tuple!(A, B, C, D);
+
+/// A Snowcat type, inferred from trace rows or parsed from a schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum SnowcatType {
+    Bool,
+    Int,
+    Str,
+    Unit,
+    /// Element type of an empty collection, until other rows tell.
+    Unknown,
+    /// Type of `null` where no other rows tell. Rendered as the string
+    /// `"null"`, with type `Str`.
+    Null,
+    Seq(Box<SnowcatType>),
+    Set(Box<SnowcatType>),
+    Fun(Box<SnowcatType>, Box<SnowcatType>),
+    Tuple(Vec<SnowcatType>),
+    Record(BTreeMap<String, SnowcatType>),
+    Variant(BTreeMap<String, SnowcatType>),
+    /// `Some(T) | None(UNIT)`: a top-level field that may be `null` or
+    /// missing.
+    Option(Box<SnowcatType>),
+    /// A type this module does not interpret, such as an uninterpreted
+    /// `NODE` type; values are rendered without checking.
+    Other(String),
+}
+
+/// Why `null` or missing values are rejected below the top level.
+const NESTED_PARTIAL: &str = "only top-level fields can be partial observations";
+
+/// A type conflict at a path inside a row.
+struct Conflict {
+    path: String,
+    expected: String,
+    found: String,
+}
+
+impl Conflict {
+    fn new(path: &str, expected: impl fmt::Display, found: impl fmt::Display) -> Self {
+        Self {
+            path: path.to_string(),
+            expected: expected.to_string(),
+            found: found.to_string(),
+        }
+    }
+
+    fn at(self, line: usize) -> Error {
+        ValidationError::TypeConflict {
+            line,
+            field: self.path,
+            expected: self.expected,
+            found: self.found,
+        }
+        .into()
+    }
+}
+
+impl SnowcatType {
+    /// Infer the record type of one row.
+    pub(crate) fn infer_row(row: &serde_json::Map<String, serde_json::Value>, line: usize) -> Result<Self, Error> {
+        row.iter()
+            .map(|(k, v)| Ok((k.clone(), Self::infer_value(v, k)?)))
+            .collect::<Result<_, Conflict>>()
+            .map(Self::Record)
+            .map_err(|c| c.at(line))
+    }
+
+    fn infer_value(value: &serde_json::Value, path: &str) -> Result<Self, Conflict> {
+        let merged = |elems: &mut dyn Iterator<Item = &serde_json::Value>| -> Result<Self, Conflict> {
+            let mut ty = Self::Unknown;
+            for (i, elem) in elems.enumerate() {
+                let elem_path = format!("{path}[{i}]");
+                ty = ty.merge(Self::infer_value(elem, &elem_path)?, &elem_path)?;
+            }
+            Ok(ty)
+        };
+        Ok(match value {
+            serde_json::Value::Null => Self::Null,
+            serde_json::Value::Bool(_) => Self::Bool,
+            serde_json::Value::Number(_) => Self::Int,
+            serde_json::Value::String(_) => Self::Str,
+            serde_json::Value::Array(arr) => Self::Seq(Box::new(merged(&mut arr.iter())?)),
+            serde_json::Value::Object(obj) => match itf_encoding(obj).ok().flatten() {
+                Some(ItfEncoded::Set(set)) => Self::Set(Box::new(merged(&mut set.iter())?)),
+                Some(ItfEncoded::Tuple(tuple)) => Self::Tuple(
+                    tuple
+                        .iter()
+                        .enumerate()
+                        .map(|(i, v)| Self::infer_value(v, &format!("{path}[{i}]")))
+                        .collect::<Result<_, _>>()?,
+                ),
+                Some(ItfEncoded::Map(pairs)) => Self::Fun(
+                    Box::new(merged(&mut pairs.iter().map(|(k, _)| *k))?),
+                    Box::new(merged(&mut pairs.iter().map(|(_, v)| *v))?),
+                ),
+                Some(ItfEncoded::BigInt(_)) => Self::Int,
+                Some(ItfEncoded::Variant(tag, value)) => {
+                    let ty = match value {
+                        Some(v) => Self::infer_value(v, &format!("{path}.value"))?,
+                        None => Self::Unit,
+                    };
+                    Self::Variant(BTreeMap::from([(tag.to_string(), ty)]))
+                }
+                None => Self::Record(
+                    obj.iter()
+                        .map(|(k, v)| Ok((k.clone(), Self::infer_value(v, &join(path, k))?)))
+                        .collect::<Result<_, Conflict>>()?,
+                ),
+            },
+        })
+    }
+
+    /// The type covering values of both `self` and `other`.
+    fn merge(self, other: Self, path: &str) -> Result<Self, Conflict> {
+        use SnowcatType::*;
+        Ok(match (self, other) {
+            (a, b) if a == b => a,
+            (Unknown, t) | (t, Unknown) => t,
+            (Null, Str) | (Str, Null) => Str,
+            (Null, t) | (t, Null) => return Err(Conflict::new(path, t, format!("null ({NESTED_PARTIAL})"))),
+            (Option(a), Option(b)) => Option(Box::new(a.merge(*b, path)?)),
+            (Seq(a), Seq(b)) => Seq(Box::new(a.merge(*b, path)?)),
+            (Set(a), Set(b)) => Set(Box::new(a.merge(*b, path)?)),
+            (Fun(ka, va), Fun(kb, vb)) => Fun(Box::new(ka.merge(*kb, path)?), Box::new(va.merge(*vb, path)?)),
+            (Tuple(a), Tuple(b)) if a.len() == b.len() => Tuple(
+                a.into_iter()
+                    .zip(b)
+                    .enumerate()
+                    .map(|(i, (a, b))| a.merge(b, &format!("{path}[{i}]")))
+                    .collect::<Result<_, _>>()?,
+            ),
+            (Record(mut a), Record(mut b)) => {
+                let keys: BTreeSet<String> = a.keys().chain(b.keys()).cloned().collect();
+                let mut fields = BTreeMap::new();
+                for key in keys {
+                    let ty = match (a.remove(&key), b.remove(&key)) {
+                        (Some(a), Some(b)) => a.merge(b, &join(path, &key))?,
+                        (Some(t), None) | (None, Some(t)) => {
+                            return Err(Conflict::new(&join(path, &key), t, format!("missing field ({NESTED_PARTIAL})")))
+                        }
+                        (None, None) => unreachable!("key from either record"),
+                    };
+                    fields.insert(key, ty);
+                }
+                Record(fields)
+            }
+            (Variant(mut a), Variant(b)) => {
+                for (tag, ty) in b {
+                    let ty = match a.remove(&tag) {
+                        Some(prev) => prev.merge(ty, &format!("{path}.value"))?,
+                        None => ty,
+                    };
+                    a.insert(tag, ty);
+                }
+                Variant(a)
+            }
+            (a, b) => return Err(Conflict::new(path, a, b)),
+        })
+    }
+
+    /// Merge the type of the row at `line` into the type of previous rows
+    /// (`Unknown` before the first row). Top-level fields that are `null`
+    /// or missing in some rows become `Option` if `partial_observations`
+    /// is set, and are rejected otherwise.
+    pub(crate) fn merge_row(self, row: Self, line: usize, partial_observations: bool) -> Result<Self, Error> {
+        let (mut prev, mut next) = match (self, row) {
+            (Self::Unknown, row) => return Ok(row),
+            (Self::Record(prev), Self::Record(next)) => (prev, next),
+            (prev, row) => return Err(Conflict::new("", prev, row).at(line)),
+        };
+        if !partial_observations && !prev.keys().eq(next.keys()) {
+            return Err(ValidationError::InconsistentSchema {
+                line,
+                expected: prev.into_keys().collect(),
+                found: next.into_keys().collect(),
+            }
+            .into());
+        }
+
+        let keys: BTreeSet<String> = prev.keys().chain(next.keys()).cloned().collect();
+        let mut fields = BTreeMap::new();
+        for key in keys {
+            let ty = match (prev.remove(&key), next.remove(&key)) {
+                (Some(a), Some(b)) => Self::merge_field(a, b, &key, line, partial_observations)?,
+                (Some(t), None) | (None, Some(t)) => Self::Option(Box::new(t.observed())),
+                (None, None) => unreachable!("key from either record"),
+            };
+            fields.insert(key, ty);
+        }
+        Ok(Self::Record(fields))
+    }
+
+    /// Merge the types of the top-level field `key` in two rows.
+    fn merge_field(a: Self, b: Self, key: &str, line: usize, partial_observations: bool) -> Result<Self, Error> {
+        let optional = |t: &Self| matches!(t, Self::Null | Self::Option(_));
+        if a != b && (optional(&a) || optional(&b)) {
+            if partial_observations {
+                let ty = a.observed().merge(b.observed(), key).map_err(|c| c.at(line))?;
+                return Ok(Self::Option(Box::new(ty)));
+            }
+            if !matches!((&a, &b), (Self::Null, Self::Str) | (Self::Str, Self::Null)) {
+                return Err(ValidationError::PartialObservation {
+                    line,
+                    field: key.to_string(),
+                }
+                .into());
+            }
+        }
+        a.merge(b, key).map_err(|c| c.at(line))
+    }
+
+    /// The type of a partial field's observed values.
+    fn observed(self) -> Self {
+        match self {
+            Self::Option(t) => *t,
+            Self::Null => Self::Unknown,
+            t => t,
+        }
+    }
+
+    /// Parse a Snowcat type such as `{count: Int, ids: Set(Int)}`.
+    pub(crate) fn parse(schema: &str) -> Result<Self, Error> {
+        let tokens = tokenize(schema).map_err(ValidationError::InvalidSchema)?;
+        let mut parser = Parser { tokens: &tokens, pos: 0 };
+        let ty = parser.ty().map_err(ValidationError::InvalidSchema)?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(ty),
+            Some(tok) => Err(ValidationError::InvalidSchema(format!("unexpected '{tok}' in {schema}")).into()),
+        }
+    }
+
+    /// Record fields that may be missing, i.e. of `Option` type.
+    pub(crate) fn optional_fields(&self) -> BTreeSet<String> {
+        match self {
+            Self::Record(fields) => fields
+                .iter()
+                .filter(|(_, ty)| matches!(ty, Self::Option(_)))
+                .map(|(k, _)| k.clone())
+                .collect(),
+            _ => BTreeSet::new(),
+        }
+    }
+
+    /// Standard modules the type's values need: `TLC` for functions and
+    /// `Variants` for variants.
+    pub(crate) fn required_modules(&self, modules: &mut BTreeSet<&'static str>) {
+        match self {
+            Self::Seq(t) | Self::Set(t) => t.required_modules(modules),
+            Self::Fun(k, v) => {
+                modules.insert("TLC");
+                k.required_modules(modules);
+                v.required_modules(modules);
+            }
+            Self::Tuple(ts) => ts.iter().for_each(|t| t.required_modules(modules)),
+            Self::Record(fields) => fields.values().for_each(|t| t.required_modules(modules)),
+            Self::Variant(tags) => {
+                modules.insert("Variants");
+                tags.values().for_each(|t| t.required_modules(modules));
+            }
+            Self::Option(t) => {
+                modules.insert("Variants");
+                t.required_modules(modules);
+            }
+            _ => {}
+        }
+    }
+
+    /// Render a row as a TLA+ record of this record type.
+    pub(crate) fn render_row(&self, row: &serde_json::Map<String, serde_json::Value>, line: usize) -> Result<String, Error> {
+        match self {
+            Self::Record(fields) => render_record(fields, row, line, ""),
+            _ => Err(Conflict::new("", self, "a record").at(line)),
+        }
+    }
+
+    /// Render `value` (None if missing) as a TLA+ expression of this type.
+    fn render(&self, value: Option<&serde_json::Value>, line: usize, path: &str) -> Result<String, Error> {
+        let Some(value) = value else {
+            return match self {
+                Self::Option(_) => Ok("Variant(\"None\", UNIT)".to_string()),
+                _ => Err(Conflict::new(path, self, "missing field").at(line)),
+            };
+        };
+        let conflict = |found: &dyn fmt::Display| Conflict::new(path, self, found).at(line);
+        let elems = |elems: &[serde_json::Value], ty: &Self| -> Result<String, Error> {
+            let elems: Vec<String> = elems
+                .iter()
+                .enumerate()
+                .map(|(i, v)| ty.render(Some(v), line, &format!("{path}[{i}]")))
+                .collect::<Result<_, _>>()?;
+            Ok(elems.join(", "))
+        };
+        let tuple = |elems: &[serde_json::Value], tys: &[Self]| -> Result<String, Error> {
+            if elems.len() != tys.len() {
+                return Err(conflict(&format!("a tuple of {} elements", elems.len())));
+            }
+            let elems: Vec<String> = tys
+                .iter()
+                .zip(elems)
+                .enumerate()
+                .map(|(i, (ty, v))| ty.render(Some(v), line, &format!("{path}[{i}]")))
+                .collect::<Result<_, _>>()?;
+            Ok(format!("<<{}>>", elems.join(", ")))
+        };
+
+        let encoded = value.as_object().and_then(|obj| itf_encoding(obj).ok().flatten());
+        match (self, value, encoded) {
+            (Self::Option(_), serde_json::Value::Null, _) => Ok("Variant(\"None\", UNIT)".to_string()),
+            (Self::Option(t), _, _) => Ok(format!("Variant(\"Some\", {})", t.render(Some(value), line, path)?)),
+            (Self::Null | Self::Str, serde_json::Value::Null, _) => Ok("\"null\"".to_string()),
+            (Self::Bool, serde_json::Value::Bool(_), _)
+            | (Self::Int, serde_json::Value::Number(_), _)
+            | (Self::Int, _, Some(ItfEncoded::BigInt(_)))
+            | (Self::Str, serde_json::Value::String(_), _)
+            | (Self::Unknown | Self::Other(_), _, _) => json_to_tla_value(value, line, path),
+            (Self::Unit, _, Some(ItfEncoded::Tuple([]))) => Ok("UNIT".to_string()),
+            (Self::Seq(t), serde_json::Value::Array(arr), _) => Ok(format!("<<{}>>", elems(arr, t)?)),
+            (Self::Set(t), serde_json::Value::Array(arr), _) => Ok(format!("{{{}}}", elems(arr, t)?)),
+            (Self::Set(t), _, Some(ItfEncoded::Set(set))) => Ok(format!("{{{}}}", elems(set, t)?)),
+            (Self::Tuple(tys), serde_json::Value::Array(arr), _) => tuple(arr, tys),
+            (Self::Tuple(tys), _, Some(ItfEncoded::Tuple(elems))) => tuple(elems, tys),
+            (Self::Fun(k, v), _, Some(ItfEncoded::Map(pairs))) => {
+                let pairs: Vec<String> = pairs
+                    .iter()
+                    .map(|(key, val)| {
+                        Ok(format!(
+                            "{} :> {}",
+                            k.render(Some(key), line, path)?,
+                            v.render(Some(val), line, &join(path, &key.to_string()))?
+                        ))
+                    })
+                    .collect::<Result<_, Error>>()?;
+                Ok(function(pairs))
+            }
+            // A plain JSON object as a function: serde_json writes map keys as strings.
+            (Self::Fun(k, v), serde_json::Value::Object(obj), None) => {
+                let pairs: Vec<String> = obj
+                    .iter()
+                    .map(|(key, val)| {
+                        let tla_key = match **k {
+                            Self::Str | Self::Unknown => format!("\"{}\"", escape_tla_string(key)),
+                            Self::Int if key.parse::<i128>().is_ok() => key.clone(),
+                            _ => return Err(Conflict::new(path, k, format!("key {key:?}")).at(line)),
+                        };
+                        Ok(format!("{tla_key} :> {}", v.render(Some(val), line, &join(path, key))?))
+                    })
+                    .collect::<Result<_, Error>>()?;
+                Ok(function(pairs))
+            }
+            (Self::Variant(tags), _, Some(ItfEncoded::Variant(tag, inner))) => {
+                let ty = tags.get(tag).ok_or_else(|| conflict(&format!("{tag}(...)")))?;
+                let inner = match (ty, inner) {
+                    (Self::Unit, None) => "UNIT".to_string(),
+                    (ty, inner) => ty.render(inner, line, &format!("{path}.value"))?,
+                };
+                Ok(format!("Variant(\"{}\", {inner})", escape_tla_string(tag)))
+            }
+            (Self::Record(fields), serde_json::Value::Object(obj), None) => render_record(fields, obj, line, path),
+            (_, value, _) => Err(conflict(value)),
+        }
+    }
+}
+
+fn render_record(
+    fields: &BTreeMap<String, SnowcatType>,
+    obj: &serde_json::Map<String, serde_json::Value>,
+    line: usize,
+    path: &str,
+) -> Result<String, Error> {
+    if let Some(extra) = obj.keys().find(|k| !fields.contains_key(*k)) {
+        return Err(Conflict::new(&join(path, extra), "no such field", "a value").at(line));
+    }
+    let values: Vec<String> = fields
+        .iter()
+        .map(|(key, ty)| Ok(format!("{key} |-> {}", ty.render(obj.get(key), line, &join(path, key))?)))
+        .collect::<Result<_, Error>>()?;
+    Ok(format!("[{}]", values.join(", ")))
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn function(pairs: Vec<String>) -> String {
+    if pairs.is_empty() {
+        "[x \\in {} |-> x]".to_string()
+    } else {
+        format!("({})", pairs.join(" @@ "))
+    }
+}
+
+impl fmt::Display for SnowcatType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |ts: &mut dyn Iterator<Item = String>| ts.collect::<Vec<_>>().join(", ");
+        match self {
+            Self::Bool => write!(f, "Bool"),
+            Self::Int | Self::Unknown => write!(f, "Int"),
+            Self::Str | Self::Null => write!(f, "Str"),
+            Self::Unit => write!(f, "UNIT"),
+            Self::Seq(t) => write!(f, "Seq({t})"),
+            Self::Set(t) => write!(f, "Set({t})"),
+            Self::Fun(k, v) => write!(f, "{k} -> {v}"),
+            Self::Tuple(ts) => write!(f, "<<{}>>", list(&mut ts.iter().map(|t| t.to_string()))),
+            Self::Record(fields) => write!(f, "{{{}}}", list(&mut fields.iter().map(|(k, t)| format!("{k}: {t}")))),
+            Self::Variant(tags) => {
+                let tags: Vec<String> = tags.iter().map(|(tag, t)| format!("{tag}({t})")).collect();
+                write!(f, "{}", tags.join(" | "))
+            }
+            Self::Option(t) => write!(f, "Some({t}) | None(UNIT)"),
+            Self::Other(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+fn tokenize(schema: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = schema.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' | ')' | '{' | '}' | ',' | ':' | '|' => tokens.push(c.to_string()),
+            '<' | '>' | '-' => {
+                let token = match (c, chars.next()) {
+                    ('<', Some('<')) => "<<",
+                    ('>', Some('>')) => ">>",
+                    ('-', Some('>')) => "->",
+                    _ => return Err(format!("unexpected '{c}' in {schema}")),
+                };
+                tokens.push(token.to_string());
+            }
+            c if c.is_ascii_alphanumeric() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphanumeric() || **c == '_') {
+                    ident.push(c);
+                    chars.next();
+                }
+                tokens.push(ident);
+            }
+            c => return Err(format!("unexpected '{c}' in {schema}")),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [String],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Result<&str, String> {
+        let token = self.tokens.get(self.pos).ok_or("unexpected end of schema")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(format!("expected '{expected}', found '{token}'")),
+        }
+    }
+
+    /// `variants ('->' ty)?`
+    fn ty(&mut self) -> Result<SnowcatType, String> {
+        let ty = self.variants()?;
+        if self.peek() == Some("->") {
+            self.pos += 1;
+            return Ok(SnowcatType::Fun(Box::new(ty), Box::new(self.ty()?)));
+        }
+        Ok(ty)
+    }
+
+    /// `atom ('|' atom)*`, where a union's atoms must be variant tags.
+    fn variants(&mut self) -> Result<SnowcatType, String> {
+        let first = self.atom()?;
+        if self.peek() != Some("|") {
+            return Ok(match first {
+                Atom::Type(ty) => ty,
+                Atom::Tag(tag, ty) => SnowcatType::Variant(BTreeMap::from([(tag, ty)])),
+            });
+        }
+        let mut tags = BTreeMap::new();
+        let mut atom = first;
+        loop {
+            let Atom::Tag(tag, ty) = atom else {
+                return Err("only variant tags like A(Int) can be joined with '|'".to_string());
+            };
+            tags.insert(tag, ty);
+            if self.peek() != Some("|") {
+                break;
+            }
+            self.pos += 1;
+            atom = self.atom()?;
+        }
+        let is_option = tags.len() == 2 && tags.get("None") == Some(&SnowcatType::Unit);
+        match tags.remove("Some") {
+            Some(ty) if is_option => Ok(SnowcatType::Option(Box::new(ty))),
+            Some(ty) => {
+                tags.insert("Some".to_string(), ty);
+                Ok(SnowcatType::Variant(tags))
+            }
+            None => Ok(SnowcatType::Variant(tags)),
+        }
+    }
+
+    fn atom(&mut self) -> Result<Atom, String> {
+        let token = self.next()?.to_string();
+        let ty = match token.as_str() {
+            "Bool" => SnowcatType::Bool,
+            "Int" => SnowcatType::Int,
+            "Str" => SnowcatType::Str,
+            "UNIT" => SnowcatType::Unit,
+            "(" => {
+                let ty = self.ty()?;
+                self.expect(")")?;
+                ty
+            }
+            "<<" => {
+                let mut elems = vec![self.ty()?];
+                while self.peek() == Some(",") {
+                    self.pos += 1;
+                    elems.push(self.ty()?);
+                }
+                self.expect(">>")?;
+                SnowcatType::Tuple(elems)
+            }
+            "{" => {
+                let mut fields = BTreeMap::new();
+                while self.peek() != Some("}") {
+                    if !fields.is_empty() {
+                        self.expect(",")?;
+                    }
+                    let name = self.next()?.to_string();
+                    self.expect(":")?;
+                    fields.insert(name, self.ty()?);
+                }
+                self.pos += 1;
+                SnowcatType::Record(fields)
+            }
+            name if name.starts_with(|c: char| c.is_ascii_alphabetic()) && self.peek() == Some("(") => {
+                self.pos += 1;
+                let inner = self.ty()?;
+                self.expect(")")?;
+                match name {
+                    "Seq" => SnowcatType::Seq(Box::new(inner)),
+                    "Set" => SnowcatType::Set(Box::new(inner)),
+                    tag => return Ok(Atom::Tag(tag.to_string(), inner)),
+                }
+            }
+            name if name.starts_with(|c: char| c.is_ascii_alphabetic()) => SnowcatType::Other(name.to_string()),
+            token => return Err(format!("unexpected '{token}'")),
+        };
+        Ok(Atom::Type(ty))
+    }
+}
+
+enum Atom {
+    Type(SnowcatType),
+    /// `Tag(T)`, one alternative of a variant type.
+    Tag(String, SnowcatType),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn infer_rows(rows: &[serde_json::Value], partial_observations: bool) -> Result<SnowcatType, Error> {
+        rows.iter().enumerate().try_fold(SnowcatType::Unknown, |ty, (i, row)| {
+            ty.merge_row(SnowcatType::infer_row(row.as_object().unwrap(), i + 1)?, i + 1, partial_observations)
+        })
+    }
+
+    #[test]
+    fn merges_types_across_rows() {
+        let ty = infer_rows(
+            &[
+                json!({"log": [], "name": null, "mode": {"tag": "Idle"}}),
+                json!({"log": ["a"], "name": "n1", "mode": {"tag": "Busy", "value": 1}}),
+            ],
+            false,
+        )
+        .unwrap();
+        assert_eq!(ty.to_string(), "{log: Seq(Str), mode: Busy(Int) | Idle(UNIT), name: Str}");
+        assert!(ty.optional_fields().is_empty());
+    }
+
+    #[test]
+    fn rejects_partial_rows_unless_enabled() {
+        let missing = [json!({"a": 1, "b": 2}), json!({"a": 1})];
+        let err = infer_rows(&missing, false).unwrap_err().to_string();
+        assert!(err.contains("Inconsistent record schema: line 2"), "got: {err}");
+
+        let null = [json!({"a": null}), json!({"a": 1})];
+        let err = infer_rows(&null, false).unwrap_err().to_string();
+        assert!(err.contains("'a' is null in some rows and not in others (line 2)") && err.contains("partial_observations"), "got: {err}");
+
+        for nested in [
+            [json!({"s": {"x": 1}}), json!({"s": {"x": null}})],
+            [json!({"s": {"x": 1}}), json!({"s": {}})],
+        ] {
+            let err = infer_rows(&nested, true).unwrap_err().to_string();
+            assert!(err.contains("field 's.x'") && err.contains("only top-level fields"), "got: {err}");
+        }
+    }
+
+    #[test]
+    fn merges_partial_observations() {
+        let ty = infer_rows(&[
+            json!({"log": [], "last": null, "mode": {"tag": "Idle"}}),
+            json!({"log": ["a"], "last": 3, "mode": {"tag": "Busy", "value": 1}, "extra": true}),
+        ], true)
+        .unwrap();
+        assert_eq!(
+            ty.to_string(),
+            "{extra: Some(Bool) | None(UNIT), last: Some(Int) | None(UNIT), log: Seq(Str), mode: Busy(Int) | Idle(UNIT)}"
+        );
+        assert_eq!(ty.optional_fields(), BTreeSet::from(["extra".to_string(), "last".to_string()]));
+    }
+
+    #[test]
+    fn reports_conflicts_with_paths() {
+        let err = infer_rows(&[json!({"s": {"xs": [1]}}), json!({"s": {"xs": ["a"]}})], false).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("Type conflict at line 2, field 's.xs': expected Int, found Str"));
+    }
+
+    #[test]
+    fn parses_schemas() {
+        for schema in [
+            "{a: Int, b: Set(Str), c: Int -> Seq(Bool), d: <<Int, Str>>, e: Some(Int) | None(UNIT)}",
+            "{m: A(UNIT) | B({x: Int}), n: NODE}",
+        ] {
+            assert_eq!(SnowcatType::parse(schema).unwrap().to_string(), schema);
+        }
+        assert!(SnowcatType::parse("{a: Int").is_err());
+        assert!(SnowcatType::parse("Int | Str").is_err());
+    }
+
+    #[test]
+    fn renders_plain_json_under_schema() {
+        let ty = SnowcatType::parse("{ids: Set(Int), owners: Int -> Str, last: Some(Int) | None(UNIT)}").unwrap();
+        let row = json!({"ids": [1, 2], "owners": {"7": "a"}, "last": null});
+        assert_eq!(
+            ty.render_row(row.as_object().unwrap(), 1).unwrap(),
+            r#"[ids |-> {1, 2}, last |-> Variant("None", UNIT), owners |-> (7 :> "a")]"#
+        );
+        let row = json!({"ids": [1], "owners": {}, "other": 1});
+        assert!(ty.render_row(row.as_object().unwrap(), 1).is_err());
+    }
+}
@@ -0,0 +1,328 @@
+//! Destinations for the NDJSON lines written by a [`StateEmitter`].
+//!
+//! [`StateEmitter::new`] writes to a buffered file. [`StateEmitter::with_sink`]
+//! takes any [`TraceSink`]: a [`WriterSink`] around any
+//! `std::io::Write + Send` type, a [`MemorySink`] to validate in-process
+//! right after a test, a [`RotatingSink`] for long-running services, and
+//! with the `gzip` or `zstd` feature a [`GzipSink`] or [`ZstdSink`].
+//! Compressed traces, in-memory traces and the segments of a rotated trace
+//! are accepted by [`validate_trace`](super::validate_trace) as they are.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use tla_connect::{validate_trace, MemorySink, StateEmitter, TraceValidatorConfig};
+//!
+//! let sink = MemorySink::new();
+//! let mut emitter = StateEmitter::with_sink(sink.clone());
+//! emitter.emit("init", &serde_json::json!({"count": 0}))?;
+//! emitter.finish()?;
+//!
+//! let config = TraceValidatorConfig::builder().trace_spec("specs/CounterTrace.tla").build()?;
+//! let result = validate_trace(&config, &sink.contents())?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! [`StateEmitter`]: super::StateEmitter
+//! [`StateEmitter::new`]: super::StateEmitter::new
+//! [`StateEmitter::with_sink`]: super::StateEmitter::with_sink
+
+use crate::error::{Error, ValidationError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Destination for NDJSON trace lines.
+pub trait TraceSink: Send {
+    /// Write one NDJSON line, including its trailing newline.
+    fn write_line(&mut self, line: &[u8]) -> std::io::Result<()>;
+
+    /// Flush buffered lines.
+    fn flush_lines(&mut self) -> std::io::Result<()>;
+
+    /// Complete the output, e.g. write a compression trailer. Called once,
+    /// when the emitter is finished or dropped.
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.flush_lines()
+    }
+}
+
+/// Any `std::io::Write` destination, such as a socket or a pipe.
+#[derive(Debug)]
+pub struct WriterSink<W>(W);
+
+impl<W: Write + Send> WriterSink<W> {
+    /// Write lines to `writer`, which should be buffered.
+    pub fn new(writer: W) -> Self {
+        Self(writer)
+    }
+
+    /// The underlying writer.
+    pub fn into_inner(self) -> W {
+        self.0
+    }
+}
+
+impl<W: Write + Send> TraceSink for WriterSink<W> {
+    fn write_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        self.0.write_all(line)
+    }
+
+    fn flush_lines(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// An in-memory trace buffer.
+///
+/// Clones share the buffer: keep one to read the trace after the emitter
+/// holding another is finished.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySink {
+    buf: Arc<Mutex<Vec<u8>>>,
+}
+
+impl MemorySink {
+    /// Create an empty buffer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The NDJSON written so far.
+    pub fn contents(&self) -> Vec<u8> {
+        self.buf.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+}
+
+impl TraceSink for MemorySink {
+    fn write_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        self.buf.lock().unwrap_or_else(PoisonError::into_inner).extend_from_slice(line);
+        Ok(())
+    }
+
+    fn flush_lines(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Files of at most `max_bytes` each, switching to a new file before a line
+/// that would overflow the current one.
+///
+/// The first file is `path`; later ones insert a number before the
+/// extension, so `trace.ndjson` continues in `trace.1.ndjson`,
+/// `trace.2.ndjson` and so on. Lines are never split, and each file is an
+/// NDJSON trace segment; concatenated in order they form the whole trace.
+/// Validate them together with [`TraceInput::Segments`] and
+/// [`segments`](Self::segments).
+///
+/// [`TraceInput::Segments`]: super::TraceInput::Segments
+pub struct RotatingSink {
+    path: PathBuf,
+    max_bytes: u64,
+    index: usize,
+    written: u64,
+    file: std::io::BufWriter<std::fs::File>,
+}
+
+impl RotatingSink {
+    /// Create `path` and rotate after `max_bytes`.
+    ///
+    /// Segments left over from an earlier trace at `path` are deleted, so
+    /// [`segments`](Self::segments) only returns files of this one.
+    pub fn new(path: &Path, max_bytes: u64) -> Result<Self, Error> {
+        let file = create(path)?;
+        for stale in Self::segments(path).into_iter().skip(1) {
+            std::fs::remove_file(stale).map_err(ValidationError::Io)?;
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            index: 0,
+            written: 0,
+            file,
+        })
+    }
+
+    /// The files written for `path` that exist, in order.
+    pub fn segments(path: &Path) -> Vec<PathBuf> {
+        (0..)
+            .map(|index| Self::segment_path(path, index))
+            .take_while(|segment| segment.exists())
+            .collect()
+    }
+
+    /// Path of the `index`-th file written for `path`.
+    pub fn segment_path(path: &Path, index: usize) -> PathBuf {
+        if index == 0 {
+            return path.to_path_buf();
+        }
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        match path.extension() {
+            Some(ext) => path.with_file_name(format!("{stem}.{index}.{}", ext.to_string_lossy())),
+            None => path.with_file_name(format!("{stem}.{index}")),
+        }
+    }
+}
+
+impl TraceSink for RotatingSink {
+    fn write_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        let len = line.len() as u64;
+        if self.written > 0 && self.written + len > self.max_bytes {
+            self.file.flush()?;
+            self.index += 1;
+            self.file = std::io::BufWriter::new(std::fs::File::create(Self::segment_path(&self.path, self.index))?);
+            self.written = 0;
+        }
+        self.file.write_all(line)?;
+        self.written += len;
+        Ok(())
+    }
+
+    fn flush_lines(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// A gzip-compressed trace file (requires `gzip` feature).
+#[cfg(feature = "gzip")]
+pub struct GzipSink(flate2::write::GzEncoder<std::io::BufWriter<std::fs::File>>);
+
+#[cfg(feature = "gzip")]
+impl GzipSink {
+    /// Create `path`, conventionally ending in `.ndjson.gz`.
+    pub fn new(path: &Path) -> Result<Self, Error> {
+        Ok(Self(flate2::write::GzEncoder::new(create(path)?, flate2::Compression::default())))
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl TraceSink for GzipSink {
+    fn write_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        self.0.write_all(line)
+    }
+
+    fn flush_lines(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.0.try_finish()?;
+        self.0.get_mut().flush()
+    }
+}
+
+/// A zstd-compressed trace file (requires `zstd` feature).
+#[cfg(feature = "zstd")]
+pub struct ZstdSink(zstd::stream::write::Encoder<'static, std::io::BufWriter<std::fs::File>>);
+
+#[cfg(feature = "zstd")]
+impl ZstdSink {
+    /// Create `path`, conventionally ending in `.ndjson.zst`.
+    pub fn new(path: &Path) -> Result<Self, Error> {
+        let encoder = zstd::stream::write::Encoder::new(create(path)?, 0).map_err(ValidationError::Io)?;
+        Ok(Self(encoder))
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl TraceSink for ZstdSink {
+    fn write_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        self.0.write_all(line)
+    }
+
+    fn flush_lines(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.0.do_finish()?;
+        self.0.get_mut().flush()
+    }
+}
+
+fn create(path: &Path) -> Result<std::io::BufWriter<std::fs::File>, Error> {
+    let file = std::fs::File::create(path).map_err(ValidationError::Io)?;
+    Ok(std::io::BufWriter::new(file))
+}
+
+/// Decompress a gzip or zstd trace, recognized by its magic bytes. Other
+/// input is returned as is.
+pub(crate) fn decompress(bytes: Vec<u8>) -> Result<Vec<u8>, Error> {
+    match bytes.as_slice() {
+        [0x1f, 0x8b, ..] => gunzip(&bytes),
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => unzstd(&bytes),
+        _ => Ok(bytes),
+    }
+}
+
+#[cfg(feature = "gzip")]
+fn gunzip(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    std::io::Read::read_to_end(&mut flate2::read::MultiGzDecoder::new(bytes), &mut out).map_err(ValidationError::Io)?;
+    Ok(out)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gunzip(_: &[u8]) -> Result<Vec<u8>, Error> {
+    Err(ValidationError::CompressedTrace("gzip").into())
+}
+
+#[cfg(feature = "zstd")]
+fn unzstd(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    Ok(zstd::stream::decode_all(bytes).map_err(ValidationError::Io)?)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn unzstd(_: &[u8]) -> Result<Vec<u8>, Error> {
+    Err(ValidationError::CompressedTrace("zstd").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotating_sink_splits_at_line_boundaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.ndjson");
+        let mut sink = RotatingSink::new(&path, 10).unwrap();
+        for line in ["{\"a\":1}\n", "{\"a\":2}\n", "{\"abcdefghij\":3}\n"] {
+            sink.write_line(line.as_bytes()).unwrap();
+        }
+        sink.finish().unwrap();
+
+        let read = |i| std::fs::read_to_string(RotatingSink::segment_path(&path, i)).unwrap();
+        assert_eq!(read(0), "{\"a\":1}\n");
+        assert_eq!(read(1), "{\"a\":2}\n");
+        assert_eq!(read(2), "{\"abcdefghij\":3}\n");
+        assert_eq!(RotatingSink::segment_path(&path, 2), dir.path().join("trace.2.ndjson"));
+        assert_eq!(RotatingSink::segments(&path).len(), 3);
+
+        let mut sink = RotatingSink::new(&path, 10).unwrap();
+        sink.write_line(b"{\"a\":4}\n").unwrap();
+        sink.finish().unwrap();
+        assert_eq!(RotatingSink::segments(&path), std::slice::from_ref(&path));
+        assert_eq!(read(0), "{\"a\":4}\n");
+    }
+
+    #[test]
+    fn writer_sink_writes_lines() {
+        let mut sink = WriterSink::new(Vec::new());
+        sink.write_line(b"{\"a\":1}\n").unwrap();
+        sink.finish().unwrap();
+        assert_eq!(sink.into_inner(), b"{\"a\":1}\n");
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_sink_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.ndjson.zst");
+        let mut sink = ZstdSink::new(&path).unwrap();
+        sink.write_line(b"{\"a\":1}\n").unwrap();
+        sink.finish().unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(decompress(bytes).unwrap(), b"{\"a\":1}\n");
+    }
+}
@@ -0,0 +1,240 @@
+//! TraceSpec generation from a base spec.
+//!
+//! Trace validation runs Apalache on a TraceSpec that steps the base spec
+//! through the recorded `TraceLog`. [`TraceSpecGenerator`] writes that
+//! module from the base spec's `Init`/`Next` and a mapping from spec
+//! variables to NDJSON fields, so no hand-written TLA+ is needed.
+//!
+//! The generated module `EXTENDS` the base spec and `TraceData`, adds a
+//! trace index variable, and defines `TraceConstInit`, `TraceInit`,
+//! `TraceNext` and `TraceFinished` to match [`TraceValidatorConfig`]'s
+//! defaults. Each step takes a `Next` transition and requires every mapped
+//! variable to equal the corresponding field of the next trace row. Where a
+//! field is a partial observation, missing from some rows, the variable is
+//! left unconstrained in rows that lack it.
+//!
+//! # Example
+//!
+//! ```
+//! use tla_connect::TraceSpecGenerator;
+//!
+//! let generator = TraceSpecGenerator::builder()
+//!     .spec("specs/Counter.tla")
+//!     .vars(vec!["count".to_string()])
+//!     .build()?;
+//!
+//! let module = generator.generate()?;
+//! assert!(module.contains("count' = TraceLog[tlaConnectTraceIndex + 1].count"));
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use super::TraceValidatorConfig;
+use crate::error::{Error, ValidationError};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+/// Variable holding the index of the current `TraceLog` row.
+const INDEX_VAR: &str = "tlaConnectTraceIndex";
+
+/// Generator for a TraceSpec that replays `TraceLog` against a base spec.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TraceSpecGenerator {
+    /// Path to the base TLA+ spec. Its module name is its file stem.
+    pub spec: PathBuf,
+
+    /// Name of the generated module (default: "TlaConnectTrace").
+    pub module: String,
+
+    /// Name of the base spec's Init predicate (default: "Init").
+    pub init: String,
+
+    /// Name of the base spec's Next relation (default: "Next").
+    pub next: String,
+
+    /// Top-level actions of `next` (optional). When set, `TraceNext` takes
+    /// one of these instead of `next`, and a rejected row reports them.
+    pub actions: Vec<String>,
+
+    /// Constant initialization predicate of the base spec (optional).
+    /// If None, `TraceConstInit` is `TRUE`.
+    pub cinit: Option<String>,
+
+    /// Spec variables checked against each trace row. Variables not listed
+    /// are left unconstrained.
+    pub vars: Vec<String>,
+
+    /// NDJSON field for a spec variable, where it differs from the
+    /// variable's name.
+    pub fields: BTreeMap<String, String>,
+}
+
+impl Default for TraceSpecGenerator {
+    fn default() -> Self {
+        Self {
+            spec: PathBuf::new(),
+            module: "TlaConnectTrace".into(),
+            init: "Init".into(),
+            next: "Next".into(),
+            actions: Vec::new(),
+            cinit: None,
+            vars: Vec::new(),
+            fields: BTreeMap::new(),
+        }
+    }
+}
+
+crate::builder::impl_builder!(TraceSpecGenerator, TraceSpecGeneratorBuilder {
+    required { spec: PathBuf }
+    optional { module: String, init: String, next: String, actions: Vec<String>,
+               vars: Vec<String>, fields: BTreeMap<String, String> }
+    optional_or { cinit: String }
+});
+
+impl TraceSpecGenerator {
+    /// Render the TraceSpec module.
+    pub fn generate(&self) -> Result<String, Error> {
+        self.generate_partial(&BTreeSet::new())
+    }
+
+    /// Render the TraceSpec module for a trace where `partial_fields` are
+    /// missing from some rows. Variables mapped to these fields are only
+    /// constrained where they were observed, using `TraceObserved`.
+    pub fn generate_partial(&self, partial_fields: &BTreeSet<String>) -> Result<String, Error> {
+        let base = self
+            .spec
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| ValidationError::TraceSpecGen(format!("no module name in {}", self.spec.display())))?;
+
+        for name in [base, &self.module, &self.init, &self.next]
+            .into_iter()
+            .chain(self.cinit.as_deref())
+            .chain(self.actions.iter().map(String::as_str))
+            .chain(self.vars.iter().map(String::as_str))
+            .chain(self.fields.values().map(String::as_str))
+        {
+            if !is_identifier(name) {
+                return Err(ValidationError::TraceSpecGen(format!("'{name}' is not a TLA+ identifier")).into());
+            }
+        }
+        if let Some(var) = self.fields.keys().find(|var| !self.vars.contains(var)) {
+            return Err(ValidationError::TraceSpecGen(format!("field mapping for unlisted variable '{var}'")).into());
+        }
+
+        let matches = |row: &str, primed: &str| -> String {
+            self.vars
+                .iter()
+                .map(|var| {
+                    let field = self.fields.get(var).unwrap_or(var);
+                    if partial_fields.contains(field) {
+                        format!("    /\\ TraceObserved({var}{primed}, TraceLog[{row}].{field})\n")
+                    } else {
+                        format!("    /\\ {var}{primed} = TraceLog[{row}].{field}\n")
+                    }
+                })
+                .collect()
+        };
+
+        let mut out = format!("---- MODULE {} ----\n", self.module);
+        out.push_str(&format!("EXTENDS Integers, Sequences, TraceData, {base}\n\n"));
+        out.push_str(&format!("VARIABLE\n    \\* @type: Int;\n    {INDEX_VAR}\n\n"));
+        out.push_str(&format!(
+            "TraceConstInit == {}\n\n",
+            self.cinit.as_deref().unwrap_or("TRUE")
+        ));
+        out.push_str(&format!(
+            "TraceInit ==\n    /\\ {INDEX_VAR} = 1\n    /\\ {}\n{}\n",
+            self.init,
+            matches("1", "")
+        ));
+        let next = if self.actions.is_empty() {
+            self.next.clone()
+        } else {
+            // Aligned under the first disjunct, after "    /\ ".
+            let disjuncts: Vec<String> = self.actions.iter().map(|a| format!("\\/ {a}")).collect();
+            disjuncts.join("\n       ")
+        };
+        out.push_str(&format!(
+            "TraceNext ==\n    /\\ {INDEX_VAR} < Len(TraceLog)\n    /\\ {next}\n    /\\ {INDEX_VAR}' = {INDEX_VAR} + 1\n{}\n",
+            matches(&format!("{INDEX_VAR} + 1"), "'")
+        ));
+        out.push_str("\\* Inverted invariant: violated once every trace row has been matched.\n");
+        out.push_str(&format!("TraceFinished == {INDEX_VAR} < Len(TraceLog)\n"));
+        out.push_str("====\n");
+        Ok(out)
+    }
+}
+
+impl From<TraceSpecGenerator> for TraceValidatorConfig {
+    /// Validate against the generated TraceSpec, staged next to the base spec.
+    fn from(generator: TraceSpecGenerator) -> Self {
+        Self {
+            trace_spec: generator.spec.clone(),
+            generated: Some(generator),
+            ..Default::default()
+        }
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name.chars().any(|c| c.is_ascii_alphabetic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_maps_fields() {
+        let generator = TraceSpecGenerator::builder()
+            .spec("specs/Counter.tla")
+            .vars(vec!["count".to_string(), "mode".to_string()])
+            .fields(BTreeMap::from([("count".to_string(), "counter".to_string())]))
+            .cinit("ConstInit")
+            .actions(vec!["Inc".to_string(), "Reset".to_string()])
+            .build()
+            .unwrap();
+
+        let module = generator.generate().unwrap();
+        assert!(module.starts_with("---- MODULE TlaConnectTrace ----\nEXTENDS Integers, Sequences, TraceData, Counter\n"));
+        assert!(module.contains("TraceConstInit == ConstInit\n"));
+        assert!(module.contains("    /\\ \\/ Inc\n       \\/ Reset\n"));
+        assert!(module.contains("    /\\ count = TraceLog[1].counter\n"));
+        assert!(module.contains("    /\\ mode' = TraceLog[tlaConnectTraceIndex + 1].mode\n"));
+        assert!(module.contains("TraceFinished == tlaConnectTraceIndex < Len(TraceLog)\n"));
+    }
+
+    #[test]
+    fn generate_partial_constrains_observed_fields_only() {
+        let generator = TraceSpecGenerator::builder()
+            .spec("specs/Counter.tla")
+            .vars(vec!["count".to_string(), "mode".to_string()])
+            .build()
+            .unwrap();
+
+        let module = generator.generate_partial(&BTreeSet::from(["mode".to_string()])).unwrap();
+        assert!(module.contains("    /\\ count = TraceLog[1].count\n"));
+        assert!(module.contains("    /\\ TraceObserved(mode, TraceLog[1].mode)\n"));
+        assert!(module.contains("    /\\ TraceObserved(mode', TraceLog[tlaConnectTraceIndex + 1].mode)\n"));
+    }
+
+    #[test]
+    fn generate_rejects_bad_mapping() {
+        let generator = TraceSpecGenerator::builder()
+            .spec("specs/Counter.tla")
+            .fields(BTreeMap::from([("count".to_string(), "counter".to_string())]))
+            .build()
+            .unwrap();
+        assert!(generator.generate().is_err());
+
+        let generator = TraceSpecGenerator::builder()
+            .spec("specs/Counter.tla")
+            .vars(vec!["count + 1".to_string()])
+            .build()
+            .unwrap();
+        assert!(generator.generate().is_err());
+    }
+}
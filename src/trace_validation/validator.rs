@@ -3,11 +3,21 @@
 //! Validates that a recorded NDJSON trace is a valid behavior of a TLA+
 //! specification by running Apalache on a TraceSpec.
 
-use crate::error::{Error, ValidationError};
+use crate::apalache::{parse_outcome, ApalacheOutcome};
+use crate::error::{ApalacheError, Error, ValidationError};
+use crate::runner::{ApalacheRunner, ApalacheVersion};
+use crate::tla::escape_tla_string;
+use super::schema::SnowcatType;
+use super::TraceSpecGenerator;
 use std::collections::{BTreeMap, BTreeSet};
 use std::path::{Path, PathBuf};
 use tracing::{debug, info};
 
+/// Oldest Apalache release trace validation supports: `check` with
+/// `--cinit` and `--length`, and the `Variants` module for partial
+/// observations. [`ApalacheRunner::min_version`] can only raise it.
+pub const MIN_VALIDATION_VERSION: ApalacheVersion = ApalacheVersion::new(0, 40, 0);
+
 /// Result of trace validation.
 #[derive(Debug)]
 #[non_exhaustive]
@@ -19,19 +29,160 @@ pub enum TraceResult {
     Valid,
 
     /// The trace is NOT a valid behavior of the specification.
+    #[non_exhaustive]
     Invalid {
         /// Human-readable reason for the failure.
         reason: String,
+
+        /// The first trace row the spec rejects. Only set when
+        /// [`TraceValidatorConfig::localize`] is enabled.
+        failure: Option<InvalidStep>,
     },
 }
 
+/// The first row of a rejected trace that the spec cannot reproduce.
+///
+/// Found by validating successively shorter or longer prefixes of the
+/// trace: every prefix ending before this row is valid.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct InvalidStep {
+    /// Line number of the row in the NDJSON file (1-based).
+    pub line: usize,
+
+    /// Index of the row among the trace's rows (0-based).
+    pub index: usize,
+
+    /// The row's `action` field.
+    pub action: String,
+
+    /// The preceding row without its `action` field, or None when the
+    /// initial row is rejected.
+    pub previous_state: Option<serde_json::Value>,
+
+    /// The TraceSpec predicate that rejected the row, as passed to
+    /// Apalache: [`init`](TraceValidatorConfig::init) for the first row,
+    /// otherwise [`next`](TraceValidatorConfig::next).
+    pub predicate: String,
+
+    /// Spec actions the rejected row was checked against: the
+    /// [`actions`](super::TraceSpecGenerator::actions) listed for a
+    /// generated TraceSpec. Empty for the first row and when no actions
+    /// are listed, as the disjuncts of a Next relation are not parsed.
+    pub candidate_actions: Vec<String>,
+}
+
+/// An NDJSON trace to validate.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum TraceInput<'a> {
+    /// A trace file, plain or gzip- or zstd-compressed (decompressing
+    /// requires the `gzip` or `zstd` feature).
+    File(&'a Path),
+
+    /// NDJSON in memory, e.g. the contents of a
+    /// [`MemorySink`](super::MemorySink).
+    Memory(&'a [u8]),
+
+    /// Trace files validated as one trace, in order, e.g. the
+    /// [`segments`](super::RotatingSink::segments) of a rotated trace. Each
+    /// may be compressed.
+    Segments(&'a [PathBuf]),
+}
+
+impl<'a> From<&'a Path> for TraceInput<'a> {
+    fn from(path: &'a Path) -> Self {
+        Self::File(path)
+    }
+}
+
+impl<'a> From<&'a PathBuf> for TraceInput<'a> {
+    fn from(path: &'a PathBuf) -> Self {
+        Self::File(path)
+    }
+}
+
+impl<'a> From<&'a [PathBuf]> for TraceInput<'a> {
+    fn from(paths: &'a [PathBuf]) -> Self {
+        Self::Segments(paths)
+    }
+}
+
+impl<'a> From<&'a Vec<PathBuf>> for TraceInput<'a> {
+    fn from(paths: &'a Vec<PathBuf>) -> Self {
+        Self::Segments(paths)
+    }
+}
+
+impl<'a> From<&'a [u8]> for TraceInput<'a> {
+    fn from(bytes: &'a [u8]) -> Self {
+        Self::Memory(bytes)
+    }
+}
+
+impl<'a> From<&'a Vec<u8>> for TraceInput<'a> {
+    fn from(bytes: &'a Vec<u8>) -> Self {
+        Self::Memory(bytes)
+    }
+}
+
+/// A trace as a plain NDJSON file, for staging and localization.
+struct StagedTrace {
+    path: PathBuf,
+    /// Holds the decompressed or in-memory trace's file.
+    _dir: Option<tempfile::TempDir>,
+}
+
+impl TraceInput<'_> {
+    fn stage(self) -> Result<StagedTrace, Error> {
+        let bytes = match self {
+            Self::File(path) => {
+                let not_found = |_| ValidationError::TraceFileNotFound(path.to_path_buf());
+                let mut magic = [0u8; 4];
+                let mut file = std::fs::File::open(path).map_err(not_found)?;
+                let n = std::io::Read::read(&mut file, &mut magic).map_err(ValidationError::Io)?;
+                if !matches!(magic[..n], [0x1f, 0x8b, ..] | [0x28, 0xb5, 0x2f, 0xfd]) {
+                    return Ok(StagedTrace {
+                        path: path.to_path_buf(),
+                        _dir: None,
+                    });
+                }
+                std::fs::read(path).map_err(ValidationError::Io)?
+            }
+            Self::Memory(bytes) => bytes.to_vec(),
+            Self::Segments(paths) => {
+                let mut bytes = Vec::new();
+                for path in paths {
+                    let segment = std::fs::read(path).map_err(|_| ValidationError::TraceFileNotFound(path.clone()))?;
+                    bytes.extend(super::sink::decompress(segment)?);
+                }
+                bytes
+            }
+        };
+
+        let dir = tempfile::Builder::new()
+            .prefix("tla_trace_input_")
+            .tempdir()
+            .map_err(|e| ValidationError::WorkDir(e.to_string()))?;
+        let path = dir.path().join("trace.ndjson");
+        std::fs::write(&path, super::sink::decompress(bytes)?).map_err(ValidationError::Io)?;
+        Ok(StagedTrace { path, _dir: Some(dir) })
+    }
+}
+
 /// Configuration for Apalache-based trace validation.
 #[derive(Debug, Clone)]
 #[non_exhaustive]
 pub struct TraceValidatorConfig {
-    /// Path to the TLA+ TraceSpec file.
+    /// Path to the TLA+ TraceSpec file, or to the base spec when
+    /// `generated` is set.
     pub trace_spec: PathBuf,
 
+    /// Generate the TraceSpec instead of reading it (optional). The
+    /// generated module is staged next to the generator's base spec, which
+    /// takes the place of `trace_spec`.
+    pub generated: Option<TraceSpecGenerator>,
+
     /// INIT predicate name in the TraceSpec (default: "TraceInit").
     pub init: String,
 
@@ -46,30 +197,65 @@ pub struct TraceValidatorConfig {
     pub cinit: String,
 
     /// Path to the Apalache binary (default: "apalache-mc").
+    /// Ignored when `runner` is set.
     pub apalache_bin: String,
 
+    /// How to launch Apalache (optional). If None, `apalache_bin` is run
+    /// directly.
+    pub runner: Option<ApalacheRunner>,
+
     /// Timeout for the Apalache subprocess. If None, no timeout is applied.
     pub timeout: Option<std::time::Duration>,
+
+    /// Parse and type-check the TraceSpec, including the generated
+    /// `TraceData.tla`, before validating (default: false).
+    pub preflight: bool,
+
+    /// Locate the first rejected row of an invalid trace (default: false).
+    /// Binary search over trace prefixes adds about `log2(rows)` Apalache
+    /// runs, and the result is reported in [`TraceResult::Invalid`].
+    pub localize: bool,
+
+    /// Snowcat record type of the trace rows (optional), e.g.
+    /// `{count: Int, ids: Set(Int)}` or one built with
+    /// [`TlaType`](super::TlaType). An `action: Str` field is added if
+    /// absent. If None, the type is inferred from all rows.
+    pub schema: Option<String>,
+
+    /// Accept rows where top-level fields are `null` or missing
+    /// (default: false). Such fields are encoded as `Some`/`None` variants
+    /// and must be constrained with `TraceObserved`; see
+    /// [Partial observations](super#partial-observations). When false,
+    /// rows with differing keys are rejected with `InconsistentSchema`.
+    pub partial_observations: bool,
 }
 
 impl Default for TraceValidatorConfig {
     fn default() -> Self {
         Self {
             trace_spec: PathBuf::new(),
+            generated: None,
             init: "TraceInit".into(),
             next: "TraceNext".into(),
             inv: "TraceFinished".into(),
             cinit: "TraceConstInit".into(),
             apalache_bin: "apalache-mc".into(),
+            runner: None,
             timeout: None,
+            preflight: false,
+            localize: false,
+            schema: None,
+            partial_observations: false,
         }
     }
 }
 
 crate::builder::impl_builder!(TraceValidatorConfig, TraceValidatorConfigBuilder {
     required { trace_spec: PathBuf }
-    optional { init: String, next: String, inv: String, cinit: String, apalache_bin: String }
-    optional_or { timeout: std::time::Duration }
+    optional { init: String, next: String, inv: String, cinit: String, apalache_bin: String,
+               preflight: bool, localize: bool, partial_observations: bool }
+    optional_or { timeout: std::time::Duration, runner: ApalacheRunner, generated: TraceSpecGenerator,
+                  schema: String }
 });
 
 /// Validates Rust execution traces against TLA+ specs using Apalache.
@@ -77,12 +263,119 @@ crate::builder::impl_builder!(TraceValidatorConfig, TraceValidatorConfigBuilder
 /// Uses the "inverted invariant" technique: the TraceSpec defines a
 /// `TraceFinished` invariant that is violated when the entire trace has
 /// been consumed. If Apalache reports a violation, the trace is valid.
+///
+/// The trace is a file path or in-memory NDJSON; see [`TraceInput`].
 #[must_use = "validation result should be checked"]
-pub fn validate_trace(config: &TraceValidatorConfig, trace_file: &Path) -> Result<TraceResult, Error> {
-    let trace_spec = config
-        .trace_spec
+pub fn validate_trace<'a>(config: &TraceValidatorConfig, trace: impl Into<TraceInput<'a>>) -> Result<TraceResult, Error> {
+    let staged = trace.into().stage()?;
+    let trace_file = staged.path.as_path();
+    let result = validate_once(config, trace_file, None)?;
+    let TraceResult::Invalid { reason, .. } = result else {
+        return Ok(result);
+    };
+    if !config.localize {
+        return Ok(TraceResult::Invalid { reason, failure: None });
+    }
+
+    let rows = read_rows(trace_file)?;
+    let row_type = trace_row_type(config, trace_file)?;
+    let mut bisect = Bisect::new(rows.len());
+    while let Some(len) = bisect.probe() {
+        let prefix = write_prefix(&rows, len)?;
+        let valid = matches!(validate_once(config, &prefix.path, Some(&row_type))?, TraceResult::Valid);
+        bisect.record(valid);
+    }
+    Ok(localized(config, reason, &rows, bisect.valid_len))
+}
+
+fn validate_once(
+    config: &TraceValidatorConfig,
+    trace_file: &Path,
+    prefix_of: Option<&SnowcatType>,
+) -> Result<TraceResult, Error> {
+    let (mut cmd, run) = prepare_validation(config, trace_file, prefix_of)?;
+    let output = crate::util::run_with_timeout(&mut cmd, config.timeout)
+        .map_err(ValidationError::from)?;
+    finish_validation(&run, &output)
+}
+
+/// Async variant of [`validate_trace`] based on `tokio::process`.
+///
+/// Apalache's stdout and stderr are read concurrently while it runs.
+/// Dropping the returned future cancels validation and kills Apalache's
+/// process group, as does hitting `config.timeout`. Staging the TraceSpec,
+/// version detection and the optional pre-flight check run on tokio's
+/// blocking thread pool.
+#[cfg(feature = "async")]
+pub async fn validate_trace_async<'a>(
+    config: &TraceValidatorConfig,
+    trace: impl Into<TraceInput<'a>>,
+) -> Result<TraceResult, Error> {
+    let staged = trace.into().stage()?;
+    let trace_file = staged.path.as_path();
+    let result = validate_once_async(config, trace_file, None).await?;
+    let TraceResult::Invalid { reason, .. } = result else {
+        return Ok(result);
+    };
+    if !config.localize {
+        return Ok(TraceResult::Invalid { reason, failure: None });
+    }
+
+    let rows = read_rows(trace_file)?;
+    let row_type = trace_row_type(config, trace_file)?;
+    let mut bisect = Bisect::new(rows.len());
+    while let Some(len) = bisect.probe() {
+        let prefix = write_prefix(&rows, len)?;
+        let valid = matches!(
+            validate_once_async(config, &prefix.path, Some(&row_type)).await?,
+            TraceResult::Valid
+        );
+        bisect.record(valid);
+    }
+    Ok(localized(config, reason, &rows, bisect.valid_len))
+}
+
+#[cfg(feature = "async")]
+async fn validate_once_async(
+    config: &TraceValidatorConfig,
+    trace_file: &Path,
+    prefix_of: Option<&SnowcatType>,
+) -> Result<TraceResult, Error> {
+    let (prepare_config, prepare_trace) = (config.clone(), trace_file.to_path_buf());
+    let prefix_of = prefix_of.cloned();
+    let (cmd, run) = crate::util::spawn_blocking(move || {
+        prepare_validation(&prepare_config, &prepare_trace, prefix_of.as_ref())
+    })
+    .await?;
+    let output = crate::util::run_with_timeout_async(cmd, config.timeout)
+        .await
+        .map_err(ValidationError::from)?;
+    finish_validation(&run, &output)
+}
+
+/// State of an Apalache `check` of a staged TraceSpec needed after it exits.
+struct PreparedValidation {
+    /// Directory of the user's TraceSpec, for resolving diagnostics.
+    spec_dir: PathBuf,
+    /// Staged specs and Apalache output; must outlive the Apalache process.
+    _work_dir: tempfile::TempDir,
+}
+
+/// Stage `trace_file` and the TraceSpec and build the Apalache command.
+///
+/// `prefix_of` is set for the prefixes validated while localizing a
+/// failure: the row type of the full trace, so every prefix is encoded as
+/// in the full run. Prefixes skip the pre-flight check, which already ran
+/// for the full trace.
+fn prepare_validation(
+    config: &TraceValidatorConfig,
+    trace_file: &Path,
+    prefix_of: Option<&SnowcatType>,
+) -> Result<(std::process::Command, PreparedValidation), Error> {
+    let trace_spec = config.generated.as_ref().map_or(&config.trace_spec, |g| &g.spec);
+    let trace_spec = trace_spec
         .canonicalize()
-        .map_err(|_| ValidationError::TraceSpecNotFound(config.trace_spec.clone()))?;
+        .map_err(|_| ValidationError::TraceSpecNotFound(trace_spec.clone()))?;
 
     let trace_file = trace_file
         .canonicalize()
@@ -92,9 +385,13 @@ pub fn validate_trace(config: &TraceValidatorConfig, trace_file: &Path) -> Resul
         .parent()
         .ok_or_else(|| ValidationError::TraceSpecNotFound(trace_spec.clone()))?;
 
-    let spec_filename = trace_spec
-        .file_name()
-        .ok_or_else(|| ValidationError::TraceSpecNotFound(trace_spec.clone()))?;
+    let spec_filename = match config.generated {
+        Some(ref generator) => std::ffi::OsString::from(format!("{}.tla", generator.module)),
+        None => trace_spec
+            .file_name()
+            .ok_or_else(|| ValidationError::TraceSpecNotFound(trace_spec.clone()))?
+            .to_os_string(),
+    };
 
     info!(
         spec = %trace_spec.display(),
@@ -102,7 +399,14 @@ pub fn validate_trace(config: &TraceValidatorConfig, trace_file: &Path) -> Resul
         "Validating trace with Apalache"
     );
 
-    let (trace_data, trace_len) = ndjson_to_tla_module(&trace_file)?;
+    let TraceData {
+        module: trace_data,
+        len: trace_len,
+        partial,
+    } = match prefix_of {
+        Some(row_type) => trace_data_with_type(&trace_file, row_type)?,
+        None => trace_data(&trace_file, config.schema.as_deref(), config.partial_observations)?,
+    };
 
     let work_dir = tempfile::Builder::new()
         .prefix("tla_trace_")
@@ -113,30 +417,45 @@ pub fn validate_trace(config: &TraceValidatorConfig, trace_file: &Path) -> Resul
     std::fs::create_dir_all(&spec_subdir).map_err(ValidationError::Io)?;
     std::fs::create_dir_all(&out_subdir).map_err(ValidationError::Io)?;
 
-    for entry in std::fs::read_dir(spec_dir).map_err(ValidationError::Io)? {
-        let entry = entry.map_err(ValidationError::Io)?;
-        let path = entry.path();
-        if path.extension().and_then(|e| e.to_str()) == Some("tla") {
-            let dest = spec_subdir.join(entry.file_name());
-            std::fs::copy(&path, &dest).map_err(|e| ValidationError::FileCopy {
-                path: path.clone(),
-                reason: e.to_string(),
-            })?;
-        }
-    }
+    crate::util::copy_tla_files(spec_dir, &spec_subdir).map_err(|(path, e)| ValidationError::FileCopy {
+        path,
+        reason: e.to_string(),
+    })?;
 
     let trace_data_path = spec_subdir.join("TraceData.tla");
     std::fs::write(&trace_data_path, &trace_data).map_err(ValidationError::Io)?;
 
+    if let Some(ref generator) = config.generated {
+        std::fs::write(spec_subdir.join(&spec_filename), generator.generate_partial(&partial)?)
+            .map_err(ValidationError::Io)?;
+        debug!(module = %generator.module, "Generated TraceSpec");
+    }
+
     debug!(
         "Generated TraceData.tla ({} bytes, {} trace entries)",
         trace_data.len(),
         trace_len
     );
 
+    let runner = crate::runner::resolve(&config.runner, &config.apalache_bin);
+    runner
+        .ensure_at_least(Some(MIN_VALIDATION_VERSION))
+        .map_err(ValidationError::from)?;
+    let mut cmd = runner.command();
+
+    if config.preflight && prefix_of.is_none() {
+        crate::apalache::run_spec_check(
+            &runner,
+            &spec_subdir.join(&spec_filename),
+            spec_dir,
+            true,
+            config.timeout,
+        )
+        .map_err(ValidationError::from)?;
+    }
+
     let length = trace_len.saturating_sub(1);
 
-    let mut cmd = std::process::Command::new(&config.apalache_bin);
     cmd.arg("check")
         .arg(format!("--init={}", config.init))
         .arg(format!("--next={}", config.next))
@@ -144,13 +463,20 @@ pub fn validate_trace(config: &TraceValidatorConfig, trace_file: &Path) -> Resul
         .arg(format!("--cinit={}", config.cinit))
         .arg(format!("--length={length}"))
         .arg(format!("--out-dir={}", out_subdir.display()))
-        .arg(spec_subdir.join(spec_filename));
+        .arg(spec_subdir.join(&spec_filename));
 
     debug!("Apalache command: {:?}", cmd);
 
-    let output = crate::util::run_with_timeout(&mut cmd, config.timeout)
-        .map_err(ValidationError::from)?;
+    Ok((
+        cmd,
+        PreparedValidation {
+            spec_dir: spec_dir.to_path_buf(),
+            _work_dir: work_dir,
+        },
+    ))
+}
 
+fn finish_validation(run: &PreparedValidation, output: &std::process::Output) -> Result<TraceResult, Error> {
     let stdout = String::from_utf8_lossy(&output.stdout);
     let stderr = String::from_utf8_lossy(&output.stderr);
 
@@ -159,50 +485,227 @@ pub fn validate_trace(config: &TraceValidatorConfig, trace_file: &Path) -> Resul
         debug!("Apalache stderr:\n{}", stderr);
     }
 
-    parse_apalache_output(&stdout, &stderr, output.status.code())
+    parse_apalache_output(&stdout, &stderr, output.status.code(), &run.spec_dir)
 }
 
 fn parse_apalache_output(
     stdout: &str,
     stderr: &str,
     exit_code: Option<i32>,
+    spec_dir: &Path,
 ) -> Result<TraceResult, Error> {
-    match exit_code {
-        Some(12) => {
+    match parse_outcome(stdout, stderr, exit_code) {
+        ApalacheOutcome::InvariantViolated => {
             info!("Trace validated successfully (Apalache violated TraceFinished)");
             Ok(TraceResult::Valid)
         }
 
-        Some(0) => Ok(TraceResult::Invalid {
+        ApalacheOutcome::NoError => Ok(TraceResult::Invalid {
             reason: "Apalache completed without violating TraceFinished – \
                      the trace could not be fully replayed against the spec"
                 .to_string(),
+            failure: None,
+        }),
+
+        ApalacheOutcome::Deadlock => Ok(TraceResult::Invalid {
+            reason: "TraceNext deadlocked before TraceFinished was violated – \
+                     the trace could not be fully replayed against the spec"
+                .to_string(),
+            failure: None,
         }),
 
-        _ => {
+        ApalacheOutcome::Unknown { exit_code } => {
             let error_lines: Vec<&str> = stdout
                 .lines()
                 .filter(|l| l.contains("Error") || l.contains("error"))
                 .chain(stderr.lines().filter(|l| !l.is_empty()))
                 .collect();
 
-            Err(ValidationError::from(crate::error::ApalacheError::Execution {
+            Err(ValidationError::from(ApalacheError::Execution {
                 exit_code,
                 message: error_lines.join("\n"),
             })
             .into())
         }
+
+        outcome => Err(ValidationError::from(ApalacheError::Failed {
+            outcome: outcome.with_spec_dir(spec_dir),
+        })
+        .into()),
     }
 }
 
+/// Binary search for the longest valid prefix of a trace whose full
+/// length is known to be invalid.
+struct Bisect {
+    /// Longest prefix length known to be valid (the empty prefix trivially is).
+    valid_len: usize,
+    /// Shortest prefix length known to be invalid.
+    invalid_len: usize,
+    probing: usize,
+}
+
+impl Bisect {
+    fn new(rows: usize) -> Self {
+        Self {
+            valid_len: 0,
+            invalid_len: rows,
+            probing: 0,
+        }
+    }
+
+    /// The next prefix length to validate, or None once the search is done.
+    fn probe(&mut self) -> Option<usize> {
+        (self.invalid_len - self.valid_len > 1).then(|| {
+            self.probing = self.valid_len + (self.invalid_len - self.valid_len) / 2;
+            self.probing
+        })
+    }
+
+    fn record(&mut self, valid: bool) {
+        debug!(len = self.probing, valid, "Validated trace prefix");
+        if valid {
+            self.valid_len = self.probing;
+        } else {
+            self.invalid_len = self.probing;
+        }
+    }
+}
+
+/// A trace prefix written to a temporary NDJSON file.
+struct PrefixFile {
+    path: PathBuf,
+    _dir: tempfile::TempDir,
+}
+
+/// Non-empty rows of an NDJSON trace with their 1-based line numbers.
+pub(super) fn read_rows(trace_file: &Path) -> Result<Vec<(usize, serde_json::Value)>, Error> {
+    let content = std::fs::read_to_string(trace_file).map_err(ValidationError::Io)?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .map(|row| (i + 1, row))
+                .map_err(|e| {
+                    ValidationError::InvalidJson {
+                        line: i + 1,
+                        reason: e.to_string(),
+                    }
+                    .into()
+                })
+        })
+        .collect()
+}
+
+fn write_prefix(rows: &[(usize, serde_json::Value)], len: usize) -> Result<PrefixFile, Error> {
+    let dir = tempfile::Builder::new()
+        .prefix("tla_trace_prefix_")
+        .tempdir()
+        .map_err(|e| ValidationError::WorkDir(e.to_string()))?;
+    let path = dir.path().join("prefix.ndjson");
+    let mut content = String::new();
+    for (_, row) in &rows[..len] {
+        content.push_str(&row.to_string());
+        content.push('\n');
+    }
+    std::fs::write(&path, content).map_err(ValidationError::Io)?;
+    Ok(PrefixFile { path, _dir: dir })
+}
+
+/// Build the `Invalid` result for a trace whose first `valid_len` rows are valid.
+fn localized(
+    config: &TraceValidatorConfig,
+    reason: String,
+    rows: &[(usize, serde_json::Value)],
+    valid_len: usize,
+) -> TraceResult {
+    let (line, row) = &rows[valid_len];
+    let action = row
+        .get("action")
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown")
+        .to_string();
+    let previous_state = valid_len.checked_sub(1).map(|i| {
+        let mut state = rows[i].1.clone();
+        if let Some(obj) = state.as_object_mut() {
+            obj.remove("action");
+        }
+        state
+    });
+    let (predicate, candidate_actions) = match (&config.generated, valid_len) {
+        (_, 0) => (config.init.clone(), Vec::new()),
+        (Some(generator), _) => (config.next.clone(), generator.actions.clone()),
+        (None, _) => (config.next.clone(), Vec::new()),
+    };
+
+    info!(line, action = %action, "Located first rejected trace row");
+    TraceResult::Invalid {
+        reason: format!("{reason} (first rejected row: line {line}, action '{action}')"),
+        failure: Some(InvalidStep {
+            line: *line,
+            index: valid_len,
+            action,
+            previous_state,
+            predicate,
+            candidate_actions,
+        }),
+    }
+}
+
+/// The generated `TraceData` module for an NDJSON trace.
+pub(crate) struct TraceData {
+    pub(crate) module: String,
+    pub(crate) len: usize,
+    /// Fields missing from some rows, encoded as `Some`/`None` variants.
+    pub(crate) partial: BTreeSet<String>,
+}
+
 /// Convert an NDJSON trace file to a TLA+ module defining `TraceLog`.
 #[doc(hidden)]
 pub fn ndjson_to_tla_module(trace_file: &Path) -> Result<(String, usize), Error> {
+    let data = trace_data(trace_file, None, false)?;
+    Ok((data.module, data.len))
+}
+
+/// Convert an NDJSON trace file to a `TraceData` module.
+///
+/// The rows' type is `schema` if given, otherwise the type merged from all
+/// rows (see the [`schema`](super::schema) module), where top-level fields
+/// `null` or missing in some rows are partial observations only if
+/// `partial_observations` is set. A field of type
+/// `Some(T) | None(UNIT)` is a partial observation: every row records it as
+/// `Variant("Some", value)` or `Variant("None", UNIT)`, and the module
+/// defines `TraceObserved` to constrain a variable only where the field was
+/// observed.
+pub(crate) fn trace_data(trace_file: &Path, schema: Option<&str>, partial_observations: bool) -> Result<TraceData, Error> {
+    let (json_objects, lines) = parse_trace(trace_file)?;
+    let row_type = row_type(&json_objects, &lines, schema, partial_observations)?;
+    render_trace_data(&json_objects, &lines, &row_type)
+}
+
+/// Convert an NDJSON trace file to a `TraceData` module whose rows have
+/// type `row_type`, such as the type of a longer trace it is a prefix of.
+fn trace_data_with_type(trace_file: &Path, row_type: &SnowcatType) -> Result<TraceData, Error> {
+    let (json_objects, lines) = parse_trace(trace_file)?;
+    render_trace_data(&json_objects, &lines, row_type)
+}
+
+/// The row type the validation of `trace_file` encodes its rows with.
+fn trace_row_type(config: &TraceValidatorConfig, trace_file: &Path) -> Result<SnowcatType, Error> {
+    let (json_objects, lines) = parse_trace(trace_file)?;
+    row_type(&json_objects, &lines, config.schema.as_deref(), config.partial_observations)
+}
+
+type JsonObject = serde_json::Map<String, serde_json::Value>;
+
+/// The non-empty rows of an NDJSON trace file and their 1-based line numbers.
+fn parse_trace(trace_file: &Path) -> Result<(Vec<JsonObject>, Vec<usize>), Error> {
     let content = std::fs::read_to_string(trace_file).map_err(ValidationError::Io)?;
 
     let mut json_objects = Vec::new();
-    let mut records = Vec::new();
-    let mut expected_keys: Option<BTreeSet<String>> = None;
+    let mut lines = Vec::new();
 
     for (i, line) in content.lines().enumerate() {
         let line = line.trim();
@@ -219,37 +722,59 @@ pub fn ndjson_to_tla_module(trace_file: &Path) -> Result<(String, usize), Error>
             }
         })?;
 
-        let obj_map = obj.as_object().ok_or_else(|| ValidationError::NonObjectState {
-            found: format!("line {line_num}: {}", obj),
-        })?;
-
-        let current_keys: BTreeSet<String> = obj_map.keys().cloned().collect();
-
-        if let Some(ref expected) = expected_keys {
-            if &current_keys != expected {
-                return Err(ValidationError::InconsistentSchema {
-                    line: line_num,
-                    expected: expected.iter().cloned().collect(),
-                    found: current_keys.into_iter().collect(),
-                }
-                .into());
+        let serde_json::Value::Object(obj) = obj else {
+            return Err(ValidationError::NonObjectState {
+                found: format!("line {line_num}: {}", obj),
             }
-        } else {
-            expected_keys = Some(current_keys);
-        }
+            .into());
+        };
 
         validate_json_types(&obj, line_num)?;
-
-        let record = json_obj_to_tla_record(&obj, line_num)?;
         json_objects.push(obj);
-        records.push(record);
+        lines.push(line_num);
     }
 
-    if records.is_empty() {
+    if json_objects.is_empty() {
         return Err(ValidationError::EmptyTrace(trace_file.to_path_buf()).into());
     }
 
-    let record_type = infer_snowcat_record_type(&json_objects[0])?;
+    Ok((json_objects, lines))
+}
+
+/// `schema` if given, otherwise the type merged from all rows.
+fn row_type(
+    json_objects: &[JsonObject],
+    lines: &[usize],
+    schema: Option<&str>,
+    partial_observations: bool,
+) -> Result<SnowcatType, Error> {
+    match schema {
+        Some(schema) => {
+            let mut ty = SnowcatType::parse(schema)?;
+            let SnowcatType::Record(ref mut fields) = ty else {
+                return Err(ValidationError::InvalidSchema(format!("expected a record type, got {ty}")).into());
+            };
+            fields.entry("action".to_string()).or_insert(SnowcatType::Str);
+            Ok(ty)
+        }
+        None => json_objects
+            .iter()
+            .zip(lines)
+            .try_fold(SnowcatType::Unknown, |ty, (obj, &line)| {
+                ty.merge_row(SnowcatType::infer_row(obj, line)?, line, partial_observations)
+            }),
+    }
+}
+
+/// Render parsed trace rows as a `TraceData` module with rows of type `row_type`.
+fn render_trace_data(json_objects: &[JsonObject], lines: &[usize], row_type: &SnowcatType) -> Result<TraceData, Error> {
+    let partial = row_type.optional_fields();
+
+    let records = json_objects
+        .iter()
+        .zip(lines)
+        .map(|(obj, &line)| row_type.render_row(obj, line))
+        .collect::<Result<Vec<_>, _>>()?;
 
     let actions: Vec<String> = json_objects
         .iter()
@@ -261,12 +786,20 @@ pub fn ndjson_to_tla_module(trace_file: &Path) -> Result<(String, usize), Error>
         })
         .collect();
 
+    let mut modules = BTreeSet::new();
+    row_type.required_modules(&mut modules);
+
     let count = records.len();
     let mut out = String::new();
     out.push_str("---- MODULE TraceData ----\n");
-    out.push_str("EXTENDS Integers, Sequences\n\n");
+    out.push_str("EXTENDS Integers, Sequences");
+    for module in &modules {
+        out.push_str(", ");
+        out.push_str(module);
+    }
+    out.push_str("\n\n");
 
-    out.push_str(&format!("\\* @type: () => Seq({record_type});\n"));
+    out.push_str(&format!("\\* @type: () => Seq({row_type});\n"));
     out.push_str("TraceLog == <<\n");
     for (i, record) in records.iter().enumerate() {
         if i > 0 {
@@ -285,16 +818,23 @@ pub fn ndjson_to_tla_module(trace_file: &Path) -> Result<(String, usize), Error>
         }
         out.push_str(&format!("  \"{}\"", escape_tla_string(action)));
     }
-    out.push_str("\n>>\n\n====\n");
-    Ok((out, count))
+    out.push_str("\n>>\n\n");
+
+    if !partial.is_empty() {
+        out.push_str("\\* Holds when `obs` was not observed or was observed as `value`.\n");
+        out.push_str("\\* @type: (a, Some(a) | None(UNIT)) => Bool;\n");
+        out.push_str("TraceObserved(value, obs) == VariantGetOrElse(\"Some\", obs, value) = value\n\n");
+    }
+    out.push_str("====\n");
+    Ok(TraceData {
+        module: out,
+        len: count,
+        partial,
+    })
 }
 
 /// Validate JSON types are supported (reject floats, nested structures).
-fn validate_json_types(value: &serde_json::Value, line: usize) -> Result<(), Error> {
-    let obj = value.as_object().ok_or_else(|| ValidationError::NonObjectState {
-        found: format!("{value}"),
-    })?;
-
+fn validate_json_types(obj: &serde_json::Map<String, serde_json::Value>, line: usize) -> Result<(), Error> {
     for (key, val) in obj {
         validate_json_value(val, line, key)?;
     }
@@ -320,6 +860,11 @@ fn validate_json_value(value: &serde_json::Value, line: usize, field: &str) -> R
             }
         }
         serde_json::Value::Object(obj) => {
+            itf_encoding(obj).map_err(|reason| ValidationError::UnsupportedType {
+                line,
+                field: field.to_string(),
+                reason,
+            })?;
             for (key, val) in obj {
                 validate_json_value(val, line, &format!("{field}.{key}"))?;
             }
@@ -329,53 +874,62 @@ fn validate_json_value(value: &serde_json::Value, line: usize, field: &str) -> R
     Ok(())
 }
 
-fn infer_snowcat_record_type(value: &serde_json::Value) -> Result<String, Error> {
-    let obj = value.as_object().ok_or_else(|| ValidationError::NonObjectState {
-        found: format!("{value}"),
-    })?;
+/// An ITF encoding (`#set`, `#map`, `#tup`, `#bigint` or a `tag`/`value`
+/// variant) of a value in an NDJSON row.
+pub(super) enum ItfEncoded<'a> {
+    Set(&'a [serde_json::Value]),
+    /// `[key, value]` pairs.
+    Map(Vec<(&'a serde_json::Value, &'a serde_json::Value)>),
+    Tuple(&'a [serde_json::Value]),
+    BigInt(&'a str),
+    /// Tag and value; a missing value or an empty tuple is `UNIT`.
+    Variant(&'a str, Option<&'a serde_json::Value>),
+}
 
-    let sorted: BTreeMap<_, _> = obj.iter().collect();
-    let mut fields = Vec::new();
-    for (key, val) in &sorted {
-        let ty = infer_snowcat_type(val, key)?;
-        fields.push(format!("{key}: {ty}"));
+/// Recognize an ITF-encoded value. Returns None for plain records and an
+/// error for malformed encodings.
+pub(super) fn itf_encoding(obj: &serde_json::Map<String, serde_json::Value>) -> Result<Option<ItfEncoded<'_>>, String> {
+    fn array<'a>(key: &str, val: &'a serde_json::Value) -> Result<&'a [serde_json::Value], String> {
+        val.as_array()
+            .map(Vec::as_slice)
+            .ok_or_else(|| format!("{key} must hold an array, got: {val}"))
     }
 
-    Ok(format!("{{{}}}", fields.join(", ")))
-}
-
-fn infer_snowcat_type(value: &serde_json::Value, field: &str) -> Result<String, Error> {
-    match value {
-        serde_json::Value::Bool(_) => Ok("Bool".to_string()),
-        serde_json::Value::Number(_) => Ok("Int".to_string()),
-        serde_json::Value::String(_) => Ok("Str".to_string()),
-        serde_json::Value::Array(arr) => {
-            if arr.is_empty() {
-                return Ok("Seq(Int)".to_string());
+    if obj.len() == 1 {
+        let (key, val) = obj.iter().next().expect("one entry");
+        match key.as_str() {
+            "#set" => return array(key, val).map(|elems| Some(ItfEncoded::Set(elems))),
+            "#tup" => return array(key, val).map(|elems| Some(ItfEncoded::Tuple(elems))),
+            "#map" => {
+                let pairs = array(key, val)?
+                    .iter()
+                    .map(|pair| match pair.as_array().map(Vec::as_slice) {
+                        Some([k, v]) => Ok((k, v)),
+                        _ => Err(format!("#map entries must be [key, value] pairs, got: {pair}")),
+                    })
+                    .collect::<Result<_, _>>()?;
+                return Ok(Some(ItfEncoded::Map(pairs)));
             }
-            let first_type = infer_snowcat_type(&arr[0], field)?;
-            for (i, elem) in arr.iter().enumerate().skip(1) {
-                let elem_type = infer_snowcat_type(elem, field)?;
-                if elem_type != first_type {
-                    return Err(ValidationError::InconsistentArrayType {
-                        field: format!("{field}[{i}]"),
-                        expected: first_type,
-                        found: elem_type,
-                    }
-                    .into());
+            "#bigint" => {
+                return match val.as_str() {
+                    Some(n) if n.parse::<i128>().is_ok() || n.parse::<u128>().is_ok() => Ok(Some(ItfEncoded::BigInt(n))),
+                    _ => Err(format!("#bigint must hold a decimal integer string, got: {val}")),
                 }
             }
-            Ok(format!("Seq({})", first_type))
+            "#unserializable" => return Err(format!("unserializable value: {val}")),
+            _ => {}
         }
-        serde_json::Value::Object(obj) => {
-            let sorted: BTreeMap<_, _> = obj.iter().collect();
-            let fields: Result<Vec<String>, Error> = sorted
-                .iter()
-                .map(|(k, v)| Ok(format!("{k}: {}", infer_snowcat_type(v, &format!("{field}.{k}"))?)))
-                .collect();
-            Ok(format!("{{{}}}", fields?.join(", ")))
+    }
+
+    let is_variant = obj.keys().all(|k| k == "tag" || k == "value");
+    match obj.get("tag").and_then(|t| t.as_str()) {
+        Some(tag) if is_variant => {
+            let value = obj
+                .get("value")
+                .filter(|v| v.get("#tup").and_then(|t| t.as_array()).is_none_or(|t| !t.is_empty()));
+            Ok(Some(ItfEncoded::Variant(tag, value)))
         }
-        serde_json::Value::Null => Ok("Str".to_string()),
+        _ => Ok(None),
     }
 }
 
@@ -396,7 +950,7 @@ fn json_obj_to_tla_record(value: &serde_json::Value, line: usize) -> Result<Stri
     Ok(format!("[{}]", fields.join(", ")))
 }
 
-fn json_to_tla_value(value: &serde_json::Value, line: usize, field: &str) -> Result<String, Error> {
+pub(super) fn json_to_tla_value(value: &serde_json::Value, line: usize, field: &str) -> Result<String, Error> {
     match value {
         serde_json::Value::Null => Ok("\"null\"".to_string()),
         serde_json::Value::Bool(b) => Ok(if *b { "TRUE" } else { "FALSE" }.to_string()),
@@ -423,8 +977,46 @@ fn json_to_tla_value(value: &serde_json::Value, line: usize, field: &str) -> Res
                 .collect();
             Ok(format!("<<{}>>", elems?.join(", ")))
         }
-        serde_json::Value::Object(_) => {
-            json_obj_to_tla_record(value, line)
+        serde_json::Value::Object(obj) => {
+            let conversion = |reason| ValidationError::UnsupportedType {
+                line,
+                field: field.to_string(),
+                reason,
+            };
+            let elems = |elems: &[serde_json::Value]| -> Result<Vec<String>, Error> {
+                elems
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| json_to_tla_value(v, line, &format!("{field}[{i}]")))
+                    .collect()
+            };
+            match itf_encoding(obj).map_err(conversion)? {
+                Some(ItfEncoded::Set(set)) => Ok(format!("{{{}}}", elems(set)?.join(", "))),
+                Some(ItfEncoded::Tuple(tuple)) => Ok(format!("<<{}>>", elems(tuple)?.join(", "))),
+                Some(ItfEncoded::Map(pairs)) if pairs.is_empty() => Ok("[x \\in {} |-> x]".to_string()),
+                Some(ItfEncoded::Map(pairs)) => {
+                    let pairs: Result<Vec<String>, Error> = pairs
+                        .iter()
+                        .map(|(k, v)| {
+                            Ok(format!(
+                                "{} :> {}",
+                                json_to_tla_value(k, line, field)?,
+                                json_to_tla_value(v, line, field)?
+                            ))
+                        })
+                        .collect();
+                    Ok(format!("({})", pairs?.join(" @@ ")))
+                }
+                Some(ItfEncoded::BigInt(n)) => Ok(n.to_string()),
+                Some(ItfEncoded::Variant(tag, value)) => {
+                    let value = match value {
+                        Some(v) => json_to_tla_value(v, line, &format!("{field}.value"))?,
+                        None => "UNIT".to_string(),
+                    };
+                    Ok(format!("Variant(\"{}\", {value})", escape_tla_string(tag)))
+                }
+                None => json_obj_to_tla_record(value, line),
+            }
         }
     }
 }
@@ -447,25 +1039,6 @@ impl From<&str> for TraceValidatorConfig {
     }
 }
 
-/// Escape a string for use in a TLA+ string literal.
-fn escape_tla_string(s: &str) -> String {
-    let mut out = String::with_capacity(s.len());
-    for c in s.chars() {
-        match c {
-            '\\' => out.push_str("\\\\"),
-            '"' => out.push_str("\\\""),
-            '\n' => out.push_str("\\n"),
-            '\r' => out.push_str("\\r"),
-            '\t' => out.push_str("\\t"),
-            c if c.is_control() => {
-                out.push_str(&format!("\\u{:04x}", c as u32));
-            }
-            c => out.push(c),
-        }
-    }
-    out
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -522,27 +1095,35 @@ mod tests {
 
     #[test]
     fn json_to_tla_value_float_rejected() {
-        assert!(json_to_tla_value(&json!(3.14), 1, "f").is_err());
+        assert!(json_to_tla_value(&json!(2.5), 1, "f").is_err());
     }
 
     #[test]
     fn validate_json_types_nested_float() {
         // Float nested in array of arrays should be rejected
-        let val = json!({"data": [[3.14]]});
-        assert!(validate_json_types(&val, 1).is_err());
+        let val = json!({"data": [[2.5]]});
+        assert!(validate_json_types(val.as_object().unwrap(), 1).is_err());
     }
 
     #[test]
     fn validate_json_types_nested_object_float() {
         // Float nested in object should be rejected
-        let val = json!({"outer": {"inner": 3.14}});
-        assert!(validate_json_types(&val, 1).is_err());
+        let val = json!({"outer": {"inner": 2.5}});
+        assert!(validate_json_types(val.as_object().unwrap(), 1).is_err());
     }
 
     #[test]
     fn validate_json_types_valid() {
         let val = json!({"a": 1, "b": "str", "c": true, "d": [1, 2]});
-        assert!(validate_json_types(&val, 1).is_ok());
+        assert!(validate_json_types(val.as_object().unwrap(), 1).is_ok());
+    }
+
+    fn infer_snowcat_type(value: &serde_json::Value, field: &str) -> Result<String, Error> {
+        let row = serde_json::Map::from_iter([(field.to_string(), value.clone())]);
+        match SnowcatType::infer_row(&row, 1)? {
+            SnowcatType::Record(fields) => Ok(fields[field].to_string()),
+            ty => panic!("row type {ty}"),
+        }
     }
 
     #[test]
@@ -565,6 +1146,46 @@ mod tests {
         assert!(result.is_err());
     }
 
+    /// Writes `rows` as an NDJSON trace and returns its `TraceLog` type.
+    fn merged_row_type(rows: &[serde_json::Value]) -> Result<String, Error> {
+        let dir = tempfile::tempdir().unwrap();
+        let trace = dir.path().join("trace.ndjson");
+        let ndjson: String = rows.iter().map(|row| format!("{row}\n")).collect();
+        std::fs::write(&trace, ndjson).unwrap();
+        let module = trace_data(&trace, None, false)?.module;
+        let ty = module.lines().find_map(|l| l.strip_prefix("\\* @type: () => ")).unwrap();
+        Ok(ty.trim_end_matches(';').to_string())
+    }
+
+    #[test]
+    fn merged_inference_resolves_types_from_later_rows() {
+        let ty = merged_row_type(&[
+            json!({"log": [], "ids": {"#set": []}, "mode": {"tag": "Idle"}}),
+            json!({"log": ["a"], "ids": {"#set": ["n1"]}, "mode": {"tag": "Busy", "value": 1}}),
+        ])
+        .unwrap();
+        assert_eq!(ty, "Seq({ids: Set(Str), log: Seq(Str), mode: Busy(Int) | Idle(UNIT)})");
+    }
+
+    #[test]
+    fn merged_inference_keeps_defaults_where_no_row_tells() {
+        let ty = merged_row_type(&[json!({"log": [], "last": null}), json!({"log": [], "last": "x"})]).unwrap();
+        assert_eq!(ty, "Seq({last: Str, log: Seq(Int)})");
+    }
+
+    #[test]
+    fn merged_inference_rejects_conflicts_across_rows() {
+        let err = merged_row_type(&[json!({"log": [1]}), json!({"log": [], "x": 0}), json!({"log": ["a"]})])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Inconsistent record schema: line 2"), "got: {err}");
+
+        let err = merged_row_type(&[json!({"log": [1]}), json!({"log": []}), json!({"log": ["a"]})])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("line 3, field 'log': expected Int, found Str"), "got: {err}");
+    }
+
     #[test]
     fn json_obj_to_tla_record_sorted() {
         let val = json!({"z": 1, "a": 2});
@@ -573,6 +1194,77 @@ mod tests {
         assert!(record.starts_with("[a |->"));
     }
 
+    #[test]
+    fn bisect_finds_longest_valid_prefix() {
+        for (rows, valid_len) in [(1, 0), (2, 1), (10, 0), (10, 7), (2000, 1234)] {
+            let mut bisect = Bisect::new(rows);
+            let mut probes = 0;
+            while let Some(len) = bisect.probe() {
+                bisect.record(len <= valid_len);
+                probes += 1;
+            }
+            assert_eq!(bisect.valid_len, valid_len);
+            assert!(probes <= 11);
+        }
+    }
+
+    #[test]
+    fn localized_reports_predicate_and_listed_actions() {
+        let rows = vec![(1, json!({"action": "init", "x": 0})), (2, json!({"action": "inc", "x": 2}))];
+        let generator = TraceSpecGenerator::builder()
+            .spec("specs/Counter.tla")
+            .actions(vec!["Inc".to_string(), "Reset".to_string()])
+            .build()
+            .unwrap();
+        let config = TraceValidatorConfig::builder()
+            .trace_spec("Trace.tla")
+            .generated(generator)
+            .build()
+            .unwrap();
+
+        let TraceResult::Invalid { failure: Some(first), .. } = localized(&config, String::new(), &rows, 0) else {
+            panic!("expected a localized failure");
+        };
+        assert_eq!(first.predicate, "TraceInit");
+        assert!(first.candidate_actions.is_empty());
+
+        let TraceResult::Invalid { failure: Some(step), .. } = localized(&config, String::new(), &rows, 1) else {
+            panic!("expected a localized failure");
+        };
+        assert_eq!((step.line, step.action.as_str()), (2, "inc"));
+        assert_eq!(step.predicate, "TraceNext");
+        assert_eq!(step.candidate_actions, ["Inc", "Reset"]);
+        assert_eq!(step.previous_state, Some(json!({"x": 0})));
+    }
+
+    #[test]
+    fn trace_data_encodes_partial_observations() {
+        let dir = tempfile::tempdir().unwrap();
+        let trace = dir.path().join("partial.ndjson");
+        std::fs::write(
+            &trace,
+            "{\"action\": \"init\", \"counter\": 0, \"mode\": \"idle\", \"leader\": null}\n\
+             {\"action\": \"increment\", \"counter\": 1, \"leader\": 2}\n",
+        )
+        .unwrap();
+
+        assert!(trace_data(&trace, None, false).is_err());
+        let data = trace_data(&trace, None, true).unwrap();
+        assert_eq!(data.len, 2);
+        assert_eq!(data.partial, BTreeSet::from(["leader".to_string(), "mode".to_string()]));
+        assert!(data.module.contains("EXTENDS Integers, Sequences, Variants"));
+        assert!(data
+            .module
+            .contains("Seq({action: Str, counter: Int, leader: Some(Int) | None(UNIT), mode: Some(Str) | None(UNIT)})"));
+        assert!(data.module.contains(
+            r#"[action |-> "init", counter |-> 0, leader |-> Variant("None", UNIT), mode |-> Variant("Some", "idle")]"#
+        ));
+        assert!(data.module.contains(
+            r#"[action |-> "increment", counter |-> 1, leader |-> Variant("Some", 2), mode |-> Variant("None", UNIT)]"#
+        ));
+        assert!(data.module.contains("TraceObserved(value, obs) =="));
+    }
+
     #[test]
     fn builder_missing_required_field() {
         let result = TraceValidatorConfig::builder().build();
@@ -3,9 +3,10 @@
 /// Run a subprocess command with an optional timeout.
 ///
 /// If `timeout` is `Some`, spawns the process and polls `try_wait` in a loop,
-/// killing the child if it exceeds the timeout. If `timeout` is `None`, uses
-/// the standard blocking `output()` call.
-#[cfg(any(feature = "trace-gen", feature = "trace-validation"))]
+/// killing the child if it exceeds the timeout. Stdout and stderr are read
+/// on separate threads meanwhile, so a chatty child cannot block on a full
+/// pipe. If `timeout` is `None`, uses the standard blocking `output()` call.
+#[cfg(any(feature = "trace-gen", feature = "trace-validation", feature = "rpc"))]
 pub fn run_with_timeout(
     cmd: &mut std::process::Command,
     timeout: Option<std::time::Duration>,
@@ -25,23 +26,29 @@ pub fn run_with_timeout(
         .spawn()
         .map_err(|e| ApalacheError::NotFound(e.to_string()))?;
 
+    let drain = |pipe: Option<Box<dyn std::io::Read + Send>>| {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            buf
+        })
+    };
+    let stdout = drain(child.stdout.take().map(|p| Box::new(p) as _));
+    let stderr = drain(child.stderr.take().map(|p| Box::new(p) as _));
+
     let start = std::time::Instant::now();
     let poll_interval = std::time::Duration::from_millis(100);
 
     loop {
         match child.try_wait() {
             Ok(Some(status)) => {
-                let stdout = child.stdout.take().map_or_else(Vec::new, |mut s| {
-                    let mut buf = Vec::new();
-                    let _ = std::io::Read::read_to_end(&mut s, &mut buf);
-                    buf
-                });
-                let stderr = child.stderr.take().map_or_else(Vec::new, |mut s| {
-                    let mut buf = Vec::new();
-                    let _ = std::io::Read::read_to_end(&mut s, &mut buf);
-                    buf
+                return Ok(std::process::Output {
+                    status,
+                    stdout: stdout.join().unwrap_or_default(),
+                    stderr: stderr.join().unwrap_or_default(),
                 });
-                return Ok(std::process::Output { status, stdout, stderr });
             }
             Ok(None) => {
                 if start.elapsed() >= timeout {
@@ -57,3 +64,116 @@ pub fn run_with_timeout(
         }
     }
 }
+
+/// Run blocking work such as staging specs or querying the Apalache version
+/// on tokio's blocking thread pool, propagating panics.
+#[cfg(all(feature = "async", any(feature = "trace-gen", feature = "trace-validation")))]
+pub(crate) async fn spawn_blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    tokio::task::spawn_blocking(f)
+        .await
+        .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+}
+
+/// Async counterpart of [`run_with_timeout`] based on `tokio::process`.
+///
+/// Stdout and stderr are read concurrently with waiting for the child. The
+/// child runs in its own process group (on Unix), which is killed on
+/// timeout and when the returned future is dropped before completion.
+#[cfg(all(feature = "async", any(feature = "trace-gen", feature = "trace-validation")))]
+pub async fn run_with_timeout_async(
+    cmd: std::process::Command,
+    timeout: Option<std::time::Duration>,
+) -> Result<std::process::Output, crate::error::ApalacheError> {
+    use crate::error::ApalacheError;
+    use tokio::io::AsyncReadExt;
+
+    let mut cmd = tokio::process::Command::from(cmd);
+    cmd.stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true);
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    let mut child = cmd.spawn().map_err(|e| ApalacheError::NotFound(e.to_string()))?;
+    let mut group = ProcessGroupGuard(child.id());
+
+    let mut stdout_pipe = child.stdout.take();
+    let mut stderr_pipe = child.stderr.take();
+    let run = async {
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let read_stdout = async {
+            if let Some(ref mut pipe) = stdout_pipe {
+                let _ = pipe.read_to_end(&mut stdout).await;
+            }
+        };
+        let read_stderr = async {
+            if let Some(ref mut pipe) = stderr_pipe {
+                let _ = pipe.read_to_end(&mut stderr).await;
+            }
+        };
+        let (status, (), ()) = tokio::join!(child.wait(), read_stdout, read_stderr);
+        status.map(|status| std::process::Output { status, stdout, stderr })
+    };
+
+    let result = match timeout {
+        Some(duration) => match tokio::time::timeout(duration, run).await {
+            Ok(result) => result,
+            // Dropping `group` kills the whole process group.
+            Err(_) => return Err(ApalacheError::Timeout { duration }),
+        },
+        None => run.await,
+    };
+
+    group.0 = None;
+    result.map_err(|e| ApalacheError::NotFound(e.to_string()))
+}
+
+/// Kills a child's process group on drop, unless disarmed by clearing the
+/// pid. Apalache's launcher scripts may leave the JVM as a grandchild, which
+/// killing only the direct child would orphan. The child must have been
+/// spawned with `process_group(0)`.
+#[cfg(any(feature = "trace-gen", all(feature = "async", feature = "trace-validation")))]
+pub(crate) struct ProcessGroupGuard(pub(crate) Option<u32>);
+
+#[cfg(any(feature = "trace-gen", all(feature = "async", feature = "trace-validation")))]
+impl ProcessGroupGuard {
+    /// Kill the process group now and disarm the guard.
+    pub(crate) fn kill(&mut self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.0.take().and_then(|pid| i32::try_from(pid).ok()) {
+            // SAFETY: `kill` has no memory-safety preconditions; a negative
+            // pid addresses the process group created with `process_group(0)`.
+            unsafe {
+                libc::kill(-pid, libc::SIGKILL);
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "trace-gen", all(feature = "async", feature = "trace-validation")))]
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// Copy every `.tla` file from `src_dir` into `dest_dir` (non-recursive).
+///
+/// On failure, returns the path that could not be read or copied along
+/// with the underlying IO error.
+#[cfg(any(feature = "trace-gen", feature = "trace-validation"))]
+pub fn copy_tla_files(
+    src_dir: &std::path::Path,
+    dest_dir: &std::path::Path,
+) -> Result<(), (std::path::PathBuf, std::io::Error)> {
+    let entries = std::fs::read_dir(src_dir).map_err(|e| (src_dir.to_path_buf(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| (src_dir.to_path_buf(), e))?;
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) == Some("tla") {
+            std::fs::copy(&path, dest_dir.join(entry.file_name())).map_err(|e| (path.clone(), e))?;
+        }
+    }
+    Ok(())
+}
@@ -70,7 +70,7 @@ fn test_validate_trace_with_real_apalache() {
 
     match result {
         TraceResult::Valid => {}
-        TraceResult::Invalid { reason } => {
+        TraceResult::Invalid { reason, .. } => {
             panic!("expected valid trace, got invalid: {reason}");
         }
         _ => {
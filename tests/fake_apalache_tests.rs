@@ -0,0 +1,841 @@
+//! Tests against a stub `apalache-mc` script.
+//!
+//! The stub mimics the parts of the Apalache CLI that tla-connect relies on,
+//! so trace generation can be exercised without a real Apalache install.
+
+#![cfg(unix)]
+
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use tla_connect::*;
+
+/// Write an executable stub that answers `version` and, for any other
+/// command, writes one ITF trace into `--out-dir` and exits with 12.
+/// Every non-version invocation appends a line to `calls.log`.
+fn fake_apalache(dir: &Path) -> PathBuf {
+    fake_apalache_with(dir, "exit 12")
+}
+
+/// Like [`fake_apalache`], but runs `tail` after writing the trace.
+fn fake_apalache_with(dir: &Path, tail: &str) -> PathBuf {
+    let bin = dir.join("apalache-mc");
+    let log = dir.join("calls.log");
+    let script = format!(
+        r##"#!/bin/sh
+if [ "$1" = "version" ]; then
+  echo version >> "{versions}"
+  echo "0.44.0"
+  exit 0
+fi
+echo "$@" >> "{log}"
+for arg in "$@"; do
+  case "$arg" in
+    --out-dir=*) out="${{arg#--out-dir=}}" ;;
+  esac
+done
+mkdir -p "$out/run"
+cat > "$out/run/example0.itf.json" <<'EOF'
+{{"#meta": {{}}, "vars": ["x"], "states": [{{"#meta": {{"index": 0}}, "x": {{"#bigint": "0"}}}}]}}
+EOF
+{tail}
+"##,
+        log = log.display(),
+        versions = dir.join("versions.log").display()
+    );
+    write_stub(&bin, &script);
+    bin
+}
+
+/// Write `script` to `path` and make it executable.
+fn write_stub(path: &Path, script: &str) {
+    std::fs::write(path, script).unwrap();
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// Stub tail that sets `$spec` to the module passed to Apalache (the last
+/// argument), copies `src` to `dest` and reports a valid trace.
+fn capture_tail(src: &str, dest: &Path) -> String {
+    format!(
+        "for spec; do :; done\ncp \"{src}\" \"{}\"\necho 'The outcome is: Error'\nexit 12",
+        dest.display()
+    )
+}
+
+/// Stub tail capturing the staged `TraceData.tla` at `dest`.
+fn capture_trace_data(dest: &Path) -> String {
+    capture_tail("$(dirname \"$spec\")/TraceData.tla", dest)
+}
+
+fn spec(dir: &Path) -> PathBuf {
+    let spec_dir = dir.join("spec");
+    std::fs::create_dir_all(&spec_dir).unwrap();
+    let spec = spec_dir.join("Counter.tla");
+    std::fs::write(&spec, "---- MODULE Counter ----\n====\n").unwrap();
+    spec
+}
+
+/// Arguments of every stub invocation, one per line.
+fn call_log(dir: &Path) -> String {
+    std::fs::read_to_string(dir.join("calls.log")).unwrap_or_default()
+}
+
+fn calls(dir: &Path) -> usize {
+    call_log(dir).lines().count()
+}
+
+/// A config generating traces for the stub spec with the stub at `bin`.
+fn stub_config(dir: &Path, bin: &Path) -> ApalacheConfigBuilder {
+    ApalacheConfig::builder()
+        .spec(spec(dir))
+        .apalache_bin(bin.to_str().unwrap())
+}
+
+#[test]
+fn test_trace_cache_skips_apalache_on_hit() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = fake_apalache(dir.path());
+    let config = stub_config(dir.path(), &bin)
+        .cache(TraceCache::new(dir.path().join("cache")))
+        .build()
+        .unwrap();
+
+    let first = generate_traces(&config).unwrap();
+    assert!(!first.cached);
+    assert_eq!(first.traces.len(), 1);
+    assert_eq!(calls(dir.path()), 1);
+
+    let version_checks = || {
+        std::fs::read_to_string(dir.path().join("versions.log"))
+            .unwrap_or_default()
+            .lines()
+            .count()
+    };
+    let second = generate_traces(&config).unwrap();
+    assert!(second.cached);
+    assert_eq!(second.traces, first.traces);
+    assert_eq!(calls(dir.path()), 1, "cache hit must not start Apalache");
+    assert_eq!(version_checks(), 1, "version is detected once per launcher");
+
+    // Another launcher for the same release hits the entry after detecting
+    // its version.
+    let link = dir.path().join("apalache-link");
+    std::os::unix::fs::symlink(&bin, &link).unwrap();
+    let linked = stub_config(dir.path(), &link)
+        .cache(TraceCache::new(dir.path().join("cache")))
+        .build()
+        .unwrap();
+
+    let third = generate_traces(&linked).unwrap();
+    assert!(third.cached);
+    assert_eq!(calls(dir.path()), 1, "cache hit must not start Apalache");
+    assert_eq!(version_checks(), 2);
+}
+
+#[test]
+fn test_stream_traces_yields_traces_and_outcome() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = fake_apalache(dir.path());
+    let config = stub_config(dir.path(), &bin).build().unwrap();
+
+    let mut stream = stream_traces(&config).unwrap();
+    let traces: Vec<_> = stream.by_ref().collect::<Result<_, _>>().unwrap();
+    assert_eq!(traces.len(), 1);
+    assert_eq!(stream.outcome(), Some(&ApalacheOutcome::InvariantViolated));
+}
+
+#[test]
+fn test_dropping_trace_stream_stops_apalache() {
+    let dir = tempfile::tempdir().unwrap();
+    // The stub keeps running in a grandchild, as a launcher script's JVM would.
+    let pid_file = dir.path().join("sleeper.pid");
+    let tail = format!("sleep 30 &\necho $! > \"{}\"\nwait", pid_file.display());
+    let bin = fake_apalache_with(dir.path(), &tail);
+    let config = stub_config(dir.path(), &bin).build().unwrap();
+
+    let start = std::time::Instant::now();
+    let mut stream = stream_traces(&config).unwrap();
+    assert!(stream.next().unwrap().is_ok(), "trace is yielded while Apalache runs");
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    while !pid_file.exists() && std::time::Instant::now() < deadline {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    drop(stream);
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+
+    let pid = std::fs::read_to_string(&pid_file).unwrap();
+    let alive = || {
+        std::process::Command::new("kill")
+            .args(["-0", pid.trim()])
+            .status()
+            .unwrap()
+            .success()
+    };
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    while alive() && std::time::Instant::now() < deadline {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    assert!(!alive(), "grandchild survived dropping the stream");
+}
+
+#[test]
+fn test_coverage_reports_unreachable_actions() {
+    let dir = tempfile::tempdir().unwrap();
+    // Goal invariants for "Reset" are never violated.
+    let tail = r#"for spec; do :; done
+if grep -q '"Reset"' "$spec"; then rm -rf "$out"; exit 0; fi
+exit 12"#;
+    let bin = fake_apalache_with(dir.path(), tail);
+    let config = stub_config(dir.path(), &bin).build().unwrap();
+
+    let coverage = generate_coverage_traces(&config, &["Increment", "Reset"]).unwrap();
+    assert_eq!(coverage.covered.keys().collect::<Vec<_>>(), ["Increment"]);
+    assert_eq!(coverage.unreachable, ["Reset"]);
+    assert_eq!(coverage.traces().count(), 1);
+    assert!(coverage.ensure_complete().is_err());
+
+    let log = call_log(dir.path());
+    assert!(log.lines().all(|l| l.starts_with("check --inv=TlaConnectNotTaken --max-error=1 ")));
+}
+
+#[test]
+fn test_preflight_reports_type_errors_before_generation() {
+    let dir = tempfile::tempdir().unwrap();
+    let tail = r#"if [ "$1" = "typecheck" ]; then
+  echo "[Counter.tla:3:1-3:9]: Mismatch in argument types. Expected: (Int) => Bool. Found: (Str) => Bool"
+  echo "Typechecker detected type errors"
+  exit 120
+fi
+exit 12"#;
+    let bin = fake_apalache_with(dir.path(), tail);
+    let spec = spec(dir.path());
+
+    let check = SpecCheckConfig::builder()
+        .spec(spec.clone())
+        .apalache_bin(bin.to_str().unwrap())
+        .build()
+        .unwrap();
+    let err = check_spec(&check).unwrap_err();
+    let diagnostic = &err.diagnostics()[0];
+    assert_eq!(diagnostic.file, Some(spec.canonicalize().unwrap()));
+    assert_eq!((diagnostic.line, diagnostic.column), (Some(3), Some(1)));
+    assert_eq!(diagnostic.found_type.as_deref(), Some("(Str) => Bool"));
+
+    let config = ApalacheConfig::builder()
+        .spec(spec)
+        .apalache_bin(bin.to_str().unwrap())
+        .preflight(true)
+        .build()
+        .unwrap();
+    let err = generate_traces(&config).err().expect("preflight must fail");
+    assert_eq!(err.diagnostics().len(), 1);
+    assert_eq!(calls(dir.path()), 2, "generation must not start after a failed preflight");
+}
+
+#[test]
+fn test_runner_rejects_unsupported_version_before_running() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = fake_apalache(dir.path());
+    let runner = ApalacheRunner::binary(bin.to_str().unwrap()).min_version(ApalacheVersion::new(0, 50, 0));
+    let config = ApalacheConfig::builder()
+        .spec(spec(dir.path()))
+        .runner(runner.clone())
+        .build()
+        .unwrap();
+
+    assert_eq!(runner.version().unwrap(), ApalacheVersion::new(0, 44, 0));
+    let err = generate_traces(&config).err().expect("0.44.0 is below the minimum");
+    assert!(
+        matches!(
+            err,
+            Error::TraceGen(TraceGenError::Apalache(ApalacheError::UnsupportedVersion { .. }))
+        ),
+        "unexpected error: {err}"
+    );
+    assert_eq!(calls(dir.path()), 0);
+
+    #[cfg(feature = "rpc")]
+    assert!(ApalacheServer::start(&ApalacheRunner::binary(bin.to_str().unwrap()), 0).is_err());
+}
+
+#[test]
+fn test_crate_minimum_versions_apply_without_min_version() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("apalache-old");
+    let script = format!(
+        "#!/bin/sh\nif [ \"$1\" = \"version\" ]; then echo 0.30.0; exit 0; fi\necho \"$@\" >> \"{}\"\nexit 12\n",
+        dir.path().join("calls.log").display()
+    );
+    write_stub(&bin, &script);
+    assert!(tla_connect::trace_gen::MIN_GENERATION_VERSION > ApalacheVersion::new(0, 30, 0));
+    assert!(tla_connect::trace_validation::MIN_VALIDATION_VERSION > ApalacheVersion::new(0, 30, 0));
+
+    let config = stub_config(dir.path(), &bin).build().unwrap();
+    let err = generate_traces(&config).err().expect("0.30.0 is below the generation minimum");
+    assert!(
+        matches!(
+            err,
+            Error::TraceGen(TraceGenError::Apalache(ApalacheError::UnsupportedVersion { .. }))
+        ),
+        "unexpected error: {err}"
+    );
+
+    let trace = dir.path().join("trace.ndjson");
+    std::fs::write(&trace, "{\"action\": \"init\", \"x\": 0}\n").unwrap();
+    let config = TraceValidatorConfig::builder()
+        .trace_spec(spec(dir.path()))
+        .apalache_bin(bin.to_str().unwrap())
+        .build()
+        .unwrap();
+    let err = validate_trace(&config, &trace).expect_err("0.30.0 is below the validation minimum");
+    assert!(
+        matches!(
+            err,
+            Error::Validation(ValidationError::Apalache(ApalacheError::UnsupportedVersion { .. }))
+        ),
+        "unexpected error: {err}"
+    );
+    assert_eq!(calls(dir.path()), 0);
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct XState {
+    x: i64,
+}
+
+impl State for XState {}
+
+impl ExtractState<OffByOne> for XState {
+    fn from_driver(_: &OffByOne) -> Result<Self, DriverError> {
+        Ok(XState { x: 1 })
+    }
+}
+
+/// Disagrees with the stub's trace (`x = 0`) on the first state.
+struct OffByOne;
+
+impl Driver for OffByOne {
+    type State = XState;
+
+    fn step(&mut self, _: &Step) -> Result<(), DriverError> {
+        Ok(())
+    }
+}
+
+/// Reports `x` as given, to disagree with the stub's trace unless it is 0.
+struct Fixed(i64);
+
+impl Driver for Fixed {
+    type State = XState;
+
+    fn step(&mut self, _: &Step) -> Result<(), DriverError> {
+        Ok(())
+    }
+}
+
+impl ExtractState<Fixed> for XState {
+    fn from_driver(driver: &Fixed) -> Result<Self, DriverError> {
+        Ok(XState { x: driver.0 })
+    }
+}
+
+#[test]
+fn test_constant_matrix_generates_and_replays_per_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = fake_apalache(dir.path());
+    let out_dir = dir.path().join("out");
+    let config = stub_config(dir.path(), &bin).out_dir(&out_dir).build().unwrap();
+    let matrix = [1, 2].map(|n| ConstantAssignment::new().with_int("N", n));
+
+    let generated = generate_traces_matrix(&config, &matrix).unwrap();
+    assert_eq!(generated.len(), 2);
+    for (i, entry) in generated.iter().enumerate() {
+        assert_eq!(entry.constants, matrix[i]);
+        assert_eq!(entry.generated.out_dir, out_dir.join(format!("config-{i}")));
+        assert!(entry.generated.out_dir.join("run/example0.itf.json").is_file());
+    }
+    let log = call_log(dir.path());
+    assert_eq!(log.matches("--cinit=TlaConnectConstInit ").count(), 2);
+
+    let stats = replay_traces_matrix(|_| Fixed(0), &generated).unwrap();
+    assert_eq!(stats.traces_replayed, 2);
+
+    // Only the driver for N = 2 disagrees with its traces.
+    let err = replay_traces_matrix(|c| Fixed(i64::from(*c == matrix[1])), &generated).unwrap_err();
+    assert!(
+        matches!(&err, Error::Replay(ReplayError::WithConstants { constants, .. }) if constants == "N = 2"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn test_simulate_seed_is_passed_and_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = fake_apalache(dir.path());
+    let config = stub_config(dir.path(), &bin)
+        .seed(42u64)
+        .build()
+        .unwrap();
+
+    let generated = generate_traces(&config).unwrap();
+    assert_eq!(generated.seed, Some(42));
+    let log = call_log(dir.path());
+    assert!(log.contains(" --seed=42 "));
+
+    let err = replay_generated(|| OffByOne, &generated).unwrap_err();
+    assert!(err.to_string().contains("seed 42"), "seed missing from: {err}");
+
+    let mut unseeded = config.clone();
+    unseeded.seed = None;
+    let seed = generate_traces(&unseeded).unwrap().seed.expect("a random seed is recorded");
+    let log = call_log(dir.path());
+    assert!(log.contains(&format!(" --seed={seed} ")));
+}
+
+#[test]
+fn test_parallel_workers_split_traces_and_dedup() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = fake_apalache(dir.path());
+    let config = stub_config(dir.path(), &bin)
+        .max_traces(5usize)
+        .seed(100u64)
+        .build()
+        .unwrap();
+
+    let merged = generate_traces_parallel(&config, ParallelOptions::new(3)).unwrap();
+    assert_eq!(merged.traces.len(), 3);
+    assert_eq!(merged.seed, Some(100));
+    assert!(merged.out_dir.join("worker-2").is_dir());
+
+    let log = call_log(dir.path());
+    let mut runs: Vec<_> = log
+        .lines()
+        .map(|l| {
+            let arg = |name: &str| l.split(' ').find(|a| a.starts_with(name)).unwrap().to_string();
+            (arg("--seed="), arg("--max-run="))
+        })
+        .collect();
+    runs.sort();
+    assert_eq!(
+        runs,
+        [("--seed=100", "--max-run=2"), ("--seed=101", "--max-run=2"), ("--seed=102", "--max-run=1")]
+            .map(|(s, m)| (s.to_string(), m.to_string()))
+    );
+
+    let deduped = generate_traces_parallel(&config, ParallelOptions::new(3).dedup(true)).unwrap();
+    assert_eq!(deduped.traces.len(), 1, "the stub writes the same trace in every worker");
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_async_generation_uses_trace_cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = fake_apalache(dir.path());
+    let config = stub_config(dir.path(), &bin)
+        .cache(TraceCache::new(dir.path().join("cache")))
+        .build()
+        .unwrap();
+
+    let first = generate_traces_async(&config).await.unwrap();
+    assert!(!first.cached);
+    assert_eq!(calls(dir.path()), 1);
+
+    // Entries are shared with the blocking API.
+    let second = generate_traces_async(&config).await.unwrap();
+    assert!(second.cached);
+    assert_eq!(second.traces, first.traces);
+    assert!(generate_traces(&config).unwrap().cached);
+    assert_eq!(calls(dir.path()), 1, "cache hit must not start Apalache");
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_async_generation_kills_process_group_on_timeout() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = stub_config(dir.path(), &fake_apalache(dir.path())).build().unwrap();
+    let generated = generate_traces_async(&config).await.unwrap();
+    assert_eq!(generated.traces.len(), 1);
+
+    // The stub leaves a grandchild holding the output pipes open.
+    let pid_file = dir.path().join("sleeper.pid");
+    let tail = format!("sleep 30 &\necho $! > \"{}\"\nwait", pid_file.display());
+    let config = stub_config(dir.path(), &fake_apalache_with(dir.path(), &tail))
+        .timeout(std::time::Duration::from_millis(500))
+        .build()
+        .unwrap();
+
+    let start = std::time::Instant::now();
+    let Err(err) = generate_traces_async(&config).await else {
+        panic!("expected a timeout");
+    };
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+    assert!(matches!(
+        err,
+        Error::TraceGen(TraceGenError::Apalache(ApalacheError::Timeout { .. }))
+    ));
+
+    let pid = std::fs::read_to_string(&pid_file).unwrap();
+    let alive = || {
+        std::process::Command::new("kill")
+            .args(["-0", pid.trim()])
+            .status()
+            .unwrap()
+            .success()
+    };
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    while alive() && std::time::Instant::now() < deadline {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    assert!(!alive(), "grandchild survived the timeout");
+}
+
+#[test]
+fn test_best_effort_keeps_traces_written_before_timeout() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = fake_apalache_with(dir.path(), "exec sleep 30");
+    let builder = || {
+        stub_config(dir.path(), &bin).timeout(std::time::Duration::from_millis(500))
+    };
+
+    let Err(err) = generate_traces(&builder().build().unwrap()) else {
+        panic!("expected a timeout");
+    };
+    assert!(matches!(
+        err,
+        Error::TraceGen(TraceGenError::Apalache(ApalacheError::Timeout { .. }))
+    ));
+
+    let generated = generate_traces(&builder().best_effort(true).build().unwrap()).unwrap();
+    assert!(generated.partial);
+    assert_eq!(generated.traces.len(), 1);
+    assert!(generated.outcome.is_none());
+}
+
+#[test]
+fn test_tlc_backend_converts_counterexamples() {
+    let dir = tempfile::tempdir().unwrap();
+    let java = dir.path().join("java");
+    let script = format!(
+        r#"#!/bin/sh
+echo "$@" >> "{log}"
+cat TlcConnect.cfg >> "{log}"
+cat <<'EOF'
+Error: Invariant TraceComplete is violated.
+Error: The behavior up to this point is:
+State 1: <Initial predicate>
+x = 0
+
+State 2: <Inc line 5, col 1 to line 5, col 20 of module Counter>
+x = 1
+
+
+EOF
+exit 12
+"#,
+        log = dir.path().join("calls.log").display()
+    );
+    write_stub(&java, &script);
+
+    let config = TlcConfig::builder()
+        .spec(spec(dir.path()))
+        .java(java.to_str().unwrap())
+        .seed(7u64)
+        .build()
+        .unwrap();
+    let generated = TraceGenerator::generate(&config).unwrap();
+
+    assert_eq!(generated.seed, Some(7));
+    assert_eq!(generated.traces.len(), 1);
+    assert_eq!(generated.traces[0].states.len(), 2);
+    assert!(generated.out_dir.join("trace0.itf.json").is_file());
+
+    let log = call_log(dir.path());
+    assert!(log.contains("tlc2.TLC") && log.contains("-simulate num=100") && log.contains("-seed 7"));
+    assert!(log.contains("INIT Init\nNEXT Next\n\nINVARIANT TraceComplete"));
+}
+
+/// Counts `x` up on each `inc` action.
+struct Counting(i64);
+
+impl Driver for Counting {
+    type State = XState;
+
+    fn step(&mut self, step: &Step) -> Result<(), DriverError> {
+        switch!(step {
+            "init" => {
+                self.0 = 0;
+                Ok(())
+            },
+            "inc" => {
+                self.0 += 1;
+                Ok(())
+            },
+        })
+    }
+}
+
+impl ExtractState<Counting> for XState {
+    fn from_driver(driver: &Counting) -> Result<Self, DriverError> {
+        Ok(XState { x: driver.0 })
+    }
+}
+
+#[test]
+fn test_quint_backend_replays_mbt_traces() {
+    let dir = tempfile::tempdir().unwrap();
+    let quint = dir.path().join("quint");
+    let script = format!(
+        r##"#!/bin/sh
+echo "$@" >> "{log}"
+for arg in "$@"; do
+  case "$arg" in
+    --out-itf=*) out="${{arg#--out-itf=}}" ;;
+  esac
+done
+out=$(echo "$out" | sed 's/{{seq}}/0/')
+cat > "$out" <<'EOF'
+{{"#meta": {{}}, "vars": ["x"], "states": [
+  {{"#meta": {{"index": 0}}, "x": {{"#bigint": "0"}}, "mbt::actionTaken": "init", "mbt::nondetPicks": {{}}}},
+  {{"#meta": {{"index": 1}}, "x": {{"#bigint": "1"}}, "mbt::actionTaken": "inc", "mbt::nondetPicks": {{}}}}
+]}}
+EOF
+"##,
+        log = dir.path().join("calls.log").display()
+    );
+    write_stub(&quint, &script);
+    let spec = dir.path().join("counter.qnt");
+    std::fs::write(&spec, "module counter {}\n").unwrap();
+
+    let config = QuintConfig::builder()
+        .spec(&spec)
+        .quint_bin(quint.to_str().unwrap())
+        .max_samples(20usize)
+        .seed(3u64)
+        .build()
+        .unwrap();
+    let generated = config.generate().unwrap();
+    assert_eq!(generated.seed, Some(3));
+    let stats = replay_traces(|| Counting(0), &generated.traces).unwrap();
+    assert_eq!(stats.total_states, 2);
+
+    let log = call_log(dir.path());
+    assert!(log.starts_with("run --max-samples=20 "));
+    assert!(log.contains(" --mbt --seed=3 "));
+}
+
+#[test]
+fn test_validate_trace_against_generated_trace_spec() {
+    let dir = tempfile::tempdir().unwrap();
+    let staged = dir.path().join("staged.tla");
+    let bin = fake_apalache_with(dir.path(), &capture_tail("$spec", &staged));
+    let spec = spec(dir.path());
+
+    let trace = dir.path().join("trace.ndjson");
+    std::fs::write(&trace, "{\"action\": \"init\", \"counter\": 0}\n").unwrap();
+
+    let generator = TraceSpecGenerator::builder()
+        .spec(spec)
+        .vars(vec!["x".to_string()])
+        .fields(BTreeMap::from([("x".to_string(), "counter".to_string())]))
+        .build()
+        .unwrap();
+    let mut config = TraceValidatorConfig::from(generator.clone());
+    config.apalache_bin = bin.to_str().unwrap().to_string();
+
+    let result = validate_trace(&config, &trace).unwrap();
+    assert!(matches!(result, TraceResult::Valid));
+    assert_eq!(std::fs::read_to_string(&staged).unwrap(), generator.generate().unwrap());
+}
+
+#[test]
+fn test_validate_trace_with_schema() {
+    let dir = tempfile::tempdir().unwrap();
+    let staged = dir.path().join("TraceData.tla");
+    let bin = fake_apalache_with(dir.path(), &capture_trace_data(&staged));
+    let spec = spec(dir.path());
+
+    let trace = dir.path().join("trace.ndjson");
+    std::fs::write(&trace, "{\"action\": \"init\", \"ids\": [], \"owners\": {\"1\": \"a\"}}\n").unwrap();
+
+    let config = TraceValidatorConfig::builder()
+        .trace_spec(spec)
+        .apalache_bin(bin.to_str().unwrap())
+        .schema(format!(
+            "{{ids: {}, owners: {}}}",
+            HashSet::<String>::tla_type(),
+            BTreeMap::<u64, String>::tla_type()
+        ))
+        .build()
+        .unwrap();
+    let result = validate_trace(&config, &trace).unwrap();
+    assert!(matches!(result, TraceResult::Valid));
+
+    let trace_data = std::fs::read_to_string(&staged).unwrap();
+    assert!(trace_data.contains("Seq({action: Str, ids: Set(Str), owners: Int -> Str})"));
+    assert!(trace_data.contains(r#"[action |-> "init", ids |-> {}, owners |-> (1 :> "a")]"#));
+}
+
+#[test]
+fn test_validate_trace_from_memory_sink() {
+    let dir = tempfile::tempdir().unwrap();
+    let staged = dir.path().join("TraceData.tla");
+    let bin = fake_apalache_with(dir.path(), &capture_trace_data(&staged));
+
+    let sink = MemorySink::new();
+    let mut emitter = StateEmitter::with_sink(sink.clone());
+    emitter.emit("init", &serde_json::json!({"x": 0})).unwrap();
+    emitter.emit("inc", &serde_json::json!({"x": 1})).unwrap();
+    assert_eq!(emitter.finish().unwrap(), 2);
+
+    let config = TraceValidatorConfig::builder()
+        .trace_spec(spec(dir.path()))
+        .apalache_bin(bin.to_str().unwrap())
+        .build()
+        .unwrap();
+    let result = validate_trace(&config, &sink.contents()).unwrap();
+    assert!(matches!(result, TraceResult::Valid));
+    assert!(std::fs::read_to_string(&staged).unwrap().contains(r#"[action |-> "inc", x |-> 1]"#));
+}
+
+#[test]
+fn test_validate_trace_from_rotated_segments() {
+    let dir = tempfile::tempdir().unwrap();
+    let staged = dir.path().join("TraceData.tla");
+    let bin = fake_apalache_with(dir.path(), &capture_trace_data(&staged));
+
+    let trace = dir.path().join("trace.ndjson");
+    let mut emitter = StateEmitter::with_sink(RotatingSink::new(&trace, 1).unwrap());
+    for x in 0..3 {
+        emitter.emit("step", &serde_json::json!({"x": x})).unwrap();
+    }
+    emitter.finish().unwrap();
+    let segments = RotatingSink::segments(&trace);
+    assert_eq!(segments.len(), 3);
+
+    let config = TraceValidatorConfig::builder()
+        .trace_spec(spec(dir.path()))
+        .apalache_bin(bin.to_str().unwrap())
+        .build()
+        .unwrap();
+    assert!(matches!(validate_trace(&config, &segments).unwrap(), TraceResult::Valid));
+    let trace_data = std::fs::read_to_string(&staged).unwrap();
+    assert!(trace_data.contains("[action |-> \"step\", x |-> 0],\n  [action |-> \"step\", x |-> 1],\n  [action |-> \"step\", x |-> 2]"));
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn test_validate_trace_from_gzip_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = fake_apalache(dir.path());
+
+    let trace = dir.path().join("trace.ndjson.gz");
+    let mut emitter = StateEmitter::with_sink(GzipSink::new(&trace).unwrap());
+    emitter.emit("init", &serde_json::json!({"x": 0})).unwrap();
+    emitter.finish().unwrap();
+    assert_eq!(std::fs::read(&trace).unwrap()[..2], [0x1f, 0x8b]);
+
+    let config = TraceValidatorConfig::builder()
+        .trace_spec(spec(dir.path()))
+        .apalache_bin(bin.to_str().unwrap())
+        .build()
+        .unwrap();
+    assert!(matches!(validate_trace(&config, &trace).unwrap(), TraceResult::Valid));
+}
+
+#[test]
+fn test_validate_trace_localizes_first_rejected_row() {
+    let dir = tempfile::tempdir().unwrap();
+    // Accept traces of at most two rows (`--length` at most 1).
+    let tail = r#"for arg in "$@"; do
+  case "$arg" in
+    --length=0|--length=1) echo 'The outcome is: Error'; exit 12 ;;
+  esac
+done
+echo 'The outcome is: NoError'
+exit 0"#;
+    let bin = fake_apalache_with(dir.path(), tail);
+    let spec = spec(dir.path());
+
+    let trace = dir.path().join("trace.ndjson");
+    let rows: Vec<String> = ["init", "inc", "inc", "inc", "reset"]
+        .iter()
+        .enumerate()
+        .map(|(i, action)| format!("{{\"action\": \"{action}\", \"x\": {i}}}\n"))
+        .collect();
+    std::fs::write(&trace, format!("{}\n{}", rows[0], rows[1..].concat())).unwrap();
+
+    let config = TraceValidatorConfig::builder()
+        .trace_spec(spec)
+        .apalache_bin(bin.to_str().unwrap())
+        .localize(true)
+        .build()
+        .unwrap();
+    let TraceResult::Invalid { failure: Some(failure), .. } = validate_trace(&config, &trace).unwrap() else {
+        panic!("expected a localized failure");
+    };
+    assert_eq!((failure.line, failure.index), (4, 2));
+    assert_eq!(failure.action, "inc");
+    assert_eq!(failure.previous_state, Some(serde_json::json!({"x": 1})));
+    assert_eq!(failure.predicate, "TraceNext");
+    assert!(failure.candidate_actions.is_empty());
+}
+
+#[test]
+fn test_localized_prefixes_keep_the_full_trace_type() {
+    let dir = tempfile::tempdir().unwrap();
+    let types = dir.path().join("types.log");
+    // Record the row type of every staged trace; accept at most three rows.
+    let tail = format!(
+        r#"if [ "$1" = "typecheck" ]; then
+  echo 'Type checker [OK]'
+  exit 0
+fi
+for spec; do :; done
+grep '@type: () => Seq({{' "$(dirname "$spec")/TraceData.tla" >> "{}"
+for arg in "$@"; do
+  case "$arg" in
+    --length=0|--length=1|--length=2) echo 'The outcome is: Error'; exit 12 ;;
+  esac
+done
+echo 'The outcome is: NoError'
+exit 0"#,
+        types.display()
+    );
+    let bin = fake_apalache_with(dir.path(), &tail);
+    let spec = spec(dir.path());
+
+    // Only rows after the first probed prefix tell the element type of
+    // `log` and observe `y`.
+    let trace = dir.path().join("trace.ndjson");
+    let rows = [
+        r#"{"action": "init", "x": 0, "log": []}"#,
+        r#"{"action": "inc", "x": 1, "log": []}"#,
+        r#"{"action": "inc", "x": 2, "log": []}"#,
+        r#"{"action": "inc", "x": 3, "log": ["a"]}"#,
+        r#"{"action": "inc", "x": 4, "log": ["a"], "y": 7}"#,
+        r#"{"action": "reset", "x": 0, "log": ["a"]}"#,
+    ];
+    std::fs::write(&trace, rows.join("\n")).unwrap();
+
+    let config = TraceValidatorConfig::builder()
+        .trace_spec(spec)
+        .apalache_bin(bin.to_str().unwrap())
+        .localize(true)
+        .partial_observations(true)
+        .preflight(true)
+        .build()
+        .unwrap();
+    let TraceResult::Invalid { failure: Some(failure), .. } = validate_trace(&config, &trace).unwrap() else {
+        panic!("expected a localized failure");
+    };
+    assert_eq!(failure.index, 3);
+
+    let types = std::fs::read_to_string(&types).unwrap();
+    let types: Vec<&str> = types.lines().collect();
+    assert_eq!(types.len(), 3, "full trace and two prefixes");
+    assert!(types[0].contains("log: Seq(Str)") && types[0].contains("y: Some(Int) | None(UNIT)"), "{}", types[0]);
+    assert!(types.iter().all(|ty| *ty == types[0]), "{types:?}");
+    let typechecks = call_log(dir.path()).lines().filter(|l| l.starts_with("typecheck")).count();
+    assert_eq!(typechecks, 1, "pre-flight check runs once");
+}
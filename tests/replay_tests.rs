@@ -18,16 +18,11 @@ impl ExtractState<TestDriver> for TestState {
     }
 }
 
+#[derive(Default)]
 struct TestDriver {
     value: i64,
 }
 
-impl Default for TestDriver {
-    fn default() -> Self {
-        Self { value: 0 }
-    }
-}
-
 impl Driver for TestDriver {
     type State = TestState;
 
@@ -176,3 +171,119 @@ fn test_replay_with_nondet_picks() {
     let result = replay_trace_str(|| DriverWithNondet { value: 0 }, trace_json);
     assert!(result.is_ok(), "Replay failed: {:?}", result.err());
 }
+
+#[test]
+fn test_step_nondet_unwraps_option_variants() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct NoState {}
+
+    impl State for NoState {
+        fn from_spec(_: &itf::Value) -> Result<Self, DriverError> {
+            Ok(NoState {})
+        }
+    }
+
+    impl ExtractState<ProbeDriver> for NoState {
+        fn from_driver(_: &ProbeDriver) -> Result<Self, DriverError> {
+            Ok(NoState {})
+        }
+    }
+
+    struct ProbeDriver;
+
+    impl Driver for ProbeDriver {
+        type State = NoState;
+
+        fn step(&mut self, step: &Step) -> Result<(), DriverError> {
+            assert_eq!(step.nondet("amount"), Some(&itf::Value::Number(5)));
+            assert_eq!(step.nondet("target"), None);
+            assert_eq!(step.nondet("plain"), Some(&itf::Value::String("x".into())));
+            assert_eq!(step.nondet("missing"), None);
+            Ok(())
+        }
+    }
+
+    let trace_json = r###"{
+        "#meta": {"format": "ITF"},
+        "vars": ["action_taken", "nondet_picks"],
+        "states": [
+            {"#meta": {"index": 0}, "action_taken": "init", "nondet_picks": {
+                "amount": {"tag": "Some", "value": 5},
+                "target": {"tag": "None", "value": {}},
+                "plain": "x"
+            }}
+        ]
+    }"###;
+
+    let result = replay_trace_str(|| ProbeDriver, trace_json);
+    assert!(result.is_ok(), "Replay failed: {:?}", result.err());
+}
+
+#[test]
+fn test_replay_unrolls_lasso_loops() {
+    // Layout of an Apalache liveness counterexample: the last state repeats
+    // the state at the loop index.
+    let trace_json = r###"{
+        "#meta": {"format": "ITF", "source": "Counter.tla", "varTypes": {"counter": "Int"}},
+        "vars": ["counter", "action_taken"],
+        "loop": 1,
+        "states": [
+            {"#meta": {"index": 0}, "counter": {"#bigint": "0"}, "action_taken": "init"},
+            {"#meta": {"index": 1}, "counter": {"#bigint": "1"}, "action_taken": "increment"},
+            {"#meta": {"index": 2}, "counter": {"#bigint": "0"}, "action_taken": "decrement"},
+            {"#meta": {"index": 3}, "counter": {"#bigint": "1"}, "action_taken": "increment"}
+        ]
+    }"###;
+    let trace: itf::Trace<itf::Value> = serde_json::from_str(trace_json).unwrap();
+
+    let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let record = std::sync::Arc::clone(&seen);
+    let progress: ReplayProgressFn = Box::new(move |p: ReplayProgress| {
+        assert_eq!(p.trace_meta.loop_index, Some(1));
+        assert_eq!(p.trace_meta.source.as_deref(), Some("Counter.tla"));
+        assert_eq!(p.trace_meta.var_types.get("counter").map(String::as_str), Some("Int"));
+        record.lock().unwrap().push(p.action);
+    });
+
+    let stats = replay_traces_with_options(
+        TestDriver::default,
+        [&trace],
+        ReplayOptions::default().unroll_loops(2),
+        Some(progress),
+    )
+    .unwrap();
+
+    assert_eq!(stats.total_states, 8);
+    assert_eq!(
+        *seen.lock().unwrap(),
+        ["init", "increment", "decrement", "increment", "decrement", "increment", "decrement", "increment"]
+    );
+}
+
+#[test]
+fn test_replay_closes_open_lasso_without_recorded_action() {
+    // The last state differs from the loop state, so the trace does not
+    // record the action of the transition back to it.
+    let trace_json = r###"{
+        "#meta": {"format": "ITF"},
+        "vars": ["counter", "action_taken"],
+        "loop": 1,
+        "states": [
+            {"#meta": {"index": 0}, "counter": {"#bigint": "0"}, "action_taken": "init"},
+            {"#meta": {"index": 1}, "counter": {"#bigint": "1"}, "action_taken": "increment"},
+            {"#meta": {"index": 2}, "counter": {"#bigint": "2"}, "action_taken": "increment"}
+        ]
+    }"###;
+    let trace: itf::Trace<itf::Value> = serde_json::from_str(trace_json).unwrap();
+
+    let stats = replay_traces_with_options(TestDriver::default, [&trace], ReplayOptions::default(), None).unwrap();
+    assert_eq!(stats.total_states, 3);
+
+    let err = replay_traces_with_options(TestDriver::default, [&trace], ReplayOptions::default().unroll_loops(1), None)
+        .unwrap_err();
+    let Error::Step(StepError::StepExecution { context, action, .. }) = err else {
+        panic!("expected the closing transition to fail, got {err:?}");
+    };
+    assert!(matches!(context, StepContext::Replay { trace: 0, state: 3 }), "{context:?}");
+    assert_eq!(action, "unknown");
+}
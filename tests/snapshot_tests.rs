@@ -239,3 +239,72 @@ fn test_trace_actions_sequence() {
     assert!(tla_module.contains(r#""step""#));
     assert!(tla_module.contains(r#""stop""#));
 }
+
+#[test]
+fn test_itf_encodings_to_tla() {
+    let dir = tempfile::tempdir().unwrap();
+    let trace_path = write_trace(
+        &dir,
+        "encoded.ndjson",
+        &[
+            r##"{"action": "init", "ids": {"#set": [1, 2]}, "owners": {"#map": [[1, "a"]]}, "pair": {"#tup": [1, true]}, "big": {"#bigint": "18446744073709551616"}, "mode": {"tag": "Idle"}}"##,
+            r##"{"action": "start", "ids": {"#set": []}, "owners": {"#map": []}, "pair": {"#tup": [2, false]}, "big": {"#bigint": "0"}, "mode": {"tag": "Busy", "value": 3}}"##,
+        ],
+    );
+
+    let (tla_module, count) = ndjson_to_tla_module(&trace_path).unwrap();
+
+    assert_eq!(count, 2);
+    assert!(tla_module.contains("EXTENDS Integers, Sequences, TLC, Variants"));
+    assert!(tla_module.contains(
+        "Seq({action: Str, big: Int, ids: Set(Int), mode: Busy(Int) | Idle(UNIT), owners: Int -> Str, pair: <<Int, Bool>>})"
+    ));
+    assert!(tla_module.contains(
+        r#"[action |-> "init", big |-> 18446744073709551616, ids |-> {1, 2}, mode |-> Variant("Idle", UNIT), owners |-> (1 :> "a"), pair |-> <<1, TRUE>>]"#
+    ));
+    assert!(tla_module.contains(r#"ids |-> {}, mode |-> Variant("Busy", 3), owners |-> [x \in {} |-> x]"#));
+}
+
+#[test]
+fn test_malformed_itf_encoding_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let trace_path = write_trace(&dir, "bad.ndjson", &[r##"{"action": "init", "ids": {"#set": 1}}"##]);
+
+    let err = ndjson_to_tla_module(&trace_path).unwrap_err().to_string();
+    assert!(err.contains("#set must hold an array"), "got: {err}");
+}
+
+#[test]
+fn test_types_merged_across_rows() {
+    let dir = tempfile::tempdir().unwrap();
+    let trace_path = write_trace(
+        &dir,
+        "merged.ndjson",
+        &[
+            r#"{"action": "init", "queue": [], "leader": 1}"#,
+            r#"{"action": "push", "queue": ["a"], "leader": 2}"#,
+        ],
+    );
+
+    let (tla_module, _) = ndjson_to_tla_module(&trace_path).unwrap();
+
+    assert!(tla_module.contains("Seq({action: Str, leader: Int, queue: Seq(Str)})"));
+    assert!(tla_module.contains(r#"[action |-> "init", leader |-> 1, queue |-> <<>>]"#));
+    assert!(tla_module.contains(r#"[action |-> "push", leader |-> 2, queue |-> <<"a">>]"#));
+}
+
+#[test]
+fn test_type_conflict_reports_field_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let trace_path = write_trace(
+        &dir,
+        "conflict.ndjson",
+        &[
+            r#"{"action": "init", "node": {"term": 1}}"#,
+            r#"{"action": "vote", "node": {"term": "two"}}"#,
+        ],
+    );
+
+    let err = ndjson_to_tla_module(&trace_path).unwrap_err().to_string();
+    assert!(err.contains("line 2, field 'node.term': expected Int, found Str"), "got: {err}");
+}
@@ -37,6 +37,33 @@ fn test_emitter_creates_ndjson() {
     assert_eq!(line2["counter"], 1);
 }
 
+#[test]
+fn test_emitter_writes_itf_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("trace.ndjson");
+    let itf_path = dir.path().join("trace.itf.json");
+
+    let mut emitter = StateEmitter::new(&path).unwrap().with_itf_output(&itf_path);
+    emitter.emit("init", &SimpleState { counter: 0 }).unwrap();
+    emitter
+        .emit("increment", &SimpleState { counter: 1 })
+        .unwrap();
+    emitter.finish().unwrap();
+
+    let trace: itf::Trace<itf::Value> =
+        serde_json::from_str(&std::fs::read_to_string(&itf_path).unwrap()).unwrap();
+    assert_eq!(trace.vars, vec!["counter".to_string()]);
+    assert_eq!(trace.states.len(), 2);
+    assert_eq!(trace.states[1].meta.other["action"], "increment");
+    let itf::Value::Record(ref fields) = trace.states[1].value else {
+        panic!("expected a record state");
+    };
+    assert_eq!(
+        fields.get("counter"),
+        Some(&itf::Value::BigInt(itf::value::BigInt::new(1)))
+    );
+}
+
 #[test]
 fn test_emitter_rejects_non_object() {
     let dir = tempfile::tempdir().unwrap();
@@ -186,3 +213,45 @@ fn test_validation_handles_nested_objects() {
         );
     }
 }
+
+#[test]
+fn test_merge_traces_by_lamport_clock() {
+    let dir = tempfile::tempdir().unwrap();
+    let n1 = dir.path().join("n1.ndjson");
+    let n2 = dir.path().join("n2.ndjson");
+    std::fs::write(
+        &n1,
+        "{\"action\": \"init\", \"term\": 0}\n{\"action\": \"elect\", \"term\": 1, \"clock\": 1}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        &n2,
+        "{\"action\": \"init\", \"term\": 0}\n{\"action\": \"follow\", \"term\": 1, \"clock\": 2}\n",
+    )
+    .unwrap();
+
+    let config = TraceMergeConfig::builder()
+        .nodes(std::collections::BTreeMap::from([("n1".to_string(), n1), ("n2".to_string(), n2)]))
+        .build()
+        .unwrap();
+    let merged = merge_traces(&config).unwrap();
+    assert_eq!(merged.traces.len(), 1);
+    assert!(merged.exhaustive);
+
+    let rows: Vec<serde_json::Value> = std::fs::read_to_string(&merged.traces[0])
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            serde_json::json!({"action": "init", "node": "", "term": {"#map": [["n1", 0], ["n2", 0]]}}),
+            serde_json::json!({"action": "elect", "node": "n1", "term": {"#map": [["n1", 1], ["n2", 0]]}}),
+            serde_json::json!({"action": "follow", "node": "n2", "term": {"#map": [["n1", 1], ["n2", 1]]}}),
+        ]
+    );
+
+    let (tla_module, _) = ndjson_to_tla_module(&merged.traces[0]).unwrap();
+    assert!(tla_module.contains("Seq({action: Str, node: Str, term: Str -> Int})"));
+}